@@ -0,0 +1,218 @@
+use clap::{Arg, Command};
+
+use crate::shell_quote;
+
+/// Shells `ws completions` writes a script for.
+#[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+/// Names `ws complete` prints for the scripts' dynamic completion.
+#[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Names {
+    Projects,
+    Sessions,
+    /// Sessions, then projects.
+    All,
+}
+
+/// Subcommands whose `project` argument names a project that isn't in the
+/// cache: a new one, or an archived one.
+const NOT_CACHED: &[&str] = &["new", "unarchive"];
+
+/// What a subcommand's positional arguments complete to, as the
+/// `ws complete` argument, if anything.
+fn dynamic_names(command: &Command) -> Option<&'static str> {
+    let name = command.get_name();
+    command
+        .get_positionals()
+        .find_map(|arg| match arg.get_id().as_str() {
+            "name" if name == "switch" => Some("all"),
+            "project" if !NOT_CACHED.contains(&name) => Some("projects"),
+            "session" | "sessions" => Some("sessions"),
+            _ => None,
+        })
+}
+
+/// Long options (`--name`) of a built command, its global ones included.
+fn long_options(command: &Command) -> Vec<String> {
+    command
+        .get_arguments()
+        .filter_map(Arg::get_long)
+        .map(|long| format!("--{}", long))
+        .collect()
+}
+
+/// Words completed after `command`: its options, nested subcommands and
+/// the possible values of value-enum positionals (e.g. `ws import sesh`).
+fn static_words(command: &Command) -> Vec<String> {
+    let mut words = long_options(command);
+    words.extend(
+        command
+            .get_subcommands()
+            .map(|sub| sub.get_name().to_string()),
+    );
+    for arg in command.get_positionals() {
+        words.extend(
+            arg.get_possible_values()
+                .iter()
+                .map(|value| value.get_name().to_string()),
+        );
+    }
+    words
+}
+
+fn about(command: &Command) -> String {
+    command
+        .get_about()
+        .map(|about| about.to_string())
+        .unwrap_or_default()
+}
+
+fn visible_subcommands(cli: &Command) -> impl Iterator<Item = &Command> {
+    cli.get_subcommands().filter(|sub| !sub.is_hide_set())
+}
+
+/// The completion script for `shell`, built from the CLI definition.
+/// Project and session names come from `ws complete` at completion time.
+pub fn script(shell: Shell, cli: &Command) -> String {
+    match shell {
+        Shell::Bash => bash(cli),
+        Shell::Zsh => zsh(cli),
+        Shell::Fish => fish(cli),
+    }
+}
+
+fn bash(cli: &Command) -> String {
+    let subcommands: Vec<&str> = visible_subcommands(cli).map(Command::get_name).collect();
+    let mut script = String::from(
+        "_ws() {\n    local cur=${COMP_WORDS[COMP_CWORD]} sub= i words\n    \
+         for ((i = 1; i < COMP_CWORD; i++)); do\n        \
+         case ${COMP_WORDS[i]} in -*) ;; *) sub=${COMP_WORDS[i]}; break ;; esac\n    \
+         done\n    case $sub in\n",
+    );
+    script.push_str(&format!(
+        "        '') words=\"{} {}\" ;;\n",
+        subcommands.join(" "),
+        long_options(cli).join(" ")
+    ));
+    for sub in visible_subcommands(cli) {
+        let mut words = static_words(sub).join(" ");
+        if let Some(names) = dynamic_names(sub) {
+            words = format!("$(ws complete {} 2>/dev/null) {}", names, words);
+        }
+        script.push_str(&format!(
+            "        {}) words=\"{}\" ;;\n",
+            sub.get_name(),
+            words
+        ));
+    }
+    script.push_str(
+        "    esac\n    COMPREPLY=($(compgen -W \"$words\" -- \"$cur\"))\n}\n\
+         complete -F _ws ws\n",
+    );
+    script
+}
+
+fn zsh(cli: &Command) -> String {
+    let mut script =
+        String::from("#compdef ws\n\n_ws() {\n    local -a subcommands\n    subcommands=(\n");
+    for sub in visible_subcommands(cli) {
+        script.push_str(&format!(
+            "        {}\n",
+            shell_quote(&format!(
+                "{}:{}",
+                sub.get_name(),
+                about(sub).replace(':', "\\:")
+            ))
+        ));
+    }
+    script.push_str(
+        "    )\n    local sub= i\n    for ((i = 2; i < CURRENT; i++)); do\n        \
+         [[ $words[i] != -* ]] && { sub=$words[i]; break; }\n    done\n    \
+         if [[ -z $sub ]]; then\n        \
+         if [[ $PREFIX == -* ]]; then\n            compadd -- ",
+    );
+    script.push_str(&long_options(cli).join(" "));
+    script.push_str(
+        "\n        else\n            _describe command subcommands\n        fi\n        \
+         return\n    fi\n    case $sub in\n",
+    );
+    for sub in visible_subcommands(cli) {
+        script.push_str(&format!("        {})\n", sub.get_name()));
+        if let Some(names) = dynamic_names(sub) {
+            script.push_str(&format!(
+                "            [[ $PREFIX != -* ]] && compadd -- ${{(f)\"$(ws complete {} 2>/dev/null)\"}}\n",
+                names
+            ));
+        }
+        let words = static_words(sub);
+        if !words.is_empty() {
+            script.push_str(&format!("            compadd -- {}\n", words.join(" ")));
+        }
+        script.push_str("            ;;\n");
+    }
+    script.push_str("    esac\n}\n\ncompdef _ws ws\n");
+    script
+}
+
+fn fish(cli: &Command) -> String {
+    let mut script = String::from("complete -c ws -f\n");
+    for arg in cli.get_arguments() {
+        if let Some(long) = arg.get_long() {
+            let help = arg.get_help().map(|h| h.to_string()).unwrap_or_default();
+            script.push_str(&format!(
+                "complete -c ws -l {} -d {}\n",
+                long,
+                shell_quote(&help)
+            ));
+        }
+    }
+    for sub in visible_subcommands(cli) {
+        let name = sub.get_name();
+        script.push_str(&format!(
+            "complete -c ws -n __fish_use_subcommand -a {} -d {}\n",
+            name,
+            shell_quote(&about(sub))
+        ));
+        let seen = format!("'__fish_seen_subcommand_from {}'", name);
+        if let Some(names) = dynamic_names(sub) {
+            script.push_str(&format!(
+                "complete -c ws -n {} -a '(ws complete {} 2>/dev/null)'\n",
+                seen, names
+            ));
+        }
+        for nested in sub.get_subcommands() {
+            script.push_str(&format!(
+                "complete -c ws -n {} -a {} -d {}\n",
+                seen,
+                nested.get_name(),
+                shell_quote(&about(nested))
+            ));
+        }
+        for arg in sub.get_positionals() {
+            for value in arg.get_possible_values() {
+                script.push_str(&format!(
+                    "complete -c ws -n {} -a {}\n",
+                    seen,
+                    value.get_name()
+                ));
+            }
+        }
+        for arg in sub.get_arguments().filter(|arg| !arg.is_global_set()) {
+            if let Some(long) = arg.get_long() {
+                let help = arg.get_help().map(|h| h.to_string()).unwrap_or_default();
+                script.push_str(&format!(
+                    "complete -c ws -n {} -l {} -d {}\n",
+                    seen,
+                    long,
+                    shell_quote(&help)
+                ));
+            }
+        }
+    }
+    script
+}
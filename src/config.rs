@@ -0,0 +1,1070 @@
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::Result;
+
+const CONFIG_ENV: &str = "WS_CONFIG";
+/// Directory next to the config file with named layouts, one `NAME.toml`
+/// each.
+const LAYOUTS_DIR: &str = "layouts";
+/// Exclude patterns kept in a workspace root itself, one per line.
+const IGNORE_FILE: &str = ".wsignore";
+/// Top-level table of per-hostname config overrides.
+const HOST_TABLE: &str = "host";
+const RECENT_DIRS_LIMIT: usize = 10;
+const DEFAULT_WORKSPACE: &str = "~/workspace";
+const DEFAULT_SESSION_NAME: &str = "{name}";
+const DEFAULT_PREFIX_TEMPLATE: &str = "{alias}-{name}";
+const DEFAULT_PROJECT_DISPLAY: &str = "{category}/{name}";
+const ORG_TTL_SECONDS: i64 = 6 * 3600;
+const HISTORY_SIZE: usize = 10;
+const DEFAULT_HOME_SESSION: &str = "home";
+/// Files the home session's notes window opens, relative to its directory.
+const DEFAULT_HOME_NOTES: &[&str] = &["NOTES.md", "TODO.md"];
+/// Environment variables that override the `[layout]` commands.
+const SHELL_ENV: &str = "WS_SHELL";
+const EDITOR_ENV: &str = "WS_EDITOR";
+const FILE_MANAGER_ENV: &str = "WS_FILE_MANAGER";
+/// Git repositories at category/project depth that make a directory look
+/// like a workspace.
+const WORKSPACE_MIN_REPOS: usize = 3;
+
+/// Directory inside each root that `ws categories archive` moves categories
+/// to. It is never scanned.
+pub const ARCHIVE_DIR: &str = ".archive";
+
+/// Directory inside a category that holds the worktrees `ws branch` creates.
+/// It is never scanned.
+pub const WORKTREE_DIR: &str = ".worktrees";
+
+/// Apps available to `ws open --app` without configuration.
+const DEFAULT_APPS: &[(&str, &str)] = &[("code", "code {path}"), ("idea", "idea {path}")];
+/// `ws new` templates available without configuration; `[templates]`
+/// entries of the same name replace them.
+const DEFAULT_TEMPLATES: &[(&str, &str)] = &[
+    ("empty", "mkdir {name} && git -C {name} init -q"),
+    ("rust", "cargo new {name}"),
+    (
+        "node",
+        "mkdir {name} && cd {name} && git init -q && npm init -y >/dev/null",
+    ),
+];
+
+/// Directory names skipped while scanning unless `scan.default_excludes` is
+/// turned off. Matching is on the directory name, with `*` wildcards.
+const DEFAULT_EXCLUDES: &[&str] = &[
+    ".git",
+    ".Trash",
+    ".Trash-*",
+    "node_modules",
+    ".venv",
+    "venv",
+    "__pycache__",
+    "Library",
+    "OneDrive*",
+    "*conflicted copy*",
+];
+
+/// Files and directories that make a directory a project with
+/// `scan.mode = "markers"`.
+const DEFAULT_MARKERS: &[&str] = &[
+    ".git",
+    "Cargo.toml",
+    "package.json",
+    "go.mod",
+    "pyproject.toml",
+    "Gemfile",
+    "mix.exs",
+    "pom.xml",
+    "build.gradle",
+];
+const MARKER_SCAN_DEPTH: usize = 4;
+
+/// User configuration read from `~/.config/ws/config.toml`. Every section is
+/// optional; a missing file behaves like an empty one.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Multiplexer sessions are kept in.
+    pub backend: Backend,
+    /// Workspace roots to scan; `~/workspace` when none are configured.
+    pub workspaces: Vec<WorkspaceRoot>,
+    /// GitHub/GitLab orgs whose repositories are offered in the picker.
+    pub orgs: Vec<OrgConfig>,
+    pub session: SessionConfig,
+    /// Scaffolding templates for `ws new`, keyed by name.
+    pub templates: BTreeMap<String, ProjectTemplate>,
+    pub recent_dirs: RecentDirsConfig,
+    pub home: HomeConfig,
+    pub attach: AttachConfig,
+    pub scan: ScanConfig,
+    pub picker: PickerConfig,
+    pub output: OutputConfig,
+    pub on_switch: SwitchConfig,
+    pub hooks: HooksConfig,
+    pub pause: PauseConfig,
+    pub history: HistoryConfig,
+    pub display: DisplayConfig,
+    pub layout: LayoutConfig,
+    /// GUI editors for `ws open --app`, as shell commands where `{path}` is
+    /// replaced by the quoted project path. `code` and `idea` are built in.
+    pub apps: BTreeMap<String, String>,
+    /// Root found from the current directory because none is configured and
+    /// the default doesn't exist; see `save_workspace`.
+    #[serde(skip)]
+    pub detected_workspace: Option<PathBuf>,
+    /// Where named layouts are read from, next to the config file.
+    #[serde(skip)]
+    pub layouts_dir: PathBuf,
+    /// `--min-depth` and `--max-depth`, kept for roots that replace the
+    /// configured ones later (`--workspace`).
+    #[serde(skip)]
+    depth_override: (Option<usize>, Option<usize>),
+}
+
+/// How projects are shown in the picker and in command output.
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DisplayConfig {
+    /// Supports `{alias}`, `{category}`, `{name}` and `{branch}` (the checked
+    /// out git branch).
+    pub project: String,
+}
+
+impl Default for DisplayConfig {
+    fn default() -> Self {
+        Self {
+            project: DEFAULT_PROJECT_DISPLAY.to_string(),
+        }
+    }
+}
+
+/// What the windows of a new session run. `WS_SHELL`, `WS_EDITOR` and
+/// `WS_FILE_MANAGER` override the commands, and the `--window-shell`,
+/// `--editor` and `--file-manager` flags override those.
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LayoutConfig {
+    /// Shell the editor window runs the editor from and returns to.
+    pub shell: String,
+    /// Terminal editor started in the editor window; empty for just a shell.
+    pub editor: String,
+    /// Command of the files window; empty for a plain shell.
+    pub file_manager: String,
+    pub editor_window: String,
+    pub files_window: String,
+    /// Named layout for projects whose `.ws.toml` doesn't pick one; the
+    /// built-in editor/files layout when unset.
+    pub default: Option<String>,
+    /// Named layouts of projects without a `.ws.toml` layout, by project
+    /// directory; `ws import tmuxinator` and `ws import tmuxp` add these.
+    pub projects: BTreeMap<String, String>,
+    /// Per project type (`rust`, `node`, ...), new names for layout windows,
+    /// e.g. `files = "server"` under `node`.
+    pub window_names: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+impl Default for LayoutConfig {
+    fn default() -> Self {
+        Self {
+            shell: "fish".to_string(),
+            editor: "hx".to_string(),
+            file_manager: "fx".to_string(),
+            editor_window: "editor".to_string(),
+            files_window: "files".to_string(),
+            default: None,
+            projects: BTreeMap::new(),
+            window_names: BTreeMap::new(),
+        }
+    }
+}
+
+/// Windows of a new session, as written in `layouts/NAME.toml` or in a
+/// project's `.ws.toml`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LayoutSpec {
+    pub windows: Vec<WindowSpec>,
+    /// Name of the window selected once the session exists; the first one
+    /// when unset.
+    #[serde(default)]
+    pub focus: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WindowSpec {
+    pub name: String,
+    /// Defaults to one past the previous window's index.
+    #[serde(default)]
+    pub index: Option<u32>,
+    /// Run by the default shell; empty starts the shell itself.
+    #[serde(default)]
+    pub command: String,
+    /// Working directory, relative to the project's.
+    #[serde(default)]
+    pub path: Option<String>,
+    /// Panes split off the window's first one, in order.
+    #[serde(default)]
+    pub panes: Vec<PaneSpec>,
+    /// tmux layout applied once the panes exist, e.g. `main-vertical`.
+    #[serde(default)]
+    pub arrange: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PaneSpec {
+    #[serde(default)]
+    pub command: String,
+    /// Working directory, relative to the project's.
+    #[serde(default)]
+    pub path: Option<String>,
+    /// Split side by side instead of one above the other.
+    #[serde(default)]
+    pub horizontal: bool,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HistoryPolicy {
+    /// Keep only the latest visit to each session.
+    #[default]
+    Collapse,
+    /// Keep every visit with its timestamp, e.g. for reporting.
+    Every,
+}
+
+/// Session switch history, used by `ws back`.
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HistoryConfig {
+    pub policy: HistoryPolicy,
+    pub max_entries: usize,
+    /// Drop visits older than this many days.
+    pub max_age_days: Option<u64>,
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        Self {
+            policy: HistoryPolicy::Collapse,
+            max_entries: HISTORY_SIZE,
+            max_age_days: None,
+        }
+    }
+}
+
+/// How `ws pause` / `ws resume` quiet a session.
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PauseConfig {
+    /// Send SIGSTOP / SIGCONT to the processes running in the session. A
+    /// pane shell's foreground job keeps running, since a job-control shell
+    /// would otherwise put it in the background; what the job runs is
+    /// stopped.
+    pub signal: bool,
+    /// Shell commands run on pause and resume, with `WS_SESSION` set.
+    pub command: Option<String>,
+    pub resume_command: Option<String>,
+}
+
+impl Default for PauseConfig {
+    fn default() -> Self {
+        Self {
+            signal: true,
+            command: None,
+            resume_command: None,
+        }
+    }
+}
+
+/// Lets the terminal and other tools follow the active project.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SwitchConfig {
+    /// Report the project directory to the terminal with OSC 7.
+    pub osc7: bool,
+    /// Terminal title set with OSC 0, with `{session}`, `{name}`,
+    /// `{category}` and `{alias}` filled in.
+    pub title: Option<String>,
+    /// Shell command run on every switch, with `WS_SESSION` and
+    /// `WS_PROJECT_PATH` in its environment.
+    pub command: Option<String>,
+}
+
+/// Shell commands run around a session's lifecycle, in the project
+/// directory and with `WS_SESSION` and `WS_PROJECT_PATH` in their
+/// environment, e.g. to start `docker compose` with the session and stop it
+/// with the session.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HooksConfig {
+    /// Run once ws created a session and its windows.
+    pub on_session_create: Option<String>,
+    /// Run every time ws switches to or attaches a session, before
+    /// attaching.
+    pub on_session_attach: Option<String>,
+    /// Run before ws kills a session, while it still exists.
+    pub on_session_kill: Option<String>,
+}
+
+/// Where the one-line result of a mutating command ("switched to api
+/// (created)") goes.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SummaryMode {
+    /// `tmux display-message` inside tmux, stdout otherwise.
+    #[default]
+    Auto,
+    Stdout,
+    Tmux,
+    Off,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OutputConfig {
+    pub summary: SummaryMode,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PickerConfig {
+    /// Number the first nine rows and pick them with `1`..`9` while the
+    /// query is empty. skim can't fall back to typing a bound key, so digits
+    /// can no longer be typed into the query.
+    pub quick_select: bool,
+    /// Leave the current session, its group and its project out of the
+    /// picker; `ws pick --exclude-current-project` turns it on per call.
+    pub exclude_current: bool,
+    /// App that `ctrl-o` opens the highlighted project in, instead of a
+    /// tmux session.
+    pub app: Option<String>,
+    /// Order of the project rows: frecency (the default), alphabetical or
+    /// history.
+    pub sort: ProjectSort,
+    /// Print a numbered list and read the choice instead of the full-screen
+    /// picker, e.g. for screen readers; `--no-tui` turns it on per call.
+    pub plain: bool,
+    /// Full-screen picker to use; `--picker` overrides it per call.
+    pub finder: Finder,
+    /// Show a preview pane in `ws pick`: a project's git status and README,
+    /// or what a session's active pane shows.
+    pub preview: bool,
+    /// Start the preview with the CI state and open pull requests of the
+    /// project's GitHub repository, as `ws health` prints them. Needs
+    /// `GITHUB_TOKEN`.
+    pub health: bool,
+    /// Show each project's branch, uncommitted changes and commits ahead
+    /// of or behind upstream in `ws pick`. Read on every pick, in parallel.
+    pub git_status: bool,
+    pub previews: PreviewCommands,
+}
+
+/// Preview commands that replace the built-in previews of one kind of
+/// picker row, run by the shell in the row's directory when it has one.
+/// `{path}` is replaced by the quoted directory and `{name}` by the quoted
+/// project or session name. An empty command shows no preview.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PreviewCommands {
+    pub project: Option<String>,
+    pub session: Option<String>,
+    /// Recent directories outside the workspace.
+    pub directory: Option<String>,
+    /// Org repositories not cloned yet; `{path}` is where they would go.
+    pub clone: Option<String>,
+    pub home: Option<String>,
+}
+
+/// The program behind the full-screen picker.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Finder {
+    /// skim, built into ws.
+    #[default]
+    Skim,
+    /// An external `fzf` (0.45 or later), which reads the user's
+    /// `FZF_DEFAULT_OPTS`.
+    Fzf,
+}
+
+/// Order of the project rows in the picker.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProjectSort {
+    /// Most frecent first: how often a project was opened, weighted by how
+    /// recently. Projects never opened follow alphabetically.
+    #[default]
+    Frecency,
+    /// By category, then name.
+    Alphabetical,
+    /// Projects with a session in history first, each group alphabetical.
+    History,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ScanConfig {
+    /// Extra patterns to skip: a directory name, or with a `/` a path
+    /// relative to the workspace root (`archive/*`).
+    pub exclude: Vec<String>,
+    /// Whether the built-in exclusions (`node_modules`, `.Trash`, ...) apply.
+    pub default_excludes: bool,
+    pub mode: ScanMode,
+    /// With the `markers` mode, names whose presence makes a directory a
+    /// project.
+    pub markers: Vec<String>,
+    /// With the `markers` mode, how deep below the root projects are looked
+    /// for.
+    pub max_depth: usize,
+}
+
+impl Default for ScanConfig {
+    fn default() -> Self {
+        Self {
+            exclude: Vec::new(),
+            default_excludes: true,
+            mode: ScanMode::default(),
+            markers: DEFAULT_MARKERS.iter().map(|m| m.to_string()).collect(),
+            max_depth: MARKER_SCAN_DEPTH,
+        }
+    }
+}
+
+/// How a workspace root is searched for projects.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScanMode {
+    /// Every directory two levels down is a project, in the category named
+    /// after the directory above it.
+    #[default]
+    Depth,
+    /// Directories containing one of the markers are projects, at any depth
+    /// up to `max_depth`; the path from the root to their parent is the
+    /// category. Projects aren't searched for projects of their own.
+    Markers,
+}
+
+impl ScanConfig {
+    /// These settings plus the patterns in the root's `.wsignore`, if it has
+    /// one. Blank lines and lines starting with `#` are skipped.
+    pub fn for_root(&self, root: &Path) -> Self {
+        let mut scan = self.clone();
+        if let Ok(contents) = fs::read_to_string(root.join(IGNORE_FILE)) {
+            scan.exclude.extend(
+                contents
+                    .lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                    .map(|line| line.trim_matches('/').to_string()),
+            );
+        }
+        scan
+    }
+
+    /// Whether the directory at `relative` (to the workspace root, with `/`
+    /// separators) is skipped. Patterns without a `/` match its name at any
+    /// depth, the others the whole relative path.
+    pub fn is_excluded(&self, relative: &str) -> bool {
+        let name = relative.rsplit('/').next().unwrap_or(relative);
+        if name == ARCHIVE_DIR || name == WORKTREE_DIR {
+            return true;
+        }
+        let defaults = if self.default_excludes {
+            DEFAULT_EXCLUDES
+        } else {
+            &[]
+        };
+        defaults
+            .iter()
+            .copied()
+            .chain(self.exclude.iter().map(|p| p.as_str()))
+            .any(|pattern| {
+                if pattern.contains('/') {
+                    wildcard_match(pattern, relative)
+                } else {
+                    wildcard_match(pattern, name)
+                }
+            })
+    }
+}
+
+/// Merges the `[host."NAME"]` table matching this machine's hostname (or its
+/// first label, e.g. `workmac` for `workmac.local`) into the rest of the
+/// config and drops the `host` tables. Tables merge key by key; anything
+/// else, including arrays like `workspaces`, is replaced.
+fn apply_host_overrides(table: &mut toml::Table) -> Result<()> {
+    let Some(hosts) = table.remove(HOST_TABLE) else {
+        return Ok(());
+    };
+    let toml::Value::Table(mut hosts) = hosts else {
+        return Err(format!("`{}` must be a table of hostnames", HOST_TABLE).into());
+    };
+
+    let hostname = crate::inventory::hostname();
+    let short = hostname.split('.').next().unwrap_or(&hostname).to_string();
+    let overrides = hosts.remove(&hostname).or_else(|| hosts.remove(&short));
+    match overrides {
+        Some(toml::Value::Table(overrides)) => merge_tables(table, overrides),
+        Some(_) => return Err(format!("`{}.\"{}\"` must be a table", HOST_TABLE, hostname).into()),
+        None => {}
+    }
+    Ok(())
+}
+
+fn merge_tables(base: &mut toml::Table, overrides: toml::Table) {
+    for (key, value) in overrides {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(value)) => {
+                merge_tables(base, value)
+            }
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Where entries of the `[table]` with this header line go in `contents`:
+/// right after the header, if there is one.
+fn table_end(contents: &str, header: &str) -> Option<usize> {
+    let mut offset = 0;
+    for line in contents.split_inclusive('\n') {
+        offset += line.len();
+        if line.trim() == header {
+            return Some(offset);
+        }
+    }
+    None
+}
+
+/// The closest directory at or above `start` with enough git repositories
+/// laid out as `<category>/<project>` to pass for a workspace.
+fn detect_workspace(start: &Path) -> Option<PathBuf> {
+    start
+        .ancestors()
+        .filter(|dir| dir.parent().is_some())
+        .find(|dir| count_repositories(dir) >= WORKSPACE_MIN_REPOS)
+        .map(Path::to_path_buf)
+}
+
+fn count_repositories(dir: &Path) -> usize {
+    let Ok(categories) = fs::read_dir(dir) else {
+        return 0;
+    };
+    categories
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| !entry.file_name().to_string_lossy().starts_with('.'))
+        .filter_map(|entry| fs::read_dir(entry.path()).ok())
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().join(".git").exists())
+        .count()
+}
+
+/// Matches `name` against `pattern`, where `*` matches any run of characters.
+fn wildcard_match(pattern: &str, name: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or("");
+    let Some(mut rest) = name.strip_prefix(first) else {
+        return false;
+    };
+
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        return rest.is_empty();
+    };
+
+    for part in middle {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    rest.len() >= last.len() && rest.ends_with(last)
+}
+
+/// A directory of `<category>/<project>` directories, or a file listing
+/// project paths (one per line, or a JSON array of strings).
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WorkspaceRoot {
+    pub path: String,
+    /// Short name for the root, used in session name prefixes. Defaults to
+    /// the root directory's name.
+    #[serde(default)]
+    pub alias: String,
+    /// How many levels below the root projects sit: 2 for
+    /// `<category>/<project>` unless set, 1 for a flat directory of
+    /// projects. With `scan.mode = "markers"` they bound where markers are
+    /// looked for instead.
+    #[serde(default)]
+    pub min_depth: Option<usize>,
+    #[serde(default)]
+    pub max_depth: Option<usize>,
+}
+
+impl WorkspaceRoot {
+    pub fn new(path: &str) -> Self {
+        let mut root = Self {
+            path: path.to_string(),
+            alias: String::new(),
+            min_depth: None,
+            max_depth: None,
+        };
+        root.normalize();
+        root
+    }
+
+    /// Identifies the root in the project cache: its path, plus the depths
+    /// when they are set, so scanning at other depths invalidates the cache.
+    pub fn cache_key(&self) -> String {
+        match (self.min_depth, self.max_depth) {
+            (None, None) => self.path.clone(),
+            (min, max) => format!(
+                "{} (depth {}-{})",
+                self.path,
+                min.map_or("".to_string(), |d| d.to_string()),
+                max.map_or("".to_string(), |d| d.to_string())
+            ),
+        }
+    }
+
+    fn normalize(&mut self) {
+        self.path = shellexpand::tilde(&self.path).to_string();
+        if self.alias.is_empty() {
+            let path = Path::new(&self.path);
+            let name = if self.is_list() {
+                path.file_stem()
+            } else {
+                path.file_name()
+            };
+            self.alias = name
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default();
+        }
+    }
+
+    /// Whether the root is a file listing project paths rather than a
+    /// directory to scan.
+    pub fn is_list(&self) -> bool {
+        Path::new(&self.path).is_file()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OrgProvider {
+    Github,
+    Gitlab,
+}
+
+/// A GitHub org/user or GitLab group. Its repositories are listed through
+/// the provider's API and cloned into `path/<repo>` when first picked.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OrgConfig {
+    pub provider: OrgProvider,
+    pub name: String,
+    pub path: String,
+    /// API host for self-hosted instances, e.g. `gitlab.example.com`.
+    #[serde(default)]
+    pub host: Option<String>,
+    /// Clone over ssh instead of https.
+    #[serde(default)]
+    pub ssh: bool,
+    /// Seconds the repository listing is cached for.
+    #[serde(default = "default_org_ttl")]
+    pub ttl: i64,
+}
+
+fn default_org_ttl() -> i64 {
+    ORG_TTL_SECONDS
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SessionConfig {
+    /// Template for session names, with `{alias}`, `{category}` and
+    /// `{name}`. Projects whose sessions would share a name get their
+    /// category (and root alias) added instead.
+    pub name: String,
+    /// Name sessions with `prefix_template` instead of `name` when the
+    /// project has a root, so projects from different roots don't collide.
+    pub prefix: bool,
+    /// Supports `{alias}`, `{category}` and `{name}`.
+    pub prefix_template: String,
+    /// Give each project its own shell history by setting `HISTFILE` and
+    /// `fish_history` in the environment of new sessions.
+    pub history: bool,
+    /// History file for `history`, with the same placeholders as
+    /// `prefix_template`. Defaults to one file per project under the ws
+    /// data directory.
+    pub history_file: Option<String>,
+    /// Most sessions opened from projects to keep running. Creating one more
+    /// snapshots and kills the least recently attached detached ones.
+    pub max_sessions: Option<usize>,
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        Self {
+            name: DEFAULT_SESSION_NAME.to_string(),
+            prefix: false,
+            prefix_template: DEFAULT_PREFIX_TEMPLATE.to_string(),
+            history: false,
+            history_file: None,
+            max_sessions: None,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    #[default]
+    Tmux,
+    /// Sessions, attach and layouts only: pause, gc, snapshots, groups,
+    /// tasks and the status line need tmux.
+    Zellij,
+    /// WezTerm workspaces, with the same limits as zellij.
+    Wezterm,
+}
+
+/// What to do when a session should be attached from outside tmux.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum AttachMode {
+    /// Replace the ws process with `tmux attach-session`.
+    #[default]
+    Exec,
+    /// Run `attach.terminal` to open the session in a new terminal window.
+    Terminal,
+    /// Print the attach command instead of running it.
+    Print,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AttachConfig {
+    pub mode: AttachMode,
+    /// Terminal launcher for `mode = "terminal"`; `{command}` is replaced by
+    /// the attach command, e.g. `alacritty -e {command}`.
+    pub terminal: Option<String>,
+}
+
+/// Suggests frequently visited directories outside the workspace (from `z`
+/// or zsh `cdr` data) in the picker.
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RecentDirsConfig {
+    pub enabled: bool,
+    pub limit: usize,
+}
+
+impl Default for RecentDirsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            limit: RECENT_DIRS_LIMIT,
+        }
+    }
+}
+
+/// A "home" item at the top of the picker, opening a dashboard session in
+/// the workspace for when no project is on the agenda yet.
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HomeConfig {
+    pub enabled: bool,
+    /// Name of the home session.
+    pub session: String,
+    /// Directory of the session; the first workspace root when unset.
+    pub path: Option<String>,
+    /// Named layout for the session instead of the dashboard: the notes
+    /// files in the editor and a shell showing `ws stats`.
+    pub layout: Option<String>,
+    /// Files the dashboard's editor opens, relative to `path`.
+    pub notes: Vec<String>,
+}
+
+impl Default for HomeConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            session: DEFAULT_HOME_SESSION.to_string(),
+            path: None,
+            layout: None,
+            notes: DEFAULT_HOME_NOTES.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+}
+
+/// How `ws new` populates a project directory.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum ProjectTemplate {
+    /// Shell command run in the category directory, e.g. `cargo new {name}`.
+    Command(String),
+    /// Directory whose contents are copied into the new project.
+    Copy { copy: String },
+}
+
+impl Config {
+    pub fn load() -> Result<Self> {
+        Self::load_from(&Self::path(), DEFAULT_WORKSPACE)
+    }
+
+    /// Loads `path`. Without configured roots, `default_workspace` is used,
+    /// or a workspace found above the current directory if it doesn't exist.
+    pub fn load_from(path: &Path, default_workspace: &str) -> Result<Self> {
+        let mut config: Self = match fs::read_to_string(path) {
+            Ok(contents) => Self::parse(&contents)
+                .map_err(|e| format!("Invalid config {}: {}", path.display(), e))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Self::default(),
+            Err(e) => return Err(format!("Failed to read config {}: {}", path.display(), e).into()),
+        };
+
+        if config.workspaces.is_empty() {
+            let default = shellexpand::tilde(default_workspace).to_string();
+            let detected = if Path::new(&default).is_dir() {
+                None
+            } else {
+                std::env::current_dir()
+                    .ok()
+                    .and_then(|cwd| detect_workspace(&cwd))
+            };
+            match detected {
+                Some(workspace) => {
+                    config
+                        .workspaces
+                        .push(WorkspaceRoot::new(&workspace.to_string_lossy()));
+                    config.detected_workspace = Some(workspace);
+                }
+                None => config.workspaces.push(WorkspaceRoot::new(&default)),
+            }
+        }
+        for root in &mut config.workspaces {
+            root.normalize();
+        }
+        for org in &mut config.orgs {
+            org.path = shellexpand::tilde(&org.path).to_string();
+        }
+        if let Some(home) = &mut config.home.path {
+            *home = shellexpand::tilde(home).to_string();
+        }
+        config.layout.projects = std::mem::take(&mut config.layout.projects)
+            .into_iter()
+            .map(|(path, layout)| {
+                let path = shellexpand::tilde(&path);
+                (path.trim_end_matches('/').to_string(), layout)
+            })
+            .collect();
+        config.layouts_dir = path
+            .parent()
+            .map_or_else(|| PathBuf::from(LAYOUTS_DIR), |dir| dir.join(LAYOUTS_DIR));
+        for (var, value) in [
+            (SHELL_ENV, &mut config.layout.shell),
+            (EDITOR_ENV, &mut config.layout.editor),
+            (FILE_MANAGER_ENV, &mut config.layout.file_manager),
+        ] {
+            if let Ok(command) = std::env::var(var) {
+                *value = command;
+            }
+        }
+        Ok(config)
+    }
+
+    /// Replaces the configured roots with a single `--workspace` root.
+    pub fn override_workspace(&mut self, workspace: Option<String>) {
+        if let Some(workspace) = workspace {
+            self.workspaces = vec![self.root(&workspace)];
+        }
+    }
+
+    /// A root for `path` given on the command line, with the depths of
+    /// `--min-depth` and `--max-depth`.
+    pub fn root(&self, path: &str) -> WorkspaceRoot {
+        let mut root = WorkspaceRoot::new(path);
+        (root.min_depth, root.max_depth) = self.depth_override;
+        root
+    }
+
+    /// Scans every root, including ones given later with `--workspace`,
+    /// between these depths instead of the configured ones.
+    pub fn override_depth(&mut self, min_depth: Option<usize>, max_depth: Option<usize>) {
+        self.depth_override = (min_depth, max_depth);
+        for root in &mut self.workspaces {
+            root.min_depth = min_depth.or(root.min_depth);
+            root.max_depth = max_depth.or(root.max_depth);
+        }
+    }
+
+    /// Appends `workspace` as a root to the config file at `path`, creating
+    /// it if needed.
+    pub fn save_workspace(path: &Path, workspace: &Path) -> Result<()> {
+        let workspace = workspace.to_string_lossy();
+        let workspace = match dirs::home_dir() {
+            Some(home) => match Path::new(workspace.as_ref()).strip_prefix(&home) {
+                Ok(rest) => format!("~/{}", rest.display()),
+                Err(_) => workspace.to_string(),
+            },
+            None => workspace.to_string(),
+        };
+        Self::append(
+            path,
+            &format!(
+                "[[workspaces]]\npath = {}\n",
+                toml::Value::String(workspace)
+            ),
+        )
+    }
+
+    /// Appends `snippet` to the config file at `path`, creating it if
+    /// needed. Nothing is written if the result wouldn't be a valid config.
+    ///
+    /// Entries under a `[table]` header the file already has go below that
+    /// header instead, since TOML doesn't allow a table twice; this lets
+    /// `[layout.projects]` be imported more than once.
+    pub fn append(path: &Path, snippet: &str) -> Result<()> {
+        let mut contents = fs::read_to_string(path).unwrap_or_default();
+        if !contents.is_empty() && !contents.ends_with('\n') {
+            contents.push('\n');
+        }
+        let mut appended = String::new();
+        let mut merging_at: Option<usize> = None;
+        for line in snippet.split_inclusive('\n') {
+            let trimmed = line.trim();
+            if trimmed.starts_with('[') {
+                merging_at = if trimmed.starts_with("[[") {
+                    None
+                } else {
+                    table_end(&contents, trimmed)
+                };
+                if merging_at.is_some() {
+                    continue;
+                }
+            }
+            match &mut merging_at {
+                Some(_) if trimmed.is_empty() => {}
+                Some(at) => {
+                    contents.insert_str(*at, line);
+                    *at += line.len();
+                }
+                None => appended.push_str(line),
+            }
+        }
+        if !contents.is_empty() && !appended.is_empty() {
+            contents.push('\n');
+        }
+        contents.push_str(&appended);
+
+        Self::parse(&contents)
+            .map_err(|e| format!("Adding to {} would break it: {}", path.display(), e))?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, contents)
+            .map_err(|e| format!("Failed to write config {}: {}", path.display(), e).into())
+    }
+
+    /// The config in `contents`, with this host's `[host."NAME"]` table
+    /// applied.
+    fn parse(contents: &str) -> Result<Self> {
+        let mut table: toml::Table = toml::from_str(contents)?;
+        apply_host_overrides(&mut table)?;
+        Ok(table.try_into()?)
+    }
+
+    pub fn path() -> PathBuf {
+        if let Ok(path) = std::env::var(CONFIG_ENV) {
+            return PathBuf::from(shellexpand::tilde(&path).to_string());
+        }
+        dirs::config_dir()
+            .unwrap_or_else(|| PathBuf::from("~/.config"))
+            .join("ws")
+            .join("config.toml")
+    }
+
+    /// The layout in `layouts/NAME.toml`.
+    pub fn layout_spec(&self, name: &str) -> Result<LayoutSpec> {
+        let path = self.layouts_dir.join(format!("{}.toml", name));
+        match fs::read_to_string(&path) {
+            Ok(contents) => toml::from_str(&contents)
+                .map_err(|e| format!("Invalid layout {}: {}", path.display(), e).into()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                Err(format!("Unknown layout '{}'", name).into())
+            }
+            Err(e) => Err(format!("Failed to read layout {}: {}", path.display(), e).into()),
+        }
+    }
+
+    /// Names of the layouts in the layouts directory.
+    pub fn layout_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(&self.layouts_dir)
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let path = entry.path();
+                if path.extension()? != "toml" {
+                    return None;
+                }
+                Some(path.file_stem()?.to_string_lossy().to_string())
+            })
+            .collect();
+        names.sort();
+        names
+    }
+
+    pub fn app(&self, name: &str) -> Result<&str> {
+        if let Some(command) = self.apps.get(name) {
+            return Ok(command);
+        }
+        DEFAULT_APPS
+            .iter()
+            .find(|(app, _)| *app == name)
+            .map(|(_, command)| *command)
+            .ok_or_else(|| {
+                let mut available: Vec<&str> = DEFAULT_APPS.iter().map(|(app, _)| *app).collect();
+                available.extend(self.apps.keys().map(|k| k.as_str()));
+                available.sort_unstable();
+                available.dedup();
+                format!(
+                    "Unknown app '{}' (available: {})",
+                    name,
+                    available.join(", ")
+                )
+                .into()
+            })
+    }
+
+    pub fn template(&self, name: &str) -> Result<ProjectTemplate> {
+        if let Some(template) = self.templates.get(name) {
+            return Ok(template.clone());
+        }
+        DEFAULT_TEMPLATES
+            .iter()
+            .find(|(template, _)| *template == name)
+            .map(|(_, command)| ProjectTemplate::Command(command.to_string()))
+            .ok_or_else(|| {
+                let mut available: Vec<&str> = DEFAULT_TEMPLATES
+                    .iter()
+                    .map(|(template, _)| *template)
+                    .collect();
+                available.extend(self.templates.keys().map(|k| k.as_str()));
+                available.sort_unstable();
+                available.dedup();
+                format!(
+                    "Unknown template '{}' (available: {})",
+                    name,
+                    available.join(", ")
+                )
+                .into()
+            })
+    }
+}
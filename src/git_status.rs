@@ -0,0 +1,123 @@
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::parallel_map;
+
+/// Repositories queried at once by `read_all`.
+const CONCURRENCY: usize = 8;
+
+/// What the picker shows about a project's repository.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GitStatus {
+    /// Checked out branch, see `branch`.
+    pub branch: String,
+    /// Uncommitted changes or untracked files.
+    pub dirty: bool,
+    /// Commits ahead of and behind the upstream branch.
+    pub ahead: u32,
+    pub behind: u32,
+}
+
+impl fmt::Display for GitStatus {
+    /// `main* ↑1 ↓2`: the branch, `*` when dirty, then the counts that
+    /// aren't zero.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.branch)?;
+        if self.dirty {
+            write!(f, "*")?;
+        }
+        if self.ahead > 0 {
+            write!(f, " ↑{}", self.ahead)?;
+        }
+        if self.behind > 0 {
+            write!(f, " ↓{}", self.behind)?;
+        }
+        Ok(())
+    }
+}
+
+/// The status of the repository at `path`, or `None` if it isn't one.
+pub fn read(path: &str) -> Option<GitStatus> {
+    let output = Command::new("git")
+        .args(["-C", path, "status", "--porcelain=v2", "--branch"])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    let mut status = GitStatus {
+        branch: branch(Path::new(path)).unwrap_or_default(),
+        dirty: false,
+        ahead: 0,
+        behind: 0,
+    };
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        if let Some(counts) = line.strip_prefix("# branch.ab ") {
+            for count in counts.split_whitespace() {
+                if let Some(ahead) = count.strip_prefix('+') {
+                    status.ahead = ahead.parse().unwrap_or(0);
+                } else if let Some(behind) = count.strip_prefix('-') {
+                    status.behind = behind.parse().unwrap_or(0);
+                }
+            }
+        } else if !line.starts_with('#') {
+            status.dirty = true;
+        }
+    }
+    Some(status)
+}
+
+/// `read` for each of `paths`, `CONCURRENCY` at a time, in order.
+pub fn read_all(paths: &[&str]) -> Vec<Option<GitStatus>> {
+    parallel_map(paths, CONCURRENCY, |path| read(path))
+        .into_iter()
+        .map(Option::flatten)
+        .collect()
+}
+
+/// The branch checked out in a git repository, or the short commit when the
+/// HEAD is detached. Reads `.git/HEAD` directly, since running git for every
+/// picker row would be slow; worktrees point at their git directory with a
+/// `.git` file.
+pub fn branch(path: &Path) -> Option<String> {
+    let dot_git = path.join(".git");
+    let git_dir = if dot_git.is_file() {
+        let contents = fs::read_to_string(&dot_git).ok()?;
+        let dir = PathBuf::from(contents.strip_prefix("gitdir:")?.trim());
+        if dir.is_absolute() {
+            dir
+        } else {
+            path.join(dir)
+        }
+    } else {
+        dot_git
+    };
+
+    let head = fs::read_to_string(git_dir.join("HEAD")).ok()?;
+    let head = head.trim();
+    match head.strip_prefix("ref: ") {
+        Some(reference) => Some(
+            reference
+                .strip_prefix("refs/heads/")
+                .unwrap_or(reference)
+                .to_string(),
+        ),
+        None => Some(head.chars().take(7).collect()),
+    }
+}
+
+/// When the checked out commit of the repository at `path` was made, as a
+/// Unix timestamp.
+pub fn last_commit_time(path: &str) -> Option<i64> {
+    let output = Command::new("git")
+        .args(["-C", path, "log", "-1", "--format=%ct"])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8_lossy(&output.stdout).trim().parse().ok()
+}
@@ -0,0 +1,156 @@
+use serde::de::IgnoredAny;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use crate::org::{fetch_json, GITHUB_TOKEN_ENV};
+use crate::{is_fresh, Result};
+
+const API: &str = "https://api.github.com";
+/// Seconds a repository's health is reused before GitHub is asked again.
+const TTL_SECONDS: i64 = 1800;
+/// Open pull requests counted; more are shown as `100+`.
+const PR_LIMIT: usize = 100;
+
+/// CI state and open pull requests of a repository's default branch.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Health {
+    pub branch: String,
+    pub checks: Checks,
+    pub open_prs: usize,
+    pub fetched_at: i64,
+}
+
+/// Combined outcome of the check runs on the branch's latest commit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Checks {
+    Passing,
+    Failing,
+    Pending,
+    None,
+}
+
+impl fmt::Display for Health {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let checks = match self.checks {
+            Checks::Passing => "checks passing",
+            Checks::Failing => "checks failing",
+            Checks::Pending => "checks running",
+            Checks::None => "no checks",
+        };
+        let prs = if self.open_prs >= PR_LIMIT {
+            format!("{}+", PR_LIMIT)
+        } else {
+            self.open_prs.to_string()
+        };
+        write!(
+            f,
+            "{}: {}, {} open pull request{}",
+            self.branch,
+            checks,
+            prs,
+            if self.open_prs == 1 { "" } else { "s" }
+        )
+    }
+}
+
+#[derive(Deserialize)]
+struct Repository {
+    default_branch: String,
+}
+
+#[derive(Deserialize)]
+struct CheckRuns {
+    check_runs: Vec<CheckRun>,
+}
+
+#[derive(Deserialize)]
+struct CheckRun {
+    status: String,
+    conclusion: Option<String>,
+}
+
+/// Whether GitHub can be asked at all; health is only fetched with a token.
+pub fn available() -> bool {
+    std::env::var(GITHUB_TOKEN_ENV).is_ok_and(|token| !token.is_empty())
+}
+
+/// `owner/repo` of a GitHub remote URL (https, ssh or scp-like).
+pub fn github_repo(remote: &str) -> Option<String> {
+    let (_, rest) = remote.split_once("github.com")?;
+    let rest = rest.trim_start_matches([':', '/']);
+    let repo = rest.trim_end_matches('/').trim_end_matches(".git");
+    let mut parts = repo.split('/');
+    let (owner, name) = (parts.next()?, parts.next()?);
+    (!owner.is_empty() && !name.is_empty() && parts.next().is_none())
+        .then(|| format!("{}/{}", owner, name))
+}
+
+/// The health of `repo`, from `cache_file` if it is recent enough,
+/// otherwise fetched and, with `store`, stored there.
+pub fn cached(repo: &str, cache_file: &Path, store: bool, now: i64) -> Result<Health> {
+    let mut cache: HashMap<String, Health> = fs::read_to_string(cache_file)
+        .ok()
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default();
+    if let Some(health) = cache.get(repo) {
+        if is_fresh(health.fetched_at, TTL_SECONDS, now) {
+            return Ok(health.clone());
+        }
+    }
+
+    let health = fetch(repo, now)?;
+    if !store {
+        return Ok(health);
+    }
+    cache.insert(repo.to_string(), health.clone());
+    if let Some(parent) = cache_file.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(cache_file, serde_json::to_string_pretty(&cache)?)?;
+    Ok(health)
+}
+
+fn fetch(repo: &str, now: i64) -> Result<Health> {
+    let get = |path: &str| format!("{}/repos/{}{}", API, repo, path);
+    let auth = "Authorization: Bearer";
+
+    let repository: Repository = fetch_json(&get(""), GITHUB_TOKEN_ENV, auth)?;
+    let branch = repository.default_branch;
+    let runs: CheckRuns = fetch_json(
+        &get(&format!("/commits/{}/check-runs?per_page=100", branch)),
+        GITHUB_TOKEN_ENV,
+        auth,
+    )?;
+    let pulls: Vec<IgnoredAny> = fetch_json(
+        &get(&format!("/pulls?state=open&per_page={}", PR_LIMIT)),
+        GITHUB_TOKEN_ENV,
+        auth,
+    )?;
+
+    let failed = |run: &CheckRun| {
+        matches!(
+            run.conclusion.as_deref(),
+            Some("failure" | "timed_out" | "cancelled" | "action_required")
+        )
+    };
+    let checks = if runs.check_runs.is_empty() {
+        Checks::None
+    } else if runs.check_runs.iter().any(failed) {
+        Checks::Failing
+    } else if runs.check_runs.iter().any(|run| run.status != "completed") {
+        Checks::Pending
+    } else {
+        Checks::Passing
+    };
+
+    Ok(Health {
+        branch,
+        checks,
+        open_prs: pulls.len(),
+        fetched_at: now,
+    })
+}
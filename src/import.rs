@@ -0,0 +1,584 @@
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config::Config;
+use crate::yaml::{self, Value};
+use crate::{messages, Result};
+
+/// Config carried over from another sessionizer.
+pub struct Import {
+    /// TOML to append to the ws config.
+    pub snippet: String,
+    /// A project list file the snippet refers to, and the paths to write to
+    /// it.
+    pub list: Option<(PathBuf, Vec<String>)>,
+    /// Named layouts to add to the layouts directory, as names and TOML.
+    pub layouts: Vec<(String, String)>,
+    /// Settings ws has no equivalent for.
+    pub warnings: Vec<String>,
+}
+
+/// The parts of `sesh.toml` ws can use; other keys are ignored.
+#[derive(Deserialize)]
+struct SeshConfig {
+    #[serde(default)]
+    session: Vec<SeshSession>,
+    #[serde(default)]
+    import: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct SeshSession {
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    path: String,
+    #[serde(default)]
+    startup_command: Option<String>,
+}
+
+/// `~/.config/sesh/sesh.toml`, honouring `XDG_CONFIG_HOME` like sesh does.
+pub fn sesh_config_path() -> PathBuf {
+    std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| dirs::home_dir().map(|home| home.join(".config")))
+        .unwrap_or_else(|| PathBuf::from("~/.config"))
+        .join("sesh")
+        .join("sesh.toml")
+}
+
+/// sesh's configured sessions as a project list file at `list_path`, added
+/// as a workspace root aliased `sesh`.
+pub fn sesh(config_path: &Path, list_path: &Path) -> Result<Import> {
+    let contents = fs::read_to_string(config_path)
+        .map_err(|e| messages::READ_FAILED.format(&[&config_path.display(), &e]))?;
+    let config: SeshConfig = toml::from_str(&contents)
+        .map_err(|e| format!("Invalid sesh config {}: {}", config_path.display(), e))?;
+
+    let mut paths = Vec::new();
+    let mut warnings = Vec::new();
+    for session in config.session {
+        if session.path.is_empty() {
+            warnings.push(format!("session '{}' has no path, skipped", session.name));
+            continue;
+        }
+        let path = shellexpand::tilde(&session.path).to_string();
+        let dir_name = Path::new(&path)
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        if !session.name.is_empty() && session.name != dir_name {
+            warnings.push(format!(
+                "session '{}' will be named after its directory '{}'",
+                session.name, dir_name
+            ));
+        }
+        if let Some(command) = session.startup_command.filter(|c| !c.is_empty()) {
+            warnings.push(format!(
+                "startup_command of '{}' ({}) not imported; add it to a layout or the \
+                 project's .ws.toml",
+                dir_name, command
+            ));
+        }
+        if !paths.contains(&path) {
+            paths.push(path);
+        }
+    }
+    for file in config.import {
+        warnings.push(format!("imported config {} not read", file));
+    }
+    if paths.is_empty() {
+        return Err(format!("No sessions with a path in {}", config_path.display()).into());
+    }
+
+    let snippet = format!(
+        "# sessions imported from sesh\n[[workspaces]]\npath = {}\nalias = \"sesh\"\n",
+        toml::Value::String(home_relative(list_path))
+    );
+    Ok(Import {
+        snippet,
+        list: Some((list_path.to_path_buf(), paths)),
+        layouts: Vec::new(),
+        warnings,
+    })
+}
+
+/// t-smart-tmux offers the directories zoxide knows, which the picker's
+/// recent directories read too.
+pub fn t_smart_tmux() -> Import {
+    Import {
+        snippet: "# directories from zoxide, as t-smart-tmux lists them\n\
+                  [recent_dirs]\nenabled = true\n"
+            .to_string(),
+        list: None,
+        layouts: Vec::new(),
+        warnings: Vec::new(),
+    }
+}
+
+/// Where tmuxinator keeps its projects: `$TMUXINATOR_CONFIG`, else the XDG
+/// config directory, else the older `~/.tmuxinator`.
+pub fn tmuxinator_dir() -> PathBuf {
+    tool_dir("TMUXINATOR_CONFIG", "tmuxinator", ".tmuxinator")
+}
+
+/// Where tmuxp keeps its workspaces: `$TMUXP_CONFIGDIR`, else the XDG
+/// config directory, else the older `~/.tmuxp`.
+pub fn tmuxp_dir() -> PathBuf {
+    tool_dir("TMUXP_CONFIGDIR", "tmuxp", ".tmuxp")
+}
+
+fn tool_dir(env: &str, name: &str, legacy: &str) -> PathBuf {
+    if let Some(dir) = std::env::var_os(env).filter(|dir| !dir.is_empty()) {
+        return PathBuf::from(shellexpand::tilde(&dir.to_string_lossy()).as_ref());
+    }
+    let home = dirs::home_dir().unwrap_or_else(|| PathBuf::from("~"));
+    let xdg = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map_or_else(|| home.join(".config"), PathBuf::from)
+        .join(name);
+    let legacy = home.join(legacy);
+    if !xdg.is_dir() && legacy.is_dir() {
+        legacy
+    } else {
+        xdg
+    }
+}
+
+/// tmuxinator projects as named layouts, with their roots in a project list
+/// file at `list_path` that use them. `source` is a project file or the
+/// directory of them. What `config` has from an earlier import is left out
+/// of the snippet.
+pub fn tmuxinator(source: &Path, list_path: &Path, config: &Config) -> Result<Import> {
+    let extensions = ["yml", "yaml"];
+    converted(
+        "tmuxinator",
+        source,
+        &extensions,
+        list_path,
+        config,
+        from_tmuxinator,
+    )
+}
+
+/// tmuxp workspaces (YAML or JSON), like `tmuxinator`.
+pub fn tmuxp(source: &Path, list_path: &Path, config: &Config) -> Result<Import> {
+    let extensions = ["yaml", "yml", "json"];
+    converted("tmuxp", source, &extensions, list_path, config, from_tmuxp)
+}
+
+/// A session as tmuxinator or tmuxp describe it.
+struct Session {
+    root: Option<String>,
+    windows: Vec<Window>,
+    focus: Option<String>,
+}
+
+struct Window {
+    name: String,
+    path: Option<String>,
+    arrange: Option<String>,
+    /// Commands of each pane; the first pane is the window's own.
+    panes: Vec<(Vec<String>, Option<String>)>,
+}
+
+fn converted(
+    tool: &str,
+    source: &Path,
+    extensions: &[&str],
+    list_path: &Path,
+    config: &Config,
+    convert: fn(&Value, &mut Vec<String>) -> Result<Session>,
+) -> Result<Import> {
+    let files: Vec<PathBuf> = if source.is_dir() {
+        let mut files: Vec<PathBuf> = fs::read_dir(source)
+            .map_err(|e| messages::READ_FAILED.format(&[&source.display(), &e]))?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| {
+                path.extension()
+                    .is_some_and(|ext| extensions.iter().any(|e| ext == *e))
+            })
+            .collect();
+        files.sort();
+        files
+    } else {
+        vec![source.to_path_buf()]
+    };
+
+    let mut paths = Vec::new();
+    let mut assignments = Vec::new();
+    let mut layouts = Vec::new();
+    let mut warnings = Vec::new();
+    for file in &files {
+        let Some(name) = file
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+        else {
+            continue;
+        };
+        let contents = fs::read_to_string(file)
+            .map_err(|e| messages::READ_FAILED.format(&[&file.display(), &e]))?;
+        let document = if file.extension().is_some_and(|ext| ext == "json") {
+            serde_json::from_str(&contents)
+                .map(Value::from_json)
+                .map_err(|e| e.to_string())
+        } else {
+            yaml::parse(&contents).map_err(|e| e.to_string())
+        };
+        let mut file_warnings = Vec::new();
+        let session = match document
+            .and_then(|document| convert(&document, &mut file_warnings).map_err(|e| e.to_string()))
+        {
+            Ok(session) => session,
+            Err(e) => {
+                warnings.push(format!("{} skipped: {}", file.display(), e));
+                continue;
+            }
+        };
+        if contents.contains("<%") {
+            file_warnings.push("ERB tags are copied as they are".to_string());
+        }
+        warnings.extend(
+            file_warnings
+                .into_iter()
+                .map(|warning| format!("{}: {}", name, warning)),
+        );
+
+        match &session.root {
+            Some(root) => {
+                let root = shellexpand::tilde(root).to_string();
+                if !config
+                    .layout
+                    .projects
+                    .contains_key(root.trim_end_matches('/'))
+                {
+                    assignments.push((home_relative(Path::new(&root)), name.clone()));
+                }
+                if !paths.contains(&root) {
+                    paths.push(root);
+                }
+            }
+            None => warnings.push(format!(
+                "{} has no root; use its layout with `layout = \"{}\"` in a .ws.toml",
+                name, name
+            )),
+        }
+        layouts.push((name, layout_toml(&session)?));
+    }
+    if layouts.is_empty() {
+        return Err(format!("No {} projects found in {}", tool, source.display()).into());
+    }
+
+    let mut snippet = String::new();
+    let listed = config
+        .workspaces
+        .iter()
+        .any(|root| Path::new(&root.path) == list_path);
+    if !paths.is_empty() && !listed {
+        snippet.push_str(&format!(
+            "# projects imported from {tool}\n[[workspaces]]\npath = {}\nalias = \"{tool}\"\n\n",
+            toml::Value::String(home_relative(list_path))
+        ));
+    }
+    if !assignments.is_empty() {
+        snippet.push_str(&format!("[layout.projects]\n# imported from {}\n", tool));
+        for (path, layout) in &assignments {
+            snippet.push_str(&format!(
+                "{} = {}\n",
+                toml::Value::String(path.clone()),
+                toml::Value::String(layout.clone())
+            ));
+        }
+    }
+    Ok(Import {
+        snippet,
+        list: (!paths.is_empty()).then(|| (list_path.to_path_buf(), paths)),
+        layouts,
+        warnings,
+    })
+}
+
+/// Shell code for a pane's commands, ending in an interactive shell the way
+/// tmuxinator and tmuxp leave panes after typing the commands into one.
+fn pane_command(before: &[String], commands: &[String]) -> String {
+    let commands: Vec<&str> = before
+        .iter()
+        .chain(commands)
+        .map(|c| c.trim())
+        .filter(|c| !c.is_empty())
+        .collect();
+    if commands.is_empty() {
+        String::new()
+    } else {
+        format!("{}; exec \"${{SHELL:-sh}}\"", commands.join("; "))
+    }
+}
+
+/// Strings of a command or list of commands. tmuxp also accepts
+/// `{cmd: ...}` entries.
+fn commands(value: &Value) -> Vec<String> {
+    match value {
+        Value::String(command) => vec![command.clone()],
+        Value::List(items) => items.iter().flat_map(commands).collect(),
+        Value::Map(_) => value.get("cmd").map(commands).unwrap_or_default(),
+        Value::Null => Vec::new(),
+    }
+}
+
+fn warn_unsupported(value: &Value, supported: &[&str], warnings: &mut Vec<String>) {
+    if let Value::Map(entries) = value {
+        for (key, _) in entries {
+            if !supported.contains(&key.as_str()) {
+                warnings.push(format!("{} not imported", key));
+            }
+        }
+    }
+}
+
+fn from_tmuxinator(document: &Value, warnings: &mut Vec<String>) -> Result<Session> {
+    warn_unsupported(
+        document,
+        &[
+            "name",
+            "project_name",
+            "root",
+            "project_root",
+            "pre_window",
+            "pre_tab",
+            "startup_window",
+            "windows",
+            "tabs",
+        ],
+        warnings,
+    );
+    let root = document
+        .get("root")
+        .or_else(|| document.get("project_root"))
+        .and_then(Value::as_str)
+        .map(str::to_string);
+    let before = document
+        .get("pre_window")
+        .or_else(|| document.get("pre_tab"))
+        .map(commands)
+        .unwrap_or_default();
+    let Some(Value::List(items)) = document.get("windows").or_else(|| document.get("tabs")) else {
+        return Err("no windows".into());
+    };
+
+    let mut windows = Vec::new();
+    for item in items {
+        let Value::Map(entries) = item else {
+            return Err("a window isn't a `name: ...` entry".into());
+        };
+        let Some((name, value)) = entries.first() else {
+            continue;
+        };
+        let mut window = Window {
+            name: name.clone(),
+            path: None,
+            arrange: None,
+            panes: Vec::new(),
+        };
+        match value {
+            Value::Map(_) => {
+                warn_unsupported(value, &["root", "layout", "panes", "pre"], warnings);
+                window.path = value
+                    .get("root")
+                    .and_then(Value::as_str)
+                    .map(str::to_string);
+                window.arrange = value
+                    .get("layout")
+                    .and_then(Value::as_str)
+                    .map(str::to_string);
+                let window_before: Vec<String> = before
+                    .iter()
+                    .cloned()
+                    .chain(value.get("pre").map(commands).unwrap_or_default())
+                    .collect();
+                let panes = match value.get("panes") {
+                    Some(Value::List(panes)) => panes.clone(),
+                    _ => vec![Value::Null],
+                };
+                for pane in &panes {
+                    // A pane may be named: `- name: [commands]`.
+                    let pane_commands = match pane {
+                        Value::Map(entries) => entries
+                            .first()
+                            .map(|(_, commands_value)| commands(commands_value))
+                            .unwrap_or_default(),
+                        other => commands(other),
+                    };
+                    window
+                        .panes
+                        .push((vec![pane_command(&window_before, &pane_commands)], None));
+                }
+            }
+            other => window
+                .panes
+                .push((vec![pane_command(&before, &commands(other))], None)),
+        }
+        windows.push(window);
+    }
+
+    let focus = document.get("startup_window").and_then(|window| {
+        let window = window.as_str()?;
+        match window.parse::<usize>() {
+            // tmuxinator counts windows from tmux's base-index, usually 0 or 1.
+            Ok(index) => windows
+                .get(index.saturating_sub(1).min(windows.len().saturating_sub(1)))
+                .map(|w| w.name.clone()),
+            Err(_) => Some(window.to_string()),
+        }
+    });
+    Ok(Session {
+        root,
+        windows,
+        focus,
+    })
+}
+
+fn from_tmuxp(document: &Value, warnings: &mut Vec<String>) -> Result<Session> {
+    warn_unsupported(
+        document,
+        &[
+            "session_name",
+            "start_directory",
+            "shell_command_before",
+            "windows",
+        ],
+        warnings,
+    );
+    let root = document
+        .get("start_directory")
+        .and_then(Value::as_str)
+        .map(str::to_string);
+    let before = document
+        .get("shell_command_before")
+        .map(commands)
+        .unwrap_or_default();
+    let Some(Value::List(items)) = document.get("windows") else {
+        return Err("no windows".into());
+    };
+
+    let mut windows = Vec::new();
+    let mut focus = None;
+    for (index, item) in items.iter().enumerate() {
+        warn_unsupported(
+            item,
+            &[
+                "window_name",
+                "layout",
+                "start_directory",
+                "shell_command_before",
+                "panes",
+                "focus",
+            ],
+            warnings,
+        );
+        let name = item
+            .get("window_name")
+            .and_then(Value::as_str)
+            .map_or_else(|| format!("window-{}", index + 1), str::to_string);
+        if item.get("focus").and_then(Value::as_str) == Some("true") {
+            focus = Some(name.clone());
+        }
+        let window_before: Vec<String> = before
+            .iter()
+            .cloned()
+            .chain(
+                item.get("shell_command_before")
+                    .map(commands)
+                    .unwrap_or_default(),
+            )
+            .collect();
+        let panes = match item.get("panes") {
+            Some(Value::List(panes)) if !panes.is_empty() => panes.clone(),
+            _ => vec![Value::Null],
+        };
+        let mut window = Window {
+            name,
+            path: item
+                .get("start_directory")
+                .and_then(Value::as_str)
+                .map(str::to_string),
+            arrange: item
+                .get("layout")
+                .and_then(Value::as_str)
+                .map(str::to_string),
+            panes: Vec::new(),
+        };
+        for pane in &panes {
+            let (pane_commands, path) = match pane {
+                // `blank` and `pane` stand for an empty pane.
+                Value::String(word) if word == "blank" || word == "pane" => (Vec::new(), None),
+                Value::Map(_) => (
+                    pane.get("shell_command").map(commands).unwrap_or_default(),
+                    pane.get("start_directory")
+                        .and_then(Value::as_str)
+                        .map(str::to_string),
+                ),
+                other => (commands(other), None),
+            };
+            window
+                .panes
+                .push((vec![pane_command(&window_before, &pane_commands)], path));
+        }
+        windows.push(window);
+    }
+    Ok(Session {
+        root,
+        windows,
+        focus,
+    })
+}
+
+/// The session as a `layouts/NAME.toml`.
+fn layout_toml(session: &Session) -> Result<String> {
+    let mut table = toml::Table::new();
+    if let Some(focus) = &session.focus {
+        table.insert("focus".to_string(), focus.clone().into());
+    }
+    let mut windows = Vec::new();
+    for window in &session.windows {
+        let mut entry = toml::Table::new();
+        entry.insert("name".to_string(), window.name.clone().into());
+        let mut panes = window.panes.iter().map(|(commands, path)| {
+            let mut pane = toml::Table::new();
+            let command = commands.join("; ");
+            if !command.is_empty() {
+                pane.insert("command".to_string(), command.into());
+            }
+            if let Some(path) = path {
+                pane.insert("path".to_string(), path.clone().into());
+            }
+            pane
+        });
+        if let Some(first) = panes.next() {
+            entry.extend(first);
+        }
+        if entry.get("path").is_none() {
+            if let Some(path) = &window.path {
+                entry.insert("path".to_string(), path.clone().into());
+            }
+        }
+        let rest: Vec<toml::Value> = panes.map(toml::Value::Table).collect();
+        if !rest.is_empty() {
+            entry.insert("panes".to_string(), rest.into());
+        }
+        if let Some(arrange) = &window.arrange {
+            entry.insert("arrange".to_string(), arrange.clone().into());
+        }
+        windows.push(toml::Value::Table(entry));
+    }
+    table.insert("windows".to_string(), windows.into());
+    Ok(toml::to_string(&table)?)
+}
+
+fn home_relative(path: &Path) -> String {
+    match dirs::home_dir().and_then(|home| path.strip_prefix(home).ok().map(Path::to_path_buf)) {
+        Some(rest) => format!("~/{}", rest.display()),
+        None => path.to_string_lossy().to_string(),
+    }
+}
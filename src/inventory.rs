@@ -0,0 +1,145 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::path::Path;
+use std::process::Command;
+
+use crate::ProjectInfo;
+
+pub const INVENTORY_VERSION: u32 = 1;
+
+/// Snapshot of one machine's workspace, written by `ws export` and read by
+/// `ws compare`. Projects are keyed by `root/category/name` since absolute
+/// paths differ between machines.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Inventory {
+    pub version: u32,
+    pub hostname: String,
+    pub home: String,
+    pub exported_at: i64,
+    pub projects: Vec<InventoryProject>,
+    pub sessions: Vec<String>,
+    /// Keys of the pinned projects; missing from inventories older ws
+    /// versions wrote.
+    #[serde(default)]
+    pub pins: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InventoryProject {
+    pub key: String,
+    pub path: String,
+    pub remote: Option<String>,
+    /// The name of the project's session on the exporting machine; missing
+    /// from inventories older ws versions wrote.
+    #[serde(default)]
+    pub session: Option<String>,
+}
+
+impl InventoryProject {
+    pub fn from_project(project: &ProjectInfo, session: Option<String>) -> Self {
+        Self {
+            key: project.qualified_name(),
+            path: project.path.clone(),
+            remote: git_remote(&project.path),
+            session,
+        }
+    }
+}
+
+/// Entries present on only one side of a comparison.
+#[derive(Debug, Default)]
+pub struct InventoryDiff<'a> {
+    pub projects_only_left: Vec<&'a InventoryProject>,
+    pub projects_only_right: Vec<&'a InventoryProject>,
+    pub sessions_only_left: Vec<&'a str>,
+    pub sessions_only_right: Vec<&'a str>,
+    pub pins_only_left: Vec<&'a str>,
+    pub pins_only_right: Vec<&'a str>,
+}
+
+impl InventoryDiff<'_> {
+    pub fn is_empty(&self) -> bool {
+        self.projects_only_left.is_empty()
+            && self.projects_only_right.is_empty()
+            && self.sessions_only_left.is_empty()
+            && self.sessions_only_right.is_empty()
+            && self.pins_only_left.is_empty()
+            && self.pins_only_right.is_empty()
+    }
+}
+
+impl Inventory {
+    pub fn diff<'a>(&'a self, other: &'a Self) -> InventoryDiff<'a> {
+        let left_keys: BTreeSet<&str> = self.projects.iter().map(|p| p.key.as_str()).collect();
+        let right_keys: BTreeSet<&str> = other.projects.iter().map(|p| p.key.as_str()).collect();
+
+        InventoryDiff {
+            projects_only_left: self
+                .projects
+                .iter()
+                .filter(|p| !right_keys.contains(p.key.as_str()))
+                .collect(),
+            projects_only_right: other
+                .projects
+                .iter()
+                .filter(|p| !left_keys.contains(p.key.as_str()))
+                .collect(),
+            sessions_only_left: self
+                .sessions
+                .iter()
+                .filter(|s| !other.sessions.contains(s))
+                .map(|s| s.as_str())
+                .collect(),
+            sessions_only_right: other
+                .sessions
+                .iter()
+                .filter(|s| !self.sessions.contains(s))
+                .map(|s| s.as_str())
+                .collect(),
+            pins_only_left: self
+                .pins
+                .iter()
+                .filter(|p| !other.pins.contains(p))
+                .map(|p| p.as_str())
+                .collect(),
+            pins_only_right: other
+                .pins
+                .iter()
+                .filter(|p| !self.pins.contains(p))
+                .map(|p| p.as_str())
+                .collect(),
+        }
+    }
+
+    /// Rewrites a path from this machine relative to `~`, so it can be used
+    /// on a machine with a different home directory.
+    pub fn portable_path(&self, path: &str) -> String {
+        match Path::new(path).strip_prefix(&self.home) {
+            Ok(rest) if !self.home.is_empty() => format!("~/{}", rest.display()),
+            _ => path.to_string(),
+        }
+    }
+}
+
+pub fn git_remote(path: &str) -> Option<String> {
+    let output = Command::new("git")
+        .args(["-C", path, "remote", "get-url", "origin"])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let remote = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (!remote.is_empty()).then_some(remote)
+}
+
+pub fn hostname() -> String {
+    Command::new("hostname")
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .filter(|name| !name.is_empty())
+        .or_else(|| std::env::var("HOSTNAME").ok())
+        .unwrap_or_else(|| "unknown".to_string())
+}
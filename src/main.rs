@@ -1,526 +1,8493 @@
+mod completions;
+mod config;
+mod git_status;
+mod health;
+mod import;
+mod inventory;
+mod messages;
+mod naming;
+mod org;
+mod process;
+mod project_config;
+mod recent_dirs;
+mod recent_files;
+#[cfg(feature = "self-update")]
+mod self_update;
+mod timings;
+mod tmux_format;
+mod todos;
+mod watch;
+mod wezterm;
+mod worktree;
+mod yaml;
+mod zellij;
+
 use clap::{Parser, Subcommand};
+use config::{
+    AttachConfig, AttachMode, Backend, Config, Finder, HistoryConfig, HistoryPolicy, HooksConfig,
+    LayoutConfig, LayoutSpec, OrgConfig, PaneSpec, ProjectSort, ProjectTemplate, ScanConfig,
+    ScanMode, SessionConfig, SummaryMode, WorkspaceRoot, ARCHIVE_DIR,
+};
+use fuzzy_matcher::skim::SkimMatcherV2;
+use fuzzy_matcher::FuzzyMatcher;
+use git_status::GitStatus;
+use inventory::{Inventory, InventoryProject, INVENTORY_VERSION};
+use org::RemoteRepo;
+use project_config::{ProjectConfig, ProjectLayout, PROJECT_CONFIG_FILE};
 use serde::{Deserialize, Serialize};
 use skim::prelude::*;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fs;
-use std::io::Cursor;
-use std::path::PathBuf;
+use std::io::{BufRead, IsTerminal, Read, Write};
+use std::os::unix::process::CommandExt;
+use std::panic::AssertUnwindSafe;
+use std::path::{Path, PathBuf};
 use std::process::Command;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tmux_format::Format;
+use tuikit::attr::{Attr, Effect};
 use walkdir::WalkDir;
+use wezterm::WeztermClient;
+use zellij::ZellijClient;
 
 const STATE_VERSION: u32 = 1;
-const MAX_HISTORY_SIZE: usize = 10;
+const STATE_LOCK_FILE: &str = "state.lock";
+/// Held by the `ws pick` showing its picker; see `PickGuard`.
+const PICK_LOCK_FILE: &str = "pick.lock";
+/// Reads of a state file that doesn't parse before it is given up on. Only
+/// a writer that doesn't rename into place (an older ws, a synced copy) can
+/// leave one behind, and usually only for a moment.
+const STATE_LOAD_ATTEMPTS: u32 = 3;
+const STATE_LOAD_RETRY_DELAY: Duration = Duration::from_millis(50);
+/// Runs of a tmux command that can't reach the server before its error is
+/// returned, and the pause before the first rerun, doubled after each.
+const TMUX_ATTEMPTS: u32 = 4;
+const TMUX_RETRY_DELAY: Duration = Duration::from_millis(50);
+/// `read-only` or `memory` selects a state mode like `--read-only` and
+/// `--in-memory` do.
+const STATE_MODE_ENV: &str = "WS_STATE";
+/// Set on the copy of `ws pick` that refreshes an expired cache in the
+/// background; see `spawn_background_refresh`.
+const BACKGROUND_REFRESH_ENV: &str = "WS_BACKGROUND_REFRESH";
+/// Project list written by `ws import sesh`, next to the config file.
+const SESH_LIST_FILE: &str = "sesh.txt";
+/// Projects `ws pick --recent-first` lists without `--max-items`.
+const RECENT_PICK_ITEMS: usize = 10;
+/// Project lists written by `ws import tmuxinator` and `ws import tmuxp`.
+const TMUXINATOR_LIST_FILE: &str = "tmuxinator.txt";
+const TMUXP_LIST_FILE: &str = "tmuxp.txt";
+/// Pause after sending an editor Escape before the keys that follow it.
+const ESCAPE_DELAY: Duration = Duration::from_millis(100);
+/// Files `ws files` offers per project, and remembers having opened.
+const RECENT_FILES_LISTED: usize = 50;
+const RECENT_FILES_KEPT: usize = 20;
+/// Programs already warned about as missing, next to the state file.
+const MISSING_TOOLS_FILE: &str = "missing-tools.json";
+const HEALTH_CACHE_FILE: &str = "health.json";
+const TODOS_CACHE_FILE: &str = "todos.json";
+/// Lines of a file shown around a TODO in the `ws todos` preview.
+const TODO_PREVIEW_CONTEXT: u32 = 10;
 const CACHE_TTL_SECONDS: i64 = 3600;
+/// How long `ws daemon` waits after a change for the ones following it.
+const DAEMON_SETTLE: Duration = Duration::from_secs(1);
 const PROJECT_SCAN_MIN_DEPTH: usize = 2;
 const PROJECT_SCAN_MAX_DEPTH: usize = 2;
 const FILES_WINDOW_INDEX: u32 = 9;
 const EDITOR_WINDOW_INDEX: u32 = 1;
+/// tmux window options holding a window's name in its layout and the name
+/// ws gave it.
+const WINDOW_ROLE_OPTION: &str = "@ws-role";
+const WINDOW_NAME_OPTION: &str = "@ws-name";
+/// Project types for `layout.window_names`, with the files that mark them.
+const PROJECT_MARKERS: &[(&str, &[&str])] = &[
+    ("rust", &["Cargo.toml"]),
+    ("node", &["package.json"]),
+    ("go", &["go.mod"]),
+    (
+        "python",
+        &["pyproject.toml", "setup.py", "requirements.txt"],
+    ),
+    ("ruby", &["Gemfile"]),
+    ("elixir", &["mix.exs"]),
+    ("java", &["pom.xml", "build.gradle", "build.gradle.kts"]),
+];
+const NOTIFY_ENV: &str = "WS_NOTIFY";
+const NOTIFY_MAX_NAMES: usize = 3;
+const DEFAULT_LAYOUT: &str = "default";
+/// Name of the home session's built-in layout, see `Layout::home`.
+const HOME_LAYOUT: &str = "home";
+const WHICH_MAX_CANDIDATES: usize = 5;
+const QUICK_SELECT_ROWS: usize = 9;
+const APP_KEY: &str = "ctrl-o";
+const APP_ACTION: &str = "app";
+/// Lines of a README shown in the picker's preview pane.
+const PREVIEW_README_LINES: usize = 40;
+const LAYOUT_TEST_WAIT_SECONDS: u64 = 2;
+const PAUSED_OPTION: &str = "@ws_paused";
+const GC_IDLE_HOURS: u64 = 24;
+/// Sessions evicted by `session.max_sessions` are saved as this plus their
+/// name, one snapshot each.
+const EVICTED_SNAPSHOT_PREFIX: &str = "evicted-";
+/// Snapshot `ws daemon` keeps up to date and `ws restore` recreates after a
+/// reboot; also the default of `ws snapshot save` and `restore`.
+const AUTO_SNAPSHOT: &str = "auto";
+/// How often `ws daemon` updates the automatic snapshot.
+const AUTO_SNAPSHOT_INTERVAL: Duration = Duration::from_secs(300);
+/// Days without a commit, open or session activity before a project drops
+/// out of its category's profile.
+const PROFILE_ACTIVE_DAYS: u64 = 30;
+/// `tmux kill-session` calls in flight at once when killing several sessions.
+const KILL_CONCURRENCY: usize = 8;
+/// Repositories whose last commit is looked up at once for profiles.
+const ACTIVITY_CONCURRENCY: usize = 8;
+/// Pane commands that mean nothing is running beyond the prompt.
+const SHELL_COMMANDS: &[&str] = &["bash", "zsh", "fish", "sh", "dash", "nu"];
+const CATEGORY_OPTION: &str = "@ws_category";
+const CATEGORY_COLOR_OPTION: &str = "@ws_category_color";
+const CATEGORY_COLORS: &[&str] = &[
+    "colour39",
+    "colour208",
+    "colour142",
+    "colour168",
+    "colour109",
+    "colour214",
+    "colour175",
+    "colour72",
+];
 
 type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
 
+/// Socket name passed to `tmux -L`; unset means the user's default server.
+static TMUX_SOCKET: OnceLock<TmuxSocket> = OnceLock::new();
+/// Overrides the directory state is kept in (`--sandbox`).
+static DATA_DIR: OnceLock<PathBuf> = OnceLock::new();
+/// Multiplexer sessions are created in (`backend`, `--backend`).
+static BACKEND: OnceLock<Backend> = OnceLock::new();
+/// Pickers print a numbered list and read the choice instead of running
+/// skim (`--no-tui`, `picker.plain`).
+static PLAIN_PICKER: OnceLock<bool> = OnceLock::new();
+/// Program behind the full-screen pickers (`picker.finder`, `--picker`).
+static FINDER: OnceLock<Finder> = OnceLock::new();
+/// When the command started, set if `--timings` asked for its phases.
+static TIMINGS: OnceLock<Instant> = OnceLock::new();
+/// `--yes`: destructive commands go ahead without asking.
+static ASSUME_YES: OnceLock<bool> = OnceLock::new();
+/// Where state is kept (`--read-only`, `--in-memory`, `WS_STATE`).
+static STATE_MODE: OnceLock<StateMode> = OnceLock::new();
+/// The state saved so far in the `memory` mode, as it would be on disk.
+static MEMORY_STATE: Mutex<Option<String>> = Mutex::new(None);
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum StateMode {
+    /// Read and written in the data directory.
+    #[default]
+    Disk,
+    /// Read from the data directory but never written, e.g. on a shared
+    /// account or in tests against real state.
+    ReadOnly,
+    /// Starts empty and only lives as long as the process, e.g. in
+    /// containers. Nothing is read from or written to the data directory.
+    Memory,
+}
+
+impl StateMode {
+    fn current() -> Self {
+        STATE_MODE.get().copied().unwrap_or_default()
+    }
+
+    fn from_env() -> Result<Self> {
+        match std::env::var(STATE_MODE_ENV).as_deref() {
+            Err(_) | Ok("") | Ok("disk") => Ok(Self::Disk),
+            Ok("read-only") => Ok(Self::ReadOnly),
+            Ok("memory") => Ok(Self::Memory),
+            Ok(other) => Err(format!(
+                "{} must be disk, read-only or memory, not '{}'",
+                STATE_MODE_ENV, other
+            )
+            .into()),
+        }
+    }
+
+    /// Whether state files in the data directory may be written.
+    fn writes(self) -> bool {
+        self == Self::Disk
+    }
+}
+
+/// A tmux server other than the user's default one.
+enum TmuxSocket {
+    /// `tmux -L`: a named socket in tmux's socket directory.
+    Name(String),
+    /// `tmux -S`: a socket at an explicit path.
+    Path(PathBuf),
+}
+
+impl TmuxSocket {
+    fn args(&self) -> [String; 2] {
+        match self {
+            Self::Name(name) => ["-L".to_string(), name.clone()],
+            Self::Path(path) => ["-S".to_string(), path.to_string_lossy().to_string()],
+        }
+    }
+}
+
 #[derive(Parser)]
 #[command(name = "ws")]
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+    /// How to attach when running outside tmux (overrides attach.mode)
+    #[arg(long, global = true, value_enum)]
+    attach: Option<AttachMode>,
+    /// Don't print a summary line after commands that change something
+    #[arg(short, long, global = true)]
+    quiet: bool,
+    /// Keep config, state and the workspace in DIR and use a private tmux
+    /// server there, e.g. for tests and demos
+    #[arg(long, global = true, value_name = "DIR")]
+    sandbox: Option<PathBuf>,
+    /// With --sandbox, use the real tmux server anyway
+    #[arg(long, global = true, requires = "sandbox")]
+    allow_tmux: bool,
+    /// Multiplexer to keep sessions in (overrides backend)
+    #[arg(long, global = true, value_enum)]
+    backend: Option<Backend>,
+    /// Choose from a numbered list typed at a prompt instead of the
+    /// full-screen picker, e.g. with a screen reader (overrides picker.plain)
+    #[arg(long, global = true)]
+    no_tui: bool,
+    /// Full-screen picker: skim (built in) or fzf (overrides picker.finder)
+    #[arg(long, global = true, value_enum, value_name = "FINDER")]
+    picker: Option<Finder>,
+    /// Editor for new sessions' editor window (overrides layout.editor)
+    #[arg(long, global = true, value_name = "COMMAND")]
+    editor: Option<String>,
+    /// Command for new sessions' files window (overrides layout.file_manager)
+    #[arg(long, global = true, value_name = "COMMAND")]
+    file_manager: Option<String>,
+    /// Shell the editor window runs in (overrides layout.shell)
+    #[arg(long, global = true, value_name = "COMMAND")]
+    window_shell: Option<String>,
+    /// Print how long loading state, checking the cache, querying tmux, the
+    /// picker and switching took
+    #[arg(long, global = true)]
+    timings: bool,
+    /// Never write state: history, the project cache and the like are read
+    /// but changes are dropped (WS_STATE=read-only)
+    #[arg(long, global = true, conflicts_with = "in_memory")]
+    read_only: bool,
+    /// Keep state in memory only, starting empty (WS_STATE=memory)
+    #[arg(long, global = true)]
+    in_memory: bool,
+    /// Don't ask before archiving or deleting
+    #[arg(short, long, global = true)]
+    yes: bool,
+    /// Shallowest level below each workspace root projects are found at
+    /// (overrides the roots' min_depth)
+    #[arg(long, global = true, value_name = "N")]
+    min_depth: Option<usize>,
+    /// Deepest level below each workspace root projects are found at
+    /// (overrides the roots' max_depth)
+    #[arg(long, global = true, value_name = "N")]
+    max_depth: Option<usize>,
 }
 
 #[derive(Subcommand)]
 enum Commands {
     /// Pick a project or session
     Pick {
-        #[arg(long, default_value = "~/workspace")]
-        workspace: String,
+        /// Workspace root to use instead of the configured ones
+        #[arg(long)]
+        workspace: Option<String>,
+        /// Read project paths from stdin (one per line) instead of scanning
+        #[arg(long)]
+        stdin: bool,
+        /// Print the selected path (or session name) instead of opening it
+        #[arg(long)]
+        print: bool,
+        /// With --print, print the selection as a JSON result
+        #[arg(long, requires = "print")]
+        json: bool,
+        /// Leave out the current session, its group and its project
+        #[arg(long)]
+        exclude_current_project: bool,
+        /// List archived projects instead of the workspace's
+        #[arg(long, conflicts_with = "stdin")]
+        archived: bool,
+        /// Scan DIR like a workspace root for this pick only, without
+        /// touching the cache (--workspace caches it)
+        #[arg(long, value_name = "DIR", conflicts_with_all = ["stdin", "archived", "workspace"])]
+        within: Option<PathBuf>,
+        /// List only running sessions and the most frecent projects from the
+        /// cache, without scanning or anything else that takes time
+        #[arg(long, conflicts_with_all = ["stdin", "archived", "within", "workspace"])]
+        recent_first: bool,
+        /// How many projects --recent-first lists
+        #[arg(long, value_name = "N", requires = "recent_first")]
+        max_items: Option<usize>,
+    },
+    /// Move a project into its root's .archive, killing its sessions
+    Archive {
+        /// Project name or category/name
+        project: String,
+        /// Workspace root to use instead of the configured ones
+        #[arg(long)]
+        workspace: Option<String>,
+    },
+    /// Pin a project to the top of the picker
+    Pin {
+        /// Project name or category/name
+        project: String,
+        /// Workspace root to use instead of the configured ones
+        #[arg(long)]
+        workspace: Option<String>,
+    },
+    /// Remove a project from the top of the picker
+    Unpin {
+        /// Pinned project name or category/name
+        project: String,
+    },
+    /// Move an archived project back into its category
+    Unarchive {
+        /// Archived project name or category/name
+        project: String,
+        /// Workspace root to use instead of the configured ones
+        #[arg(long)]
+        workspace: Option<String>,
     },
     /// Kill a session (switches to previous)
-    Kill,
+    Kill {
+        /// Print what was killed as a JSON result
+        #[arg(long)]
+        json: bool,
+    },
+    /// Kill sessions that are detached and have been idle for a while
+    Gc {
+        /// Hours without activity before a session is a candidate
+        #[arg(long, default_value_t = GC_IDLE_HOURS)]
+        idle: u64,
+        /// Review the candidates in a checklist before killing them
+        #[arg(short, long)]
+        interactive: bool,
+        /// List the candidates without killing anything
+        #[arg(long, conflicts_with = "interactive")]
+        dry_run: bool,
+        /// Print what was killed (or would be) as a JSON result
+        #[arg(long)]
+        json: bool,
+    },
     /// Jump back to previous session
     Back,
+    /// Attach to the most recent session in history from outside tmux
+    Attach {
+        /// The most recent session in history, recreated from its project if
+        /// it is no longer running (the default, and the only target so far)
+        #[arg(long)]
+        last: bool,
+    },
     /// Refresh project cache
     Refresh {
-        #[arg(long, default_value = "~/workspace")]
-        workspace: String,
+        /// Print the new projects and errors as a JSON result
+        #[arg(long)]
+        json: bool,
+        /// Workspace root to use instead of the configured ones
+        #[arg(long)]
+        workspace: Option<String>,
+    },
+    /// Open a project directly, without the picker
+    Open {
+        /// Project name or category/name
+        project: String,
+        /// Window to select once the session exists (e.g. editor, files)
+        #[arg(long)]
+        window: Option<String>,
+        /// Open the project in a GUI editor (code, idea, or one from [apps])
+        #[arg(long)]
+        app: Option<String>,
+        /// With --app, open the tmux session as well
+        #[arg(long, requires = "app")]
+        session: bool,
+        /// Workspace root to use instead of the configured ones
+        #[arg(long)]
+        workspace: Option<String>,
+    },
+    /// Pick one of a project's recently edited files and open it in the
+    /// project's editor window
+    Files {
+        /// Project name or category/name (defaults to the current session's)
+        project: Option<String>,
+        /// Workspace root to use instead of the configured ones
+        #[arg(long)]
+        workspace: Option<String>,
+    },
+    /// Pick a TODO or FIXME comment from the projects and open it in the
+    /// project's editor window
+    Todos {
+        /// Only this project (name or category/name)
+        #[arg(long)]
+        project: Option<String>,
+        /// Search again instead of using results from the last 10 minutes
+        #[arg(long)]
+        refresh: bool,
+        /// Workspace root to use instead of the configured ones
+        #[arg(long)]
+        workspace: Option<String>,
+    },
+    /// Switch to a session or project by name, without the picker
+    Switch {
+        /// Session or project name, category/name, or a unique prefix of one
+        name: String,
+        /// Workspace root to use instead of the configured ones
+        #[arg(long)]
+        workspace: Option<String>,
+    },
+    /// Run a task from a project's .ws.toml in its own window
+    Run {
+        /// Project name or category/name (defaults to the current session's)
+        project: Option<String>,
+        /// Task from the [tasks] section of the project's .ws.toml
+        task: Option<String>,
+        /// Pick the task from a list
+        #[arg(long, conflicts_with = "task")]
+        pick: bool,
+        /// Workspace root to use instead of the configured ones
+        #[arg(long)]
+        workspace: Option<String>,
+    },
+    /// Switch to a session for a branch of the project, in its own worktree
+    Branch {
+        /// Branch to switch to (picked from a list if omitted)
+        branch: Option<String>,
+        /// Project name or category/name (defaults to the current session's)
+        #[arg(short, long)]
+        project: Option<String>,
+        /// Workspace root to use instead of the configured ones
+        #[arg(long)]
+        workspace: Option<String>,
+    },
+    /// Print a project's environment for eval, e.g. `ws env api | source`
+    Env {
+        /// Project name or category/name (defaults to the current session's)
+        project: Option<String>,
+        /// Syntax to print (defaults to fish if $SHELL is fish, else posix)
+        #[arg(long, value_enum)]
+        shell: Option<EnvShell>,
+        /// Workspace root to use instead of the configured ones
+        #[arg(long)]
+        workspace: Option<String>,
+    },
+    /// Show CI checks and open pull requests of a project's GitHub
+    /// repository (needs GITHUB_TOKEN)
+    Health {
+        /// Project name or category/name (defaults to the current session's)
+        project: Option<String>,
+        /// Repository directory instead of a project
+        #[arg(long, value_name = "DIR", conflicts_with = "project")]
+        path: Option<PathBuf>,
+        /// Workspace root to use instead of the configured ones
+        #[arg(long)]
+        workspace: Option<String>,
+    },
+    /// Convert another sessionizer's config into ws config
+    Import {
+        /// Tool whose config to read
+        #[arg(value_enum)]
+        from: ImportSource,
+        /// Config file, or directory of tmuxinator/tmuxp project files, to
+        /// read instead of the tool's default
+        #[arg(long, value_name = "PATH")]
+        file: Option<PathBuf>,
+        /// Append to the ws config instead of printing
+        #[arg(long)]
+        write: bool,
+    },
+    /// Open another view of a session in its tmux session group
+    Group {
+        /// Session to join (defaults to the current session)
+        session: Option<String>,
+    },
+    /// Create a project from a template and open it
+    New {
+        /// Project to create, as category/name
+        project: String,
+        /// Template: empty, rust, node, or one from the [templates] section
+        #[arg(long)]
+        template: Option<String>,
+        /// Workspace root to use instead of the configured ones
+        #[arg(long)]
+        workspace: Option<String>,
+    },
+    /// Clone a repository into the workspace and open it
+    Clone {
+        /// URL to clone (https, ssh or git@host:owner/repo)
+        url: String,
+        /// Category to clone into (defaults to the repository's owner)
+        #[arg(long)]
+        category: Option<String>,
+        /// Workspace root to use instead of the configured ones
+        #[arg(long)]
+        workspace: Option<String>,
+    },
+    /// List categories with project counts, or create and archive them
+    Categories {
+        #[command(subcommand)]
+        command: Option<CategoryCommands>,
+        /// Workspace root to use instead of the configured ones
+        #[arg(long, global = true)]
+        workspace: Option<String>,
+    },
+    /// Save the live sessions under a name and bring them back later
+    Snapshot {
+        #[command(subcommand)]
+        command: SnapshotCommands,
+    },
+    /// Recreate the sessions of a snapshot, by default the automatic one
+    /// `ws daemon` keeps, e.g. after a reboot
+    Restore { name: Option<String> },
+    /// Open a category's recently active projects together
+    Profile {
+        #[command(subcommand)]
+        command: ProfileCommands,
+        /// Workspace root to use instead of the configured ones
+        #[arg(long, global = true)]
+        workspace: Option<String>,
+    },
+    /// Inspect session layouts
+    Layout {
+        #[command(subcommand)]
+        command: LayoutCommands,
+    },
+    /// Show or configure local usage statistics (never leave the machine)
+    Stats {
+        /// Per-command usage counts and latencies
+        #[arg(long)]
+        commands: bool,
+        /// Start recording usage
+        #[arg(long, conflicts_with = "disable")]
+        enable: bool,
+        /// Stop recording usage (keeps what was recorded)
+        #[arg(long)]
+        disable: bool,
+        /// Forget all recorded usage
+        #[arg(long)]
+        reset: bool,
+    },
+    /// Explain how a query resolves to a project, session and layout
+    Which {
+        query: String,
+        /// Workspace root to use instead of the configured ones
+        #[arg(long)]
+        workspace: Option<String>,
+    },
+    /// Write an inventory of projects and sessions for `ws compare`
+    Export {
+        /// File to write (defaults to stdout)
+        #[arg(long)]
+        output: Option<PathBuf>,
+        /// Workspace root to use instead of the configured ones
+        #[arg(long)]
+        workspace: Option<String>,
+    },
+    /// List the workspace's projects
+    List {
+        /// text (one name per line), json (with sessions and open counts)
+        /// or tsv
+        #[arg(long, value_enum, default_value_t = ListFormat::Text)]
+        format: ListFormat,
+        /// Same as --format json
+        #[arg(long, conflicts_with_all = ["format", "tsv"])]
+        json: bool,
+        /// Same as --format tsv
+        #[arg(long, conflicts_with = "format")]
+        tsv: bool,
+        /// Only live sessions
+        #[arg(long, conflicts_with = "projects")]
+        sessions: bool,
+        /// Only projects
+        #[arg(long)]
+        projects: bool,
+        /// Workspace root to use instead of the configured ones
+        #[arg(long)]
+        workspace: Option<String>,
+    },
+    /// Show what differs between two exported inventories
+    Compare {
+        left: PathBuf,
+        right: PathBuf,
+        /// Print commands that would bring each side up to date
+        #[arg(long)]
+        commands: bool,
+    },
+    /// Stop the processes of sessions (the current one by default)
+    Pause { sessions: Vec<String> },
+    /// Continue paused sessions (all of them by default), or recreate the
+    /// sessions in history after the tmux server was restarted
+    Resume { sessions: Vec<String> },
+    /// Print the active project's category for the tmux status line
+    Statusline {
+        /// Session to describe (defaults to the current session)
+        #[arg(long)]
+        session: Option<String>,
+        /// Store the category in tmux user options instead of printing it
+        #[arg(long)]
+        update: bool,
+    },
+    /// Check for the programs ws runs and list what is degraded without them
+    Doctor,
+    /// Keep the project cache up to date in the background, rescanning when
+    /// the workspace roots change
+    Daemon,
+    /// Print a shell completion script, e.g. `ws completions fish | source`
+    Completions {
+        #[arg(value_enum)]
+        shell: completions::Shell,
+    },
+    /// Print project or session names for completion scripts
+    #[command(hide = true)]
+    Complete {
+        #[arg(value_enum)]
+        names: completions::Names,
+    },
+    /// Replace this ws with the latest GitHub release
+    #[cfg(feature = "self-update")]
+    SelfUpdate {
+        /// stable (full releases) or pre (pre-releases too)
+        #[arg(long, value_enum, default_value_t = self_update::Channel::Stable)]
+        channel: self_update::Channel,
+        /// Only report whether a newer release exists
+        #[arg(long)]
+        check: bool,
+    },
+}
+
+impl Commands {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Pick { .. } => "pick",
+            Self::Archive { .. } => "archive",
+            Self::Unarchive { .. } => "unarchive",
+            Self::Pin { .. } => "pin",
+            Self::Unpin { .. } => "unpin",
+            Self::Kill { .. } => "kill",
+            Self::Gc { .. } => "gc",
+            Self::Back => "back",
+            Self::Attach { .. } => "attach",
+            Self::Refresh { .. } => "refresh",
+            Self::Open { .. } => "open",
+            Self::Switch { .. } => "switch",
+            Self::Files { .. } => "files",
+            Self::Todos { .. } => "todos",
+            Self::Run { .. } => "run",
+            Self::Branch { .. } => "branch",
+            Self::Env { .. } => "env",
+            Self::Group { .. } => "group",
+            Self::New { .. } => "new",
+            Self::Clone { .. } => "clone",
+            Self::Categories { .. } => "categories",
+            Self::Snapshot { .. } => "snapshot",
+            Self::Restore { .. } => "restore",
+            Self::Profile { .. } => "profile",
+            Self::Layout { .. } => "layout",
+            Self::Stats { .. } => "stats",
+            Self::Which { .. } => "which",
+            Self::Export { .. } => "export",
+            Self::List { .. } => "list",
+            Self::Compare { .. } => "compare",
+            Self::Pause { .. } => "pause",
+            Self::Resume { .. } => "resume",
+            Self::Statusline { .. } => "statusline",
+            Self::Doctor => "doctor",
+            Self::Daemon => "daemon",
+            Self::Completions { .. } => "completions",
+            Self::Complete { .. } => "complete",
+            Self::Health { .. } => "health",
+            Self::Import { .. } => "import",
+            #[cfg(feature = "self-update")]
+            Self::SelfUpdate { .. } => "self-update",
+        }
+    }
+
+    /// Commands built on tmux features other backends don't have.
+    fn needs_tmux(&self) -> bool {
+        matches!(
+            self,
+            Self::Gc { .. }
+                | Self::Group { .. }
+                | Self::Run { .. }
+                | Self::Snapshot { .. }
+                | Self::Restore { .. }
+                | Self::Pause { .. }
+                | Self::Resume { .. }
+                | Self::Statusline { .. }
+                | Self::Layout { .. }
+                | Self::Files { .. }
+                | Self::Todos { .. }
+        )
+    }
+}
+
+#[derive(Subcommand)]
+enum CategoryCommands {
+    /// Create a category directory in the first workspace directory
+    Create { name: String },
+    /// Move a category out of the workspace into the root's .archive
+    Archive {
+        /// Category name, or root/category when several roots have it
+        name: String,
+    },
+}
+
+/// What `ws pick` does with the selection.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum PickOutput {
+    Open,
+    /// `--print`: one path or session name per line.
+    Print,
+    /// `--print --json`.
+    Json,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum ListFormat {
+    Text,
+    Json,
+    Tsv,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum ImportSource {
+    Sesh,
+    TSmartTmux,
+    Tmuxinator,
+    Tmuxp,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum EnvShell {
+    Posix,
+    Fish,
+}
+
+#[derive(Subcommand)]
+enum SnapshotCommands {
+    /// Capture every live session with its windows, panes and directories
+    Save {
+        #[arg(default_value = AUTO_SNAPSHOT)]
+        name: String,
+    },
+    /// Recreate the sessions of a snapshot that aren't running
+    Restore {
+        #[arg(default_value = AUTO_SNAPSHOT)]
+        name: String,
+    },
+    /// List saved snapshots
+    List,
+    /// Forget a snapshot
+    Delete { name: String },
+}
+
+/// Profiles are generated from categories: a category's profile is its
+/// projects with a commit, an open or session activity in the last `days`.
+#[derive(Subcommand)]
+enum ProfileCommands {
+    /// List the categories' profiles and how many projects are active
+    List {
+        #[arg(long, default_value_t = PROFILE_ACTIVE_DAYS)]
+        days: u64,
+    },
+    /// Open a session for each active project of a category
+    Open {
+        /// Category name, or root/category when several roots have it
+        name: String,
+        #[arg(long, default_value_t = PROFILE_ACTIVE_DAYS)]
+        days: u64,
+    },
+}
+
+#[derive(Subcommand)]
+enum LayoutCommands {
+    /// Build a layout on a throwaway tmux server and report failing commands
+    Test {
+        name: String,
+        /// Project to run the layout in (defaults to the current directory)
+        #[arg(long)]
+        project: Option<String>,
+        /// Seconds to let window commands start before inspecting them
+        #[arg(long, default_value_t = LAYOUT_TEST_WAIT_SECONDS)]
+        wait: u64,
+        /// Workspace root to use instead of the configured ones
+        #[arg(long)]
+        workspace: Option<String>,
     },
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 struct ProjectInfo {
+    /// Stable ID, see `project_id`. Features that remember a project key it
+    /// by this rather than by its name or path.
+    #[serde(default)]
+    id: String,
     path: String,
     category: String,
     name: String,
+    /// Alias of the workspace root the project was found in, if any.
+    #[serde(default)]
+    root: String,
+    /// Repository status, read for the picker with `picker.git_status`.
+    #[serde(skip)]
+    git: Option<GitStatus>,
 }
 
 impl ProjectInfo {
+    /// Builds a project from its directory, using the parent directory's
+    /// name as the category.
+    fn from_path(path: &Path) -> Option<Self> {
+        let category = path.parent()?.file_name()?.to_str()?;
+        let name = path.file_name()?.to_str()?;
+        Some(Self {
+            id: project_id(path),
+            path: path.to_string_lossy().to_string(),
+            category: category.to_string(),
+            name: name.to_string(),
+            root: String::new(),
+            git: None,
+        })
+    }
+
     fn display_name(&self) -> String {
         format!("{}/{}", self.category, self.name)
     }
+
+    /// The project as shown to the user, per a `display.project` template.
+    /// Placeholders that come out empty don't leave stray spaces behind.
+    fn label(&self, template: &str) -> String {
+        let mut label = expand_project_template(template, self);
+        if label.contains("{branch}") {
+            let branch = match &self.git {
+                Some(status) => status.branch.clone(),
+                None => git_status::branch(Path::new(&self.path)).unwrap_or_default(),
+            };
+            label = label.replace("{branch}", &branch);
+        }
+        label.split_whitespace().collect::<Vec<_>>().join(" ")
+    }
+
+    /// `root/category/name`, unique across workspace roots.
+    fn qualified_name(&self) -> String {
+        if self.root.is_empty() {
+            self.display_name()
+        } else {
+            format!("{}/{}", self.root, self.display_name())
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 struct SessionInfo {
     name: String,
     last_active: i64,
+    /// Stopped with `ws pause`.
+    #[serde(default)]
+    paused: bool,
+}
+
+impl SessionInfo {
+    /// `last_active`, unless the backend doesn't report it.
+    fn active_at(&self) -> Option<i64> {
+        (self.last_active > 0).then_some(self.last_active)
+    }
 }
 
 #[derive(Debug, Clone)]
 enum SelectableItem {
     Session(String),
     Project(ProjectInfo),
+    /// A recently visited directory outside the workspace.
+    Directory(ProjectInfo),
+    /// An org repository that is not cloned yet.
+    Remote(ProjectInfo, RemoteRepo),
+    /// The home session's directory, see `HomeConfig`.
+    Home(String),
 }
 
 impl SelectableItem {
-    fn to_display_string(&self) -> String {
+    /// Picker label; projects are shown per the `display.project` template.
+    fn to_display_string(&self, template: &str) -> String {
         match self {
             Self::Session(name) => format!("session: {}", name),
-            Self::Project(info) => format!("project: {}", info.display_name()),
+            Self::Project(info) => format!("project: {}", info.label(template)),
+            Self::Directory(info) => format!("dir: {}", tilde_path(&info.path)),
+            Self::Remote(info, _) => format!("clone: {}", info.label(template)),
+            Self::Home(path) => format!("home: {}", tilde_path(path)),
         }
     }
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-struct State {
-    version: u32,
-    history: Vec<String>,
-    cache: ProjectCache,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-struct ProjectCache {
-    projects: Vec<ProjectInfo>,
-    updated_at: i64,
-    ttl: i64,
-}
-
-impl State {
-    fn load() -> Self {
-        let state_path = Self::state_path();
-        fs::read_to_string(&state_path)
-            .ok()
-            .and_then(|contents| serde_json::from_str(&contents).ok())
-            .unwrap_or_default()
-    }
 
-    fn save(&self) -> Result<()> {
-        let state_path = Self::state_path();
-        if let Some(parent) = state_path.parent() {
-            fs::create_dir_all(parent)?;
+    fn path(&self) -> Option<&str> {
+        match self {
+            Self::Session(_) => None,
+            Self::Project(info) | Self::Directory(info) | Self::Remote(info, _) => Some(&info.path),
+            Self::Home(path) => Some(path),
         }
-        let json = serde_json::to_string_pretty(self)?;
-        fs::write(&state_path, json)?;
-        Ok(())
-    }
-
-    fn state_path() -> PathBuf {
-        dirs::data_local_dir()
-            .unwrap_or_else(|| PathBuf::from("/tmp"))
-            .join("ws")
-            .join("state.json")
     }
 
-    fn push_history(&mut self, session: String) {
-        self.history.retain(|s| s != &session);
-        self.history.push(session);
-        if self.history.len() > MAX_HISTORY_SIZE {
-            self.history.remove(0);
+    fn git_status(&self) -> Option<&GitStatus> {
+        match self {
+            Self::Project(info) | Self::Directory(info) => info.git.as_ref(),
+            Self::Session(_) | Self::Remote(..) | Self::Home(_) => None,
         }
     }
 
-    fn previous_session(&self) -> Option<&str> {
-        if self.history.len() >= 2 {
-            Some(&self.history[self.history.len() - 2])
-        } else {
-            self.history.last().map(|s| s.as_str())
+    /// What `ws pick --print` writes for the item.
+    fn print_value(&self) -> &str {
+        match self {
+            Self::Session(name) => name,
+            Self::Project(info) | Self::Directory(info) | Self::Remote(info, _) => &info.path,
+            Self::Home(path) => path,
         }
     }
+}
 
-    fn cache_valid(&self) -> bool {
-        let now = current_timestamp();
-        now - self.cache.updated_at < self.cache.ttl
+#[derive(Debug, Clone)]
+struct LayoutWindow {
+    index: u32,
+    name: String,
+    /// The window's name in the layout, before `layout.window_names`
+    /// renamed it for the project's type. Windows that aren't from a layout
+    /// have none.
+    role: Option<String>,
+    /// Run by the default shell; empty starts the shell itself.
+    command: String,
+    /// Working directory, the session's when unset or relative to it.
+    path: Option<String>,
+    /// Panes split off the window's first one.
+    panes: Vec<PaneSpec>,
+    /// tmux layout applied once the panes exist.
+    arrange: Option<String>,
+}
+
+/// The directory a layout window or pane with `relative` as its path starts
+/// in: the session's `path` when unset, else `relative` resolved against it.
+fn layout_directory(path: &str, relative: &Option<String>) -> String {
+    match relative {
+        Some(relative) => Path::new(path)
+            .join(shellexpand::tilde(relative).as_ref())
+            .to_string_lossy()
+            .to_string(),
+        None => path.to_string(),
     }
+}
 
-    fn refresh_cache(&mut self, workspace: &str) -> Result<()> {
-        self.cache.projects = scan_projects(workspace)?;
-        self.cache.updated_at = current_timestamp();
-        Ok(())
+/// The command a running editor opens `file` (at `line`) with, for editors
+/// `ws files` knows.
+fn editor_open_command(editor: &str, file: &str, line: Option<u32>) -> Option<String> {
+    let file = match editor {
+        "kak" => format!("'{}'", file.replace('\'', "''")),
+        "hx" | "helix" => escape_chars(file, " \t\\'\"%"),
+        // The characters vim's fnameescape() escapes.
+        _ => escape_chars(file, " \t\n*?[{`$\\%#'\"|!<"),
+    };
+    match (editor, line) {
+        ("hx" | "helix", None) => Some(format!(":open {}", file)),
+        ("hx" | "helix", Some(line)) => Some(format!(":open {}:{}", file, line)),
+        ("vim" | "nvim" | "vi" | "kak", None) => Some(format!(":edit {}", file)),
+        ("vim" | "nvim" | "vi", Some(line)) => Some(format!(":edit +{} {}", line, file)),
+        ("kak", Some(line)) => Some(format!(":edit {} {}", file, line)),
+        _ => None,
     }
+}
 
-    fn ensure_cache_valid(&mut self, workspace: &str) -> Result<()> {
-        if !self.cache_valid() {
-            self.refresh_cache(workspace)?;
+/// `value` with a backslash before each of `special`.
+fn escape_chars(value: &str, special: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if special.contains(c) {
+            escaped.push('\\');
         }
-        Ok(())
+        escaped.push(c);
     }
+    escaped
 }
 
-impl Default for State {
-    fn default() -> Self {
-        Self {
-            version: STATE_VERSION,
-            history: Vec::new(),
-            cache: ProjectCache {
-                projects: Vec::new(),
-                updated_at: 0,
-                ttl: CACHE_TTL_SECONDS,
-            },
+/// Editor arguments opening `file` at `line`: `file:LINE` for helix, the
+/// `+LINE file` most other editors (vi, emacs, nano, kak) understand.
+fn editor_file_arguments(editor: &str, file: &str, line: Option<u32>) -> String {
+    match line {
+        None => shell_quote(file),
+        Some(line) if matches!(editor, "hx" | "helix") => {
+            shell_quote(&format!("{}:{}", file, line))
         }
+        Some(line) => format!("+{} {}", line, shell_quote(file)),
     }
 }
 
-struct TmuxClient;
+fn file_window_name(file: &str) -> String {
+    Path::new(file).file_name().map_or_else(
+        || file.to_string(),
+        |name| name.to_string_lossy().to_string(),
+    )
+}
 
-impl TmuxClient {
-    fn is_in_tmux() -> bool {
-        std::env::var("TMUX").is_ok()
+/// Runs `editor` from `shell` so that quitting the editor leaves the shell
+/// behind. fish has `-C` for this; other shells exec themselves afterwards.
+fn editor_command(shell: &str, editor: &str) -> String {
+    if editor.is_empty() {
+        shell.to_string()
+    } else if Path::new(shell).file_name() == Some("fish".as_ref()) {
+        format!("{} -C {}", shell, shell_quote(editor))
+    } else {
+        format!(
+            "{} -c {}",
+            shell,
+            shell_quote(&format!("{}; exec {}", editor, shell))
+        )
     }
+}
 
-    fn current_session() -> Result<String> {
-        let output = Command::new("tmux")
-            .args(["display-message", "-p", "#{session_name}"])
-            .output()?;
+/// Windows created for a new session. The first window is created together
+/// with the session; the rest are added at their fixed indices.
+#[derive(Debug, Clone)]
+struct Layout {
+    name: String,
+    windows: Vec<LayoutWindow>,
+    focus: u32,
+}
 
-        if output.status.success() {
-            Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
-        } else {
-            Err("Failed to get current session".into())
+impl Layout {
+    /// The editor and files windows. Without the configured file manager,
+    /// there is no files window.
+    fn builtin(config: &LayoutConfig) -> Self {
+        let mut layout = Self {
+            name: DEFAULT_LAYOUT.to_string(),
+            windows: vec![
+                LayoutWindow {
+                    index: EDITOR_WINDOW_INDEX,
+                    name: config.editor_window.clone(),
+                    role: Some(config.editor_window.clone()),
+                    command: editor_command(&config.shell, &config.editor),
+                    path: None,
+                    panes: Vec::new(),
+                    arrange: None,
+                },
+                LayoutWindow {
+                    index: FILES_WINDOW_INDEX,
+                    name: config.files_window.clone(),
+                    role: Some(config.files_window.clone()),
+                    command: config.file_manager.clone(),
+                    path: None,
+                    panes: Vec::new(),
+                    arrange: None,
+                },
+            ],
+            focus: EDITOR_WINDOW_INDEX,
+        };
+        // Checked once, as `ws open` and restores build this per session.
+        static FILE_MANAGER: OnceLock<bool> = OnceLock::new();
+        let file_manager = *FILE_MANAGER.get_or_init(|| {
+            config.file_manager.is_empty()
+                || tool_available(&config.file_manager, "sessions get no files window")
+        });
+        if !file_manager {
+            layout.windows.retain(|w| w.index != FILES_WINDOW_INDEX);
         }
+        layout
     }
 
-    fn list_sessions() -> Result<Vec<SessionInfo>> {
-        let output = Command::new("tmux")
-            .args([
-                "list-sessions",
-                "-F",
-                "#{session_name}|#{session_last_attached}",
-            ])
-            .output()?;
+    /// The home session's layout: `home.layout`, else the notes files in the
+    /// editor and a shell that starts out showing `ws stats`.
+    fn home(config: &Config) -> Result<Self> {
+        if let Some(name) = &config.home.layout {
+            return Self::named(name, config);
+        }
+        let editor = if config.layout.editor.is_empty() {
+            String::new()
+        } else {
+            std::iter::once(config.layout.editor.clone())
+                .chain(config.home.notes.iter().map(|file| shell_quote(file)))
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        let ws = std::env::current_exe().map_or_else(
+            |_| "ws".to_string(),
+            |ws| shell_quote(&ws.to_string_lossy()),
+        );
+        let window = |index: u32, name: &str, command: String| LayoutWindow {
+            index,
+            name: name.to_string(),
+            role: Some(name.to_string()),
+            command,
+            path: None,
+            panes: Vec::new(),
+            arrange: None,
+        };
+        Ok(Self {
+            name: HOME_LAYOUT.to_string(),
+            windows: vec![
+                window(
+                    EDITOR_WINDOW_INDEX,
+                    "notes",
+                    editor_command(&config.layout.shell, &editor),
+                ),
+                window(
+                    EDITOR_WINDOW_INDEX + 1,
+                    "stats",
+                    editor_command(&config.layout.shell, &format!("{} stats", ws)),
+                ),
+            ],
+            focus: EDITOR_WINDOW_INDEX,
+        })
+    }
 
-        if !output.status.success() {
-            return Ok(Vec::new());
+    /// The built-in layout or one from the layouts directory.
+    fn named(name: &str, config: &Config) -> Result<Self> {
+        if name == DEFAULT_LAYOUT {
+            return Ok(Self::builtin(&config.layout));
         }
+        match config.layout_spec(name) {
+            Ok(spec) => Self::from_spec(name, spec),
+            Err(e) => {
+                let available: Vec<String> = std::iter::once(DEFAULT_LAYOUT.to_string())
+                    .chain(config.layout_names())
+                    .collect();
+                Err(format!("{} (available: {})", e, available.join(", ")).into())
+            }
+        }
+    }
 
-        let sessions = String::from_utf8_lossy(&output.stdout);
-        Ok(sessions
-            .lines()
-            .filter_map(|line| {
-                let parts: Vec<&str> = line.split('|').collect();
-                if parts.len() >= 2 {
-                    Some(SessionInfo {
-                        name: parts[0].to_string(),
-                        last_active: parts[1].parse().unwrap_or(0),
-                    })
-                } else {
-                    None
+    /// The layout for a new session of `project`: the one its `.ws.toml`
+    /// picks or defines, else `layout.default`, else the built-in one. Its
+    /// windows are renamed per `layout.window_names` for the project's type.
+    fn for_project(project: &ProjectInfo, config: &Config) -> Result<Self> {
+        let mut layout = Self::chosen_for_project(project, config)?;
+        let names = project_type(Path::new(&project.path))
+            .and_then(|kind| config.layout.window_names.get(kind));
+        if let Some(names) = names {
+            for window in &mut layout.windows {
+                if let Some(name) = names.get(&window.name) {
+                    window.name = name.clone();
                 }
-            })
-            .collect())
+            }
+        }
+        Ok(layout)
     }
 
-    fn has_session(name: &str) -> Result<bool> {
-        let status = Command::new("tmux")
-            .args(["has-session", "-t", name])
-            .status()?;
-        Ok(status.success())
+    fn chosen_for_project(project: &ProjectInfo, config: &Config) -> Result<Self> {
+        // A broken .ws.toml shouldn't keep the project from opening.
+        let chosen = match ProjectConfig::load(&project.path) {
+            Ok(project_config) => project_config.layout,
+            Err(e) => {
+                messages::warn(e);
+                None
+            }
+        };
+        match chosen {
+            Some(ProjectLayout::Inline(spec)) => Self::from_spec(PROJECT_CONFIG_FILE, spec),
+            Some(ProjectLayout::Named(name)) => Self::named(&name, config),
+            None => {
+                let name = config
+                    .layout
+                    .projects
+                    .get(project.path.trim_end_matches('/'))
+                    .or(config.layout.default.as_ref());
+                Self::named(name.map_or(DEFAULT_LAYOUT, String::as_str), config)
+            }
+        }
     }
 
-    fn create_session(name: &str, path: &str) -> Result<()> {
-        Command::new("tmux")
-            .args([
-                "new-session",
-                "-d",
-                "-s",
-                name,
-                "-c",
-                path,
-                "-n",
-                "editor",
-                "fish -C \"hx\"",
-            ])
-            .status()?;
+    fn from_spec(name: &str, spec: LayoutSpec) -> Result<Self> {
+        let mut windows: Vec<LayoutWindow> = Vec::new();
+        for window in spec.windows {
+            let index = window.index.unwrap_or_else(|| {
+                windows
+                    .last()
+                    .map_or(EDITOR_WINDOW_INDEX, |previous| previous.index + 1)
+            });
+            if windows.iter().any(|w| w.index == index) {
+                return Err(format!("Layout '{}' uses window index {} twice", name, index).into());
+            }
+            windows.push(LayoutWindow {
+                index,
+                role: Some(window.name.clone()),
+                name: window.name,
+                command: window.command,
+                path: window.path,
+                panes: window.panes,
+                arrange: window.arrange,
+            });
+        }
 
-        Command::new("tmux")
-            .args([
-                "new-window",
-                "-t",
-                &format!("{}:{}", name, FILES_WINDOW_INDEX),
-                "-c",
-                path,
-                "-n",
-                "files",
-                "fx",
-            ])
-            .status()?;
+        let focus = match &spec.focus {
+            Some(focus) => windows
+                .iter()
+                .find(|w| &w.name == focus)
+                .map(|w| w.index)
+                .ok_or_else(|| format!("Layout '{}' has no window '{}' to focus", name, focus))?,
+            None => windows
+                .first()
+                .map(|w| w.index)
+                .ok_or_else(|| format!("Layout '{}' has no windows", name))?,
+        };
+        Ok(Self {
+            name: name.to_string(),
+            windows,
+            focus,
+        })
+    }
+}
 
-        Command::new("tmux")
-            .args([
-                "select-window",
-                "-t",
-                &format!("{}:{}", name, EDITOR_WINDOW_INDEX),
-            ])
-            .status()?;
+#[derive(Debug)]
+struct WindowInfo {
+    session: String,
+    session_path: String,
+    index: u32,
+    active: bool,
+    path: String,
+    name: String,
+    /// Command the window was started with; empty for the default shell.
+    command: String,
+    /// tmux's description of the window's pane layout, for `select-layout`.
+    layout: String,
+}
 
-        Ok(())
-    }
+/// A pane of a live window, in `list_all_panes` order.
+#[derive(Debug)]
+struct PaneInfo {
+    session: String,
+    window: u32,
+    path: String,
+    /// Command the pane was started with; empty for the default shell.
+    command: String,
+}
 
-    fn switch_client(name: &str) -> Result<()> {
-        Command::new("tmux")
-            .args(["switch-client", "-t", name])
-            .status()?;
-        Ok(())
-    }
+/// Live sessions saved by `ws snapshot save`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Snapshot {
+    saved_at: i64,
+    /// Most recently active first.
+    sessions: Vec<SnapshotSession>,
+}
 
-    fn attach_session(name: &str) -> Result<()> {
-        Command::new("tmux")
-            .args(["attach-session", "-t", name])
-            .status()?;
-        Ok(())
-    }
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SnapshotSession {
+    name: String,
+    path: String,
+    /// ID of the project the session was opened from, if ws opened it.
+    #[serde(default)]
+    project: Option<String>,
+    windows: Vec<SnapshotWindow>,
+    active: u32,
+}
 
-    fn kill_session(name: &str) -> Result<()> {
-        Command::new("tmux")
-            .args(["kill-session", "-t", name])
-            .status()?;
-        Ok(())
-    }
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SnapshotWindow {
+    index: u32,
+    name: String,
+    /// Directory and command of the first pane.
+    path: String,
+    command: String,
+    /// The other panes, and tmux's layout of them all.
+    #[serde(default)]
+    panes: Vec<SnapshotPane>,
+    #[serde(default)]
+    layout: Option<String>,
+}
 
-    fn switch_or_attach(name: &str) -> Result<()> {
-        if Self::is_in_tmux() {
-            Self::switch_client(name)
-        } else {
-            Self::attach_session(name)
-        }
-    }
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SnapshotPane {
+    path: String,
+    command: String,
 }
 
-fn scan_projects(workspace: &str) -> Result<Vec<ProjectInfo>> {
-    let workspace = shellexpand::tilde(workspace).to_string();
-    let mut projects = Vec::new();
+#[derive(Debug)]
+struct PaneStatus {
+    window: String,
+    dead: bool,
+    exit_status: Option<i32>,
+    command: String,
+}
 
-    for entry in WalkDir::new(&workspace)
-        .min_depth(PROJECT_SCAN_MIN_DEPTH)
-        .max_depth(PROJECT_SCAN_MAX_DEPTH)
-        .into_iter()
-        .filter_map(|e| e.ok())
-        .filter(|e| e.file_type().is_dir())
-    {
-        if let Some(parent) = entry.path().parent() {
-            if let (Some(category), Some(name)) = (
-                parent.file_name().and_then(|n| n.to_str()),
-                entry.file_name().to_str(),
-            ) {
-                projects.push(ProjectInfo {
-                    path: entry.path().to_string_lossy().to_string(),
-                    category: category.to_string(),
-                    name: name.to_string(),
-                });
-            }
-        }
-    }
+/// What `ws gc` looks at to decide whether a session is still in use.
+#[derive(Debug)]
+struct SessionActivity {
+    name: String,
+    /// Unix time of the last input or output in the session.
+    activity: i64,
+    attached: bool,
+    windows: usize,
+    /// Unix time a client last attached, 0 if none ever did.
+    last_attached: i64,
+}
 
-    projects.sort_by(|a, b| {
-        a.category
-            .cmp(&b.category)
-            .then_with(|| a.name.cmp(&b.name))
-    });
+/// A switch to a session, oldest first in `State::history`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(from = "StoredHistoryEntry")]
+struct HistoryEntry {
+    session: String,
+    at: i64,
+}
 
-    Ok(projects)
+/// History entries used to be bare session names, without a timestamp.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredHistoryEntry {
+    Name(String),
+    Entry { session: String, at: i64 },
 }
 
-struct Picker;
+impl From<StoredHistoryEntry> for HistoryEntry {
+    fn from(stored: StoredHistoryEntry) -> Self {
+        match stored {
+            StoredHistoryEntry::Name(session) => Self { session, at: 0 },
+            StoredHistoryEntry::Entry { session, at } => Self { session, at },
+        }
+    }
+}
 
-impl Picker {
-    fn pick(items: &[String], prompt: &str) -> Option<usize> {
+#[derive(Debug, Serialize, Deserialize)]
+struct State {
+    version: u32,
+    history: Vec<HistoryEntry>,
+    cache: ProjectCache,
+    /// Session name -> project path for sessions opened from a project.
+    #[serde(default)]
+    sessions: HashMap<String, String>,
+    #[serde(default)]
+    stats: UsageStats,
+    /// Group leader -> extra sessions created with `ws group`.
+    #[serde(default)]
+    groups: HashMap<String, Vec<String>>,
+    /// Org clone directory -> repository listing fetched from its provider.
+    #[serde(default)]
+    orgs: HashMap<String, OrgCache>,
+    #[serde(default)]
+    snapshots: BTreeMap<String, Snapshot>,
+    /// Project ID -> how often and when it was last opened.
+    #[serde(default)]
+    opens: HashMap<String, ProjectOpens>,
+    /// Project ID -> files opened with `ws files`, most recent first.
+    #[serde(default)]
+    recent_files: HashMap<String, Vec<String>>,
+    /// Projects `ws pin` put at the top of the picker, in the order they
+    /// were pinned. Kept whole, so they stay listed when a scan misses them.
+    #[serde(default)]
+    pinned: Vec<ProjectInfo>,
+    /// Detected workspaces the user chose not to save to the config.
+    #[serde(default)]
+    declined_workspaces: Vec<String>,
+    /// Cache generation this process loaded, to tell on save whether
+    /// another process refreshed the cache in the meantime.
+    #[serde(skip)]
+    loaded_generation: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ProjectOpens {
+    path: String,
+    count: u64,
+    last_opened: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct OrgCache {
+    repos: Vec<RemoteRepo>,
+    updated_at: i64,
+}
+
+/// Opt-in usage counters, keyed by command name. Only stored in the local
+/// state file.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct UsageStats {
+    enabled: bool,
+    commands: BTreeMap<String, CommandStats>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CommandStats {
+    count: u64,
+    total_ms: u64,
+    max_ms: u64,
+    last_used: i64,
+    /// Phase name (see `--timings`) -> time spent in it.
+    #[serde(default)]
+    phases: BTreeMap<String, PhaseStats>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct PhaseStats {
+    count: u64,
+    total_ms: u64,
+    max_ms: u64,
+}
+
+impl UsageStats {
+    fn record(&mut self, command: &str, elapsed_ms: u64, phases: &[(&str, Duration)]) {
+        if !self.enabled {
+            return;
+        }
+        let entry = self.commands.entry(command.to_string()).or_default();
+        entry.count += 1;
+        entry.total_ms += elapsed_ms;
+        entry.max_ms = entry.max_ms.max(elapsed_ms);
+        entry.last_used = current_timestamp();
+        for (name, elapsed) in phases {
+            let elapsed_ms = elapsed.as_millis() as u64;
+            let phase = entry.phases.entry(name.to_string()).or_default();
+            phase.count += 1;
+            phase.total_ms += elapsed_ms;
+            phase.max_ms = phase.max_ms.max(elapsed_ms);
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ProjectCache {
+    projects: Vec<ProjectInfo>,
+    updated_at: i64,
+    ttl: i64,
+    /// Root paths the cache was built from; a different set invalidates it.
+    #[serde(default)]
+    roots: Vec<String>,
+    /// Bumped by every refresh.
+    #[serde(default)]
+    generation: u64,
+}
+
+impl State {
+    fn load() -> Self {
+        timings::time("state load", || {
+            let mut state: Self = match StateMode::current() {
+                StateMode::Memory => MEMORY_STATE
+                    .lock()
+                    .unwrap()
+                    .as_deref()
+                    .and_then(|contents| serde_json::from_str(contents).ok()),
+                _ => Self::read(&Self::state_path()),
+            }
+            .unwrap_or_default();
+            // Caches written before projects had IDs.
+            for project in &mut state.cache.projects {
+                if project.id.is_empty() {
+                    project.id = project_id(Path::new(&project.path));
+                }
+            }
+            state.loaded_generation = state.cache.generation;
+            state
+        })
+    }
+
+    /// The state stored at `path`, read again after a short pause while it
+    /// doesn't parse. A file that never does is copied to `state.json.bad`
+    /// before the next save replaces it.
+    fn read(path: &Path) -> Option<Self> {
+        let mut error = None;
+        for attempt in 0..STATE_LOAD_ATTEMPTS {
+            if attempt > 0 {
+                std::thread::sleep(STATE_LOAD_RETRY_DELAY);
+            }
+            let contents = fs::read_to_string(path).ok()?;
+            match serde_json::from_str(&contents) {
+                Ok(state) => return Some(state),
+                Err(e) => error = Some(e),
+            }
+        }
+        let error = error?;
+        if StateMode::current().writes() {
+            let backup = path.with_extension("json.bad");
+            fs::copy(path, &backup).ok();
+            messages::warn(messages::STATE_UNREADABLE_MOVED.format(&[
+                &path.display(),
+                &error,
+                &backup.display(),
+            ]));
+        } else {
+            messages::warn(messages::STATE_UNREADABLE.format(&[&path.display(), &error]));
+        }
+        None
+    }
+
+    /// Writes the state under `state.lock`, so concurrent invocations don't
+    /// interleave their writes. If another process refreshed the cache
+    /// since this one loaded it, its cache is kept unless this process
+    /// refreshed too.
+    fn save(&self) -> Result<()> {
+        match StateMode::current() {
+            StateMode::Disk => {}
+            StateMode::ReadOnly => return Ok(()),
+            StateMode::Memory => {
+                *MEMORY_STATE.lock().unwrap() = Some(serde_json::to_string(self)?);
+                return Ok(());
+            }
+        }
+        let state_path = Self::state_path();
+        if let Some(parent) = state_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let lock = fs::File::create(state_path.with_file_name(STATE_LOCK_FILE))?;
+        lock.lock()?;
+
+        let mut state = serde_json::to_value(self)?;
+        let stored = fs::read_to_string(&state_path)
+            .ok()
+            .and_then(|contents| serde_json::from_str::<serde_json::Value>(&contents).ok());
+        if let Some(mut stored) = stored {
+            let stored_generation = stored["cache"]["generation"].as_u64().unwrap_or(0);
+            if stored_generation > self.loaded_generation {
+                if self.cache.generation == self.loaded_generation {
+                    state["cache"] = stored["cache"].take();
+                } else {
+                    state["cache"]["generation"] = (stored_generation + 1).into();
+                }
+            }
+        }
+
+        // Written aside, flushed to disk and renamed, so readers never see
+        // a partial file, not even after a crash.
+        let temporary = state_path.with_extension("json.tmp");
+        let mut file = fs::File::create(&temporary)?;
+        file.write_all(serde_json::to_string_pretty(&state)?.as_bytes())?;
+        file.sync_all()?;
+        fs::rename(&temporary, &state_path)?;
+        Ok(())
+    }
+
+    fn state_path() -> PathBuf {
+        DATA_DIR
+            .get()
+            .cloned()
+            .unwrap_or_else(|| {
+                dirs::data_local_dir()
+                    .unwrap_or_else(|| PathBuf::from("/tmp"))
+                    .join("ws")
+            })
+            .join("state.json")
+    }
+
+    /// Records a switch to `session`. With the `collapse` policy earlier
+    /// visits to the same session are dropped; with `every` each visit is
+    /// kept. Entries past `max_entries` or `max_age_days` are purged.
+    fn push_history(&mut self, session: String, config: &HistoryConfig) {
+        let now = current_timestamp();
+        if config.policy == HistoryPolicy::Collapse {
+            self.history.retain(|e| e.session != session);
+        }
+        self.history.push(HistoryEntry { session, at: now });
+
+        if let Some(days) = config.max_age_days {
+            let cutoff = now - days as i64 * 24 * 3600;
+            self.history.retain(|e| e.at >= cutoff);
+        }
+        if self.history.len() > config.max_entries {
+            let excess = self.history.len() - config.max_entries;
+            self.history.drain(..excess);
+        }
+    }
+
+    /// The session visited before the current one.
+    fn previous_session(&self) -> Option<&str> {
+        let current = self.history.last()?;
+        let previous = self
+            .history
+            .iter()
+            .rev()
+            .find(|e| e.session != current.session)
+            .unwrap_or(current);
+        Some(&previous.session)
+    }
+
+    /// Points what was stored for `from` at `to` after the project was
+    /// renamed or moved, and returns how many references changed. Stored
+    /// paths may no longer exist, so they match by path as well as by ID.
+    fn remap_project(&mut self, from: &ProjectInfo, to: &ProjectInfo) -> usize {
+        let mut remapped = 0;
+        if let Some(mut opens) = self.opens.remove(&from.id) {
+            opens.path = to.path.clone();
+            self.opens.insert(to.id.clone(), opens);
+        }
+        for pinned in &mut self.pinned {
+            if pinned.path == from.path || pinned.id == from.id {
+                *pinned = to.clone();
+                remapped += 1;
+            }
+        }
+        for path in self.sessions.values_mut() {
+            if *path == from.path || project_id(Path::new(path.as_str())) == from.id {
+                *path = to.path.clone();
+                remapped += 1;
+            }
+        }
+        for session in self.snapshots.values_mut().flat_map(|s| &mut s.sessions) {
+            if session.project.as_deref() == Some(from.id.as_str()) {
+                session.project = Some(to.id.clone());
+                session.path = to.path.clone();
+                remapped += 1;
+            }
+        }
+        remapped
+    }
+
+    /// Sessions in history that were lost to a tmux server restart, most
+    /// recent first. A restart is assumed when none of them is running and
+    /// the server is gone or was started after the last switch.
+    fn lost_sessions(&self, live: &[String], server_started: Option<i64>) -> Vec<String> {
+        let Some(last) = self.history.last() else {
+            return Vec::new();
+        };
+        let restarted = server_started.is_none_or(|started| started > last.at);
+        if !restarted || self.history.iter().any(|e| live.contains(&e.session)) {
+            return Vec::new();
+        }
+
+        let mut lost: Vec<String> = Vec::new();
+        for entry in self.history.iter().rev() {
+            if !lost.contains(&entry.session) {
+                lost.push(entry.session.clone());
+            }
+        }
+        lost
+    }
+
+    /// Counts an open of `project` for frecency ordering.
+    fn record_open(&mut self, project: &ProjectInfo) {
+        let opens = self
+            .opens
+            .entry(project.id.clone())
+            .or_insert_with(|| ProjectOpens {
+                path: project.path.clone(),
+                count: 0,
+                last_opened: 0,
+            });
+        opens.count += 1;
+        opens.last_opened = current_timestamp();
+    }
+
+    /// Counts picking a running session as an open of its project.
+    fn record_session_open(&mut self, session: &str) {
+        let Some(path) = self.sessions.get(session) else {
+            return;
+        };
+        if let Some(opens) = self.opens.values_mut().find(|o| &o.path == path) {
+            opens.count += 1;
+            opens.last_opened = current_timestamp();
+        }
+    }
+
+    /// How often a project was opened, weighted by how long ago it was last
+    /// opened, as zoxide ranks directories.
+    fn frecency(&self, project_id: &str, now: i64) -> f64 {
+        let Some(opens) = self.opens.get(project_id) else {
+            return 0.0;
+        };
+        let age = now - opens.last_opened;
+        let weight = if age < 3600 {
+            4.0
+        } else if age < 86400 {
+            2.0
+        } else if age < 7 * 86400 {
+            0.5
+        } else {
+            0.25
+        };
+        opens.count as f64 * weight
+    }
+
+    /// `frecency` of the project a session was opened for.
+    fn session_frecency(&self, session: &str, now: i64) -> f64 {
+        let Some(path) = self.sessions.get(session) else {
+            return 0.0;
+        };
+        self.opens
+            .iter()
+            .find(|(_, o)| &o.path == path)
+            .map_or(0.0, |(id, _)| self.frecency(id, now))
+    }
+
+    /// Paths of the projects behind the sessions in history.
+    fn visited_paths(&self) -> HashSet<&str> {
+        self.history
+            .iter()
+            .filter_map(|e| self.sessions.get(&e.session))
+            .map(|path| path.as_str())
+            .collect()
+    }
+
+    /// Finds a cached project by `category/name` (or `root/category/name` when
+    /// several roots share it) or, failing that, by a unique bare name.
+    fn find_project(&self, query: &str) -> Result<&ProjectInfo> {
+        find_project_in(&self.cache.projects, query)
+    }
+
+    /// Adds a project to the cache without a full rescan, keeping the
+    /// category/name ordering of `scan_projects`.
+    fn add_project(&mut self, project: ProjectInfo) {
+        self.cache.projects.retain(|p| p.path != project.path);
+        let index = self
+            .cache
+            .projects
+            .partition_point(|p| (&p.category, &p.name) < (&project.category, &project.name));
+        self.cache.projects.insert(index, project);
+    }
+
+    fn is_pinned(&self, project: &ProjectInfo) -> bool {
+        self.pinned.iter().any(|p| p.path == project.path)
+    }
+
+    /// The project at `path`: the cached one, else one read from the
+    /// directory, for projects the cache doesn't have (yet).
+    fn project_for_path(&self, path: &str) -> Option<ProjectInfo> {
+        self.cache
+            .projects
+            .iter()
+            .find(|p| p.path == path)
+            .cloned()
+            .or_else(|| ProjectInfo::from_path(Path::new(path)))
+    }
+
+    fn project_for_session(&self, session: &str, naming: &SessionConfig) -> Option<&ProjectInfo> {
+        match self.sessions.get(session) {
+            Some(path) => self.cache.projects.iter().find(|p| &p.path == path),
+            None => {
+                let names = naming::SessionNames::new(&self.cache.projects, naming);
+                self.cache
+                    .projects
+                    .iter()
+                    .find(|p| names.name(p).is_ok_and(|name| name == session))
+            }
+        }
+    }
+
+    /// Every session sharing a group with `session`, leader first. Sessions
+    /// outside any group are returned on their own.
+    fn session_group(&self, session: &str) -> Vec<String> {
+        let leader = self
+            .groups
+            .iter()
+            .find(|(_, members)| members.iter().any(|m| m == session))
+            .map_or(session, |(leader, _)| leader.as_str());
+
+        let mut group = vec![leader.to_string()];
+        if let Some(members) = self.groups.get(leader) {
+            group.extend(members.iter().cloned());
+        }
+        group
+    }
+
+    fn forget_sessions(&mut self, sessions: &[String]) {
+        self.history.retain(|e| !sessions.contains(&e.session));
+        for session in sessions {
+            self.sessions.remove(session);
+            self.groups.remove(session);
+        }
+        for members in self.groups.values_mut() {
+            members.retain(|m| !sessions.contains(m));
+        }
+        self.groups.retain(|_, members| !members.is_empty());
+    }
+
+    /// Whether the cache can be used without a rescan. Staleness is judged by
+    /// wall-clock time, which can jump; `ws refresh` always rescans, whatever
+    /// the timestamps say.
+    fn cache_valid(&self, roots: &[WorkspaceRoot]) -> bool {
+        is_fresh(self.cache.updated_at, self.cache.ttl, current_timestamp())
+            && self.cache.roots == root_paths(roots)
+    }
+
+    /// Whether the cache has expired but was built from `roots`, so it is
+    /// still worth showing while it is refreshed.
+    fn cache_stale(&self, roots: &[WorkspaceRoot]) -> bool {
+        self.cache.updated_at != 0
+            && self.cache.roots == root_paths(roots)
+            && !self.cache_valid(roots)
+    }
+
+    /// Rescans the workspace roots and reports the projects that were not in
+    /// the previous cache. A cache that was never populated, or was built
+    /// from other roots, reports nothing new. Cached projects behind paths
+    /// that could not be read are kept rather than dropped.
+    fn refresh_cache(&mut self, config: &Config) -> Refresh {
+        let started = Instant::now();
+        let mut projects = Vec::new();
+        let mut errors = Vec::new();
+        for root in &config.workspaces {
+            let (found, failed) = scan_projects(root, &config.scan);
+            projects.extend(found);
+            errors.extend(failed);
+        }
+        for cached in &self.cache.projects {
+            if errors.iter().any(|e| e.covers(cached))
+                && !projects.iter().any(|p| p.path == cached.path)
+            {
+                projects.push(cached.clone());
+            }
+        }
+        projects
+            .sort_by(|a, b| (&a.category, &a.name, &a.root).cmp(&(&b.category, &b.name, &b.root)));
+        self.stats
+            .record("scan", started.elapsed().as_millis() as u64, &[]);
+        let new_projects =
+            if self.cache.updated_at == 0 || self.cache.roots != root_paths(&config.workspaces) {
+                Vec::new()
+            } else {
+                projects
+                    .iter()
+                    .filter(|p| !self.cache.projects.iter().any(|c| c.path == p.path))
+                    .cloned()
+                    .collect()
+            };
+
+        for pinned in &mut self.pinned {
+            if let Some(found) = projects.iter().find(|p| p.path == pinned.path) {
+                *pinned = found.clone();
+            }
+        }
+        self.cache.projects = projects;
+        self.cache.updated_at = current_timestamp();
+        self.cache.roots = root_paths(&config.workspaces);
+        self.cache.generation += 1;
+        self.prune(config);
+        Refresh {
+            new_projects,
+            errors,
+        }
+    }
+
+    /// Drops what state keeps about projects that are gone: session mappings
+    /// to deleted directories and repository listings of orgs no longer in
+    /// the config. Runs with every rescan so state doesn't grow over months
+    /// of use.
+    fn prune(&mut self, config: &Config) {
+        // Only a definite NotFound counts; an unreadable path may come back.
+        let deleted = |path: &str| {
+            fs::symlink_metadata(path).is_err_and(|e| e.kind() == std::io::ErrorKind::NotFound)
+        };
+        self.sessions.retain(|_, path| !deleted(path));
+        self.opens.retain(|_, opens| !deleted(&opens.path));
+        self.pinned.retain(|project| !deleted(&project.path));
+        self.orgs
+            .retain(|path, _| config.orgs.iter().any(|org| &org.path == path));
+    }
+
+    /// The org's repositories, fetched again once the listing is older than
+    /// the org's TTL or when `force` is set. A failed fetch falls back to the
+    /// stale listing if there is one.
+    fn org_repos(&mut self, org: &OrgConfig, force: bool) -> Result<&[RemoteRepo]> {
+        let fresh = self
+            .orgs
+            .get(&org.path)
+            .is_some_and(|cache| is_fresh(cache.updated_at, org.ttl, current_timestamp()));
+
+        if force || !fresh {
+            match org::fetch_repos(org) {
+                Ok(repos) => {
+                    self.orgs.insert(
+                        org.path.clone(),
+                        OrgCache {
+                            repos,
+                            updated_at: current_timestamp(),
+                        },
+                    );
+                }
+                Err(e) if self.orgs.contains_key(&org.path) => {
+                    messages::warn(messages::USING_CACHED_REPOSITORIES.format(&[&org.name, &e]));
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(&self.orgs[&org.path].repos)
+    }
+
+    fn ensure_cache_valid(&mut self, config: &Config) -> Result<()> {
+        if timings::time("cache check", || self.cache_valid(&config.workspaces)) {
+            return Ok(());
+        }
+        let refresh = timings::time("scan", || self.refresh_cache(config));
+        if !refresh.new_projects.is_empty() {
+            notify(&new_projects_message(
+                &refresh.new_projects,
+                &config.display.project,
+            ));
+        }
+        if !refresh.errors.is_empty() {
+            notify(&format!(
+                "ws: {} unreadable path(s) during scan, run `ws refresh` for details",
+                refresh.errors.len()
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self {
+            version: STATE_VERSION,
+            history: Vec::new(),
+            cache: ProjectCache {
+                projects: Vec::new(),
+                updated_at: 0,
+                ttl: CACHE_TTL_SECONDS,
+                roots: Vec::new(),
+                generation: 0,
+            },
+            sessions: HashMap::new(),
+            stats: UsageStats::default(),
+            groups: HashMap::new(),
+            orgs: HashMap::new(),
+            snapshots: BTreeMap::new(),
+            opens: HashMap::new(),
+            recent_files: HashMap::new(),
+            pinned: Vec::new(),
+            declined_workspaces: Vec::new(),
+            loaded_generation: 0,
+        }
+    }
+}
+
+/// Runs tmux commands again when they fail only because the server couldn't
+/// be reached for a moment: it is still starting up, or a `kill-server` left
+/// its socket behind. Other failures, "no server running" or a missing
+/// session among them, are returned at once.
+trait Retry {
+    fn output_retrying(&mut self) -> std::io::Result<std::process::Output>;
+    /// `status` with retries; tmux's output is passed through once final.
+    fn status_retrying(&mut self) -> std::io::Result<std::process::ExitStatus>;
+}
+
+impl Retry for Command {
+    fn output_retrying(&mut self) -> std::io::Result<std::process::Output> {
+        let mut delay = TMUX_RETRY_DELAY;
+        for _ in 1..TMUX_ATTEMPTS {
+            let output = self.output()?;
+            if output.status.success() || !is_transient_tmux_error(&output.stderr) {
+                return Ok(output);
+            }
+            std::thread::sleep(delay);
+            delay *= 2;
+        }
+        self.output()
+    }
+
+    fn status_retrying(&mut self) -> std::io::Result<std::process::ExitStatus> {
+        let output = self.output_retrying()?;
+        std::io::stdout().write_all(&output.stdout)?;
+        std::io::stderr().write_all(&output.stderr)?;
+        Ok(output.status)
+    }
+}
+
+/// Whether tmux failed to reach its server rather than to carry out the
+/// command, so that running it again can succeed.
+fn is_transient_tmux_error(stderr: &[u8]) -> bool {
+    let stderr = String::from_utf8_lossy(stderr);
+    [
+        "Connection refused",
+        "Resource temporarily unavailable",
+        "server exited unexpectedly",
+        "lost server",
+    ]
+    .iter()
+    .any(|message| stderr.contains(message))
+}
+
+struct TmuxClient;
+
+impl TmuxClient {
+    fn command() -> Command {
+        let mut command = Command::new("tmux");
+        if let Some(socket) = TMUX_SOCKET.get() {
+            command.args(socket.args());
+        }
+        command
+    }
+
+    /// Routes every following tmux call to a separate server.
+    fn use_socket(socket: TmuxSocket) -> Result<()> {
+        TMUX_SOCKET
+            .set(socket)
+            .map_err(|_| "Already using a separate tmux server".into())
+    }
+
+    /// Whether ws runs inside the tmux server it talks to. With a separate
+    /// server, an enclosing tmux belongs to someone else.
+    fn is_in_tmux() -> bool {
+        std::env::var("TMUX").is_ok() && TMUX_SOCKET.get().is_none()
+    }
+
+    /// When the tmux server was started, or `None` if none is running.
+    fn server_start_time() -> Option<i64> {
+        let output = Self::command()
+            .args(["display-message", "-p", "#{start_time}"])
+            .output_retrying()
+            .ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        String::from_utf8_lossy(&output.stdout).trim().parse().ok()
+    }
+
+    fn current_session() -> Result<String> {
+        let mut command = Self::command();
+        command.args(["display-message", "-p"]);
+        if let Ok(pane) = std::env::var("TMUX_PANE") {
+            command.args(["-t", &pane]);
+        }
+        let output = command.arg("#{session_name}").output_retrying()?;
+
+        if output.status.success() {
+            Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+        } else {
+            Err(messages::CURRENT_SESSION_FAILED.text().into())
+        }
+    }
+
+    fn list_sessions() -> Result<Vec<SessionInfo>> {
+        let format = Format::new(&["session_name", "session_last_attached", PAUSED_OPTION]);
+        let output = Self::command()
+            .args(["list-sessions", "-F", &format.template()])
+            .output_retrying()?;
+
+        if !output.status.success() {
+            return Ok(Vec::new());
+        }
+
+        Ok(format
+            .rows(&output.stdout)
+            .into_iter()
+            .filter(|row| !row.get("session_name").is_empty())
+            .map(|row| SessionInfo {
+                name: row.get("session_name").to_string(),
+                last_active: row.parse("session_last_attached").unwrap_or(0),
+                paused: row.flag(PAUSED_OPTION),
+            })
+            .collect())
+    }
+
+    fn session_activity() -> Result<Vec<SessionActivity>> {
+        let format = Format::new(&[
+            "session_name",
+            "session_activity",
+            "session_attached",
+            "session_windows",
+            "session_last_attached",
+        ]);
+        let output = Self::command()
+            .args(["list-sessions", "-F", &format.template()])
+            .output_retrying()?;
+
+        if !output.status.success() {
+            return Ok(Vec::new());
+        }
+
+        Ok(format
+            .rows(&output.stdout)
+            .into_iter()
+            .filter(|row| !row.get("session_name").is_empty())
+            .map(|row| SessionActivity {
+                name: row.get("session_name").to_string(),
+                activity: row.parse("session_activity").unwrap_or(0),
+                attached: row.flag("session_attached"),
+                windows: row.parse("session_windows").unwrap_or(0),
+                last_attached: row.parse("session_last_attached").unwrap_or(0),
+            })
+            .collect())
+    }
+
+    fn has_session(name: &str) -> Result<bool> {
+        let status = Self::command()
+            .args(["has-session", "-t", &session_target(name)])
+            .status_retrying()?;
+        Ok(status.success())
+    }
+
+    /// Creates a detached session laid out per `layout`. `env` is set in the
+    /// session environment, so every window started in it inherits it. The
+    /// session's own directory is always `path`, so windows opened by hand
+    /// later start in the project even if the first window doesn't.
+    fn create_session(
+        name: &str,
+        path: &str,
+        layout: &Layout,
+        env: &[(String, String)],
+    ) -> Result<()> {
+        let Some((first, rest)) = layout.windows.split_first() else {
+            return Err(format!("Layout '{}' has no windows", layout.name).into());
+        };
+        let mut command = Self::command();
+        command.args(["new-session", "-d", "-s", name, "-c", path]);
+        for (key, value) in env {
+            command.arg("-e").arg(format!("{}={}", key, value));
+        }
+        command.args(["-n", &first.name]);
+        if !first.command.is_empty() && first.path.is_none() {
+            command.args(["--", &first.command]);
+        }
+        command.status_retrying()?;
+        // The first window gets the server's base-index, not its own index.
+        let first_target = format!("{}:", session_target(name));
+        if first.path.is_some() {
+            let mut command = Self::command();
+            command.args([
+                "respawn-pane",
+                "-k",
+                "-t",
+                &first_target,
+                "-c",
+                &layout_directory(path, &first.path),
+            ]);
+            if !first.command.is_empty() {
+                command.args(["--", &first.command]);
+            }
+            command.status_retrying()?;
+        }
+        Self::split_panes(&first_target, first, path)?;
+        Self::tag_window(&first_target, first)?;
+
+        for window in rest {
+            let target = window_target(name, &window.index.to_string());
+            let mut command = Self::command();
+            command.args([
+                "new-window",
+                "-t",
+                &target,
+                "-c",
+                &layout_directory(path, &window.path),
+                "-n",
+                &window.name,
+            ]);
+            if !window.command.is_empty() {
+                command.args(["--", &window.command]);
+            }
+            command.status_retrying()?;
+            Self::split_panes(&target, window, path)?;
+            Self::tag_window(&target, window)?;
+        }
+
+        Self::command()
+            .args([
+                "select-window",
+                "-t",
+                &window_target(name, &layout.focus.to_string()),
+            ])
+            .status_retrying()?;
+
+        Ok(())
+    }
+
+    /// Records which layout window `target` is and the name ws gave it, so
+    /// `rename_windows` can follow later changes to the layout's names.
+    fn tag_window(target: &str, window: &LayoutWindow) -> Result<()> {
+        let Some(role) = &window.role else {
+            return Ok(());
+        };
+        let name = &window.name;
+        Self::command()
+            .args(["set-option", "-w", "-t", target, WINDOW_ROLE_OPTION, role])
+            .arg(";")
+            .args(["set-option", "-w", "-t", target, WINDOW_NAME_OPTION, name])
+            .status_retrying()?;
+        Ok(())
+    }
+
+    /// Renames the session's windows whose layout name now maps to another
+    /// name. Windows renamed by hand since ws named them are left alone.
+    fn rename_windows(session: &str, layout: &Layout) -> Result<()> {
+        let format = Format::new(&[
+            "window_index",
+            WINDOW_ROLE_OPTION,
+            WINDOW_NAME_OPTION,
+            "window_name",
+        ]);
+        let output = Self::command()
+            .args([
+                "list-windows",
+                "-t",
+                &session_target(session),
+                "-F",
+                &format.template(),
+            ])
+            .output_retrying()?;
+        if !output.status.success() {
+            return Err(messages::LIST_WINDOWS_FAILED.format(&[&session]).into());
+        }
+
+        for row in format.rows(&output.stdout) {
+            let index = row.get("window_index");
+            let role = row.get(WINDOW_ROLE_OPTION);
+            let current = row.get("window_name");
+            if role.is_empty() || current != row.get(WINDOW_NAME_OPTION) {
+                continue;
+            }
+            let Some(window) = layout
+                .windows
+                .iter()
+                .find(|w| w.role.as_deref() == Some(role))
+            else {
+                continue;
+            };
+            if window.name != current {
+                let target = window_target(session, index);
+                Self::command()
+                    .args(["rename-window", "-t", &target, &window.name])
+                    .status_retrying()?;
+                Self::tag_window(&target, window)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds the layout window's extra panes to the tmux window `target`,
+    /// arranges them and goes back to the first pane. Relative pane paths are
+    /// relative to `path`, the session's.
+    fn split_panes(target: &str, window: &LayoutWindow, path: &str) -> Result<()> {
+        if window.panes.is_empty() {
+            return Ok(());
+        }
+        for pane in &window.panes {
+            let mut command = Self::command();
+            command.args([
+                "split-window",
+                "-t",
+                target,
+                "-c",
+                &layout_directory(path, &pane.path),
+            ]);
+            if pane.horizontal {
+                command.arg("-h");
+            }
+            if !pane.command.is_empty() {
+                command.args(["--", &pane.command]);
+            }
+            command.status_retrying()?;
+        }
+        if let Some(arrange) = &window.arrange {
+            Self::command()
+                .args(["select-layout", "-t", target, arrange])
+                .status_retrying()?;
+        }
+        Self::command()
+            .args(["select-pane", "-t", &format!("{}.{{top-left}}", target)])
+            .status_retrying()?;
+        Ok(())
+    }
+
+    /// Every window of every session, with what its active pane runs.
+    fn list_all_windows() -> Result<Vec<WindowInfo>> {
+        let format = Format::new(&[
+            "session_name",
+            "session_path",
+            "window_index",
+            "window_active",
+            "pane_current_path",
+            "window_name",
+            "pane_start_command",
+            "window_layout",
+        ]);
+        let output = Self::command()
+            .args(["list-windows", "-a", "-F", &format.template()])
+            .output_retrying()?;
+
+        if !output.status.success() {
+            return Ok(Vec::new());
+        }
+
+        Ok(format
+            .rows(&output.stdout)
+            .into_iter()
+            .filter_map(|row| {
+                Some(WindowInfo {
+                    index: row.parse("window_index")?,
+                    session: row.get("session_name").to_string(),
+                    session_path: row.get("session_path").to_string(),
+                    active: row.flag("window_active"),
+                    path: row.get("pane_current_path").to_string(),
+                    name: row.get("window_name").to_string(),
+                    command: unquote_start_command(row.get("pane_start_command")),
+                    layout: row.get("window_layout").to_string(),
+                })
+            })
+            .collect())
+    }
+
+    /// Every pane of every session, by session, window and pane index.
+    fn list_all_panes() -> Result<Vec<PaneInfo>> {
+        let format = Format::new(&[
+            "session_name",
+            "window_index",
+            "pane_current_path",
+            "pane_start_command",
+        ]);
+        let output = Self::command()
+            .args(["list-panes", "-a", "-F", &format.template()])
+            .output_retrying()?;
+
+        if !output.status.success() {
+            return Ok(Vec::new());
+        }
+
+        Ok(format
+            .rows(&output.stdout)
+            .into_iter()
+            .filter_map(|row| {
+                Some(PaneInfo {
+                    session: row.get("session_name").to_string(),
+                    window: row.parse("window_index")?,
+                    path: row.get("pane_current_path").to_string(),
+                    command: unquote_start_command(row.get("pane_start_command")),
+                })
+            })
+            .collect())
+    }
+
+    fn list_panes(session: &str) -> Result<Vec<PaneStatus>> {
+        let format = Format::new(&[
+            "window_name",
+            "pane_dead",
+            "pane_dead_status",
+            "pane_current_command",
+        ]);
+        let output = Self::command()
+            .args([
+                "list-panes",
+                "-s",
+                "-t",
+                &session_target(session),
+                "-F",
+                &format.template(),
+            ])
+            .output_retrying()?;
+
+        if !output.status.success() {
+            return Err(messages::LIST_PANES_FAILED.format(&[&session]).into());
+        }
+
+        Ok(format
+            .rows(&output.stdout)
+            .into_iter()
+            .map(|row| PaneStatus {
+                window: row.get("window_name").to_string(),
+                dead: row.flag("pane_dead"),
+                exit_status: row.parse("pane_dead_status"),
+                command: row.get("pane_current_command").to_string(),
+            })
+            .collect())
+    }
+
+    fn create_grouped_session(name: &str, target: &str) -> Result<()> {
+        let status = Self::command()
+            .args([
+                "new-session",
+                "-d",
+                "-s",
+                name,
+                "-t",
+                &session_target(target),
+            ])
+            .status_retrying()?;
+
+        if status.success() {
+            Ok(())
+        } else {
+            Err(messages::CREATE_GROUPED_FAILED
+                .format(&[&name, &target])
+                .into())
+        }
+    }
+
+    fn set_global_option(option: &str, value: &str) -> Result<()> {
+        Self::command()
+            .args(["set-option", "-g", option, value])
+            .output_retrying()?;
+        Ok(())
+    }
+
+    fn kill_server() -> Result<()> {
+        Self::command().arg("kill-server").output_retrying()?;
+        Ok(())
+    }
+
+    fn display_message(message: &str) -> Result<()> {
+        let output = Self::command()
+            .args(["display-message", "--", message])
+            .output_retrying()?;
+        if !output.status.success() {
+            return Err(String::from_utf8_lossy(&output.stderr).trim().into());
+        }
+        Ok(())
+    }
+
+    /// Shows `message` on every attached client, for processes that run
+    /// outside tmux and so have no client of their own to show it on.
+    fn display_message_everywhere(message: &str) -> Result<()> {
+        let format = Format::new(&["client_name"]);
+        let output = Self::command()
+            .args(["list-clients", "-F", &format.template()])
+            .output_retrying()?;
+        if !output.status.success() {
+            return Ok(());
+        }
+        for row in format.rows(&output.stdout) {
+            Self::command()
+                .args([
+                    "display-message",
+                    "-c",
+                    row.get("client_name"),
+                    "--",
+                    message,
+                ])
+                .output_retrying()?;
+        }
+        Ok(())
+    }
+
+    fn select_window(session: &str, window: &str) -> Result<()> {
+        let status = Self::command()
+            .args(["select-window", "-t", &window_target(session, window)])
+            .status_retrying()?;
+
+        if status.success() {
+            Ok(())
+        } else {
+            Err(messages::NO_WINDOW.format(&[&window, &session]).into())
+        }
+    }
+
+    /// Opens `file` in the session's editor window: through the editor's own
+    /// command line if it is running there, else by starting the editor from
+    /// the window's shell. Editors ws can't drive get a window of their own.
+    /// In a `fresh` session the editor may still be starting, so the window
+    /// is restarted with the file instead.
+    fn open_file(
+        session: &str,
+        file: &str,
+        line: Option<u32>,
+        layout: &LayoutConfig,
+        fresh: bool,
+    ) -> Result<()> {
+        let editor = layout.editor.split_whitespace().next().unwrap_or("");
+        let editor_name = Path::new(editor)
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let target = window_target(session, &layout.editor_window);
+        // The editor runs under the window's shell, which tmux reports as
+        // the pane's command, so its processes are looked through instead.
+        let output = Self::command()
+            .args(["display-message", "-p", "-t", &target, "#{pane_pid}"])
+            .output_retrying()?;
+        let pane_pid: Option<u32> = output
+            .status
+            .success()
+            .then(|| String::from_utf8_lossy(&output.stdout).trim().parse().ok())
+            .flatten();
+        let running = match pane_pid {
+            Some(pid) => {
+                let mut pids = vec![pid];
+                pids.extend(process::descendants(&[pid])?);
+                Some(process::command_names(&pids)?)
+            }
+            None => None,
+        };
+
+        let send = |keys: &[&str]| -> Result<()> {
+            let status = Self::command()
+                .args(["send-keys", "-t", &target])
+                .args(keys)
+                .status_retrying()?;
+            if !status.success() {
+                return Err(messages::SEND_KEYS_FAILED.format(&[&target]).into());
+            }
+            Ok(())
+        };
+        let editor_with_file = format!(
+            "{} {}",
+            if layout.editor.is_empty() {
+                "${EDITOR:-vi}"
+            } else {
+                &layout.editor
+            },
+            editor_file_arguments(&editor_name, file, line)
+        );
+        match running {
+            Some(_) if fresh => {
+                let status = Self::command()
+                    .args(["respawn-pane", "-k", "-t", &target])
+                    .arg(editor_command(&layout.shell, &editor_with_file))
+                    .status_retrying()?;
+                if !status.success() {
+                    return Err(messages::OPEN_FILE_FAILED.format(&[&file, &target]).into());
+                }
+            }
+            Some(commands) if !editor.is_empty() && commands.contains(&editor_name) => {
+                let Some(open) = editor_open_command(&editor_name, file, line) else {
+                    let directory = Path::new(file)
+                        .parent()
+                        .map(|dir| dir.to_string_lossy().to_string())
+                        .unwrap_or_default();
+                    return Self::run_in_window(
+                        session,
+                        &file_window_name(file),
+                        &directory,
+                        &editor_with_file,
+                    );
+                };
+                // Right after Escape, a `:` reads as Alt-: to the editor.
+                send(&["Escape"])?;
+                std::thread::sleep(ESCAPE_DELAY);
+                send(&["-l", &open])?;
+                send(&["Enter"])?;
+            }
+            Some(_) => {
+                send(&["-l", &editor_with_file])?;
+                send(&["Enter"])?;
+            }
+            None => {
+                return Err(format!(
+                    "Session '{}' has no '{}' window to open {} in",
+                    session, layout.editor_window, file
+                )
+                .into())
+            }
+        }
+        Self::select_window(session, &layout.editor_window)
+    }
+
+    /// Runs `command` in the session's window called `window`, replacing
+    /// whatever ran there. The window stays open when the command exits, so
+    /// its output can be read.
+    fn run_in_window(session: &str, window: &str, path: &str, command: &str) -> Result<()> {
+        let target = window_target(session, window);
+        let exists = Self::command()
+            .args(["list-panes", "-t", &target])
+            .output_retrying()?
+            .status
+            .success();
+
+        // A new window starts with a shell: a quick command would exit and
+        // close the window before remain-on-exit could be set on it.
+        if !exists {
+            Self::command()
+                .args([
+                    "new-window",
+                    "-d",
+                    "-t",
+                    &format!("{}:", session_target(session)),
+                    "-n",
+                    window,
+                    "-c",
+                    path,
+                ])
+                .status_retrying()?;
+        }
+        Self::command()
+            .args(["set-option", "-w", "-t", &target, "remain-on-exit", "on"])
+            .status_retrying()?;
+
+        let status = Self::command()
+            .args(["respawn-window", "-k", "-t", &target, "-c", path, command])
+            .status_retrying()?;
+        if !status.success() {
+            return Err(messages::START_WINDOW_FAILED
+                .format(&[&window, &session])
+                .into());
+        }
+        Ok(())
+    }
+
+    /// set-option takes a pane target, where a bare `=name` does not resolve;
+    /// the trailing `:` makes it the session's current pane.
+    fn set_session_option(name: &str, option: &str, value: &str) -> Result<()> {
+        let status = Self::command()
+            .args([
+                "set-option",
+                "-t",
+                &format!("{}:", session_target(name)),
+                "--",
+                option,
+                value,
+            ])
+            .output_retrying()?
+            .status;
+        if !status.success() {
+            return Err(messages::SET_OPTION_FAILED.format(&[&option, &name]).into());
+        }
+        Ok(())
+    }
+
+    fn unset_session_option(name: &str, option: &str) -> Result<()> {
+        let status = Self::command()
+            .args([
+                "set-option",
+                "-u",
+                "-t",
+                &format!("{}:", session_target(name)),
+                "--",
+                option,
+            ])
+            .output_retrying()?
+            .status;
+        if !status.success() {
+            return Err(messages::UNSET_OPTION_FAILED
+                .format(&[&option, &name])
+                .into());
+        }
+        Ok(())
+    }
+
+    fn pane_pids(session: &str) -> Result<Vec<u32>> {
+        let output = Self::command()
+            .args([
+                "list-panes",
+                "-s",
+                "-t",
+                &session_target(session),
+                "-F",
+                "#{pane_pid}",
+            ])
+            .output_retrying()?;
+
+        if !output.status.success() {
+            return Err(messages::LIST_PANES_FAILED.format(&[&session]).into());
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter_map(|line| line.trim().parse().ok())
+            .collect())
+    }
+
+    /// Asks `question` in the status line of `client` with
+    /// `confirm-before`, which returns once it is answered. The answer is
+    /// passed back through a server option named after this process.
+    fn confirm(client: &str, question: &str) -> Result<bool> {
+        let option = format!("@ws-confirmed-{}", std::process::id());
+        let status = Self::command()
+            .args([
+                "confirm-before",
+                "-t",
+                client,
+                "-p",
+                &format!("{} (y/n)", question.replace('#', "##")),
+                &format!("set-option -g {} 1", option),
+            ])
+            .status_retrying()?;
+        let output = Self::command()
+            .args(["show-options", "-gqv", &option])
+            .output_retrying()?;
+        Self::command()
+            .args(["set-option", "-gu", &option])
+            .status_retrying()
+            .ok();
+        if !status.success() && output.stdout.is_empty() {
+            return Ok(false);
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).trim() == "1")
+    }
+
+    /// The client that ws was started from: the most recently active client
+    /// showing the session of `$TMUX_PANE`. Without `-c`, tmux picks its own
+    /// "current" client, which may be another terminal attached elsewhere.
+    /// Key bindings' `run-shell` commands have no pane, so for those it is
+    /// the most recently active client of any session.
+    fn invoking_client() -> Option<String> {
+        let session_id = match std::env::var("TMUX_PANE") {
+            Ok(pane) => {
+                let output = Self::command()
+                    .args(["display-message", "-p", "-t", &pane, "#{session_id}"])
+                    .output_retrying()
+                    .ok()?;
+                Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+            }
+            Err(_) => None,
+        };
+
+        let format = Format::new(&["client_activity", "session_id", "client_name"]);
+        let output = Self::command()
+            .args(["list-clients", "-F", &format.template()])
+            .output_retrying()
+            .ok()?;
+
+        format
+            .rows(&output.stdout)
+            .into_iter()
+            .filter_map(|row| {
+                let activity: i64 = row.parse("client_activity")?;
+                session_id
+                    .as_deref()
+                    .is_none_or(|id| id == row.get("session_id"))
+                    .then(|| (activity, row.get("client_name").to_string()))
+            })
+            .max()
+            .map(|(_, client)| client)
+    }
+
+    fn switch_client(name: &str) -> Result<()> {
+        let mut command = Self::command();
+        command.arg("switch-client");
+        if let Some(client) = Self::invoking_client() {
+            command.args(["-c", &client]);
+        }
+        let status = command
+            .args(["-t", &session_target(name)])
+            .status_retrying()?;
+        if status.success() {
+            Ok(())
+        } else {
+            Err(messages::NO_SESSION_NAMED.format(&[&name]).into())
+        }
+    }
+
+    /// Switches the invoking client to the pane with this ID, e.g. `%3`,
+    /// making it the active pane of its window.
+    fn focus_pane(pane: &str) -> Result<()> {
+        let mut command = Self::command();
+        command.arg("switch-client");
+        if let Some(client) = Self::invoking_client() {
+            command.args(["-c", &client]);
+        }
+        let status = command.args(["-t", pane]).status_retrying()?;
+        if status.success() {
+            Ok(())
+        } else {
+            Err(messages::NO_PANE.format(&[&pane]).into())
+        }
+    }
+
+    /// Replaces the current process with `tmux attach-session`, so signals
+    /// and the exit status belong to tmux rather than a waiting ws process.
+    fn exec_attach(name: &str) -> Result<()> {
+        // tmux refuses to attach from inside another session while $TMUX is
+        // set; attaching to a separate server is nesting on purpose.
+        let mut command = Self::command();
+        if TMUX_SOCKET.get().is_some() {
+            command.env_remove("TMUX");
+        }
+        print_timings();
+        let error = command
+            .args(["attach-session", "-t", &session_target(name)])
+            .exec();
+        Err(messages::ATTACH_FAILED.format(&[&name, &error]).into())
+    }
+
+    fn attach_command(name: &str) -> String {
+        let mut command = vec!["tmux".to_string()];
+        if let Some(socket) = TMUX_SOCKET.get() {
+            let [flag, value] = socket.args();
+            command.push(flag);
+            command.push(shell_quote(&value));
+        }
+        command.push("attach-session".to_string());
+        command.push("-t".to_string());
+        command.push(shell_quote(&session_target(name)));
+        command.join(" ")
+    }
+
+    fn kill_session(name: &str) -> Result<()> {
+        let output = Self::command()
+            .args(["kill-session", "-t", &session_target(name)])
+            .output_retrying()?;
+        if !output.status.success() {
+            return Err(String::from_utf8_lossy(&output.stderr).trim().into());
+        }
+        Ok(())
+    }
+}
+
+/// What ws needs from the multiplexer its sessions live in. tmux is the
+/// default; with `backend = "zellij"` or `"wezterm"` only these operations
+/// are available, so commands built on other tmux features refuse to run.
+trait Multiplexer: Sync {
+    /// Whether ws runs inside one of the multiplexer's sessions.
+    fn is_inside(&self) -> bool;
+    fn current_session(&self) -> Result<String>;
+    fn list_sessions(&self) -> Result<Vec<SessionInfo>>;
+    fn has_session(&self, name: &str) -> Result<bool>;
+    fn create_session(
+        &self,
+        name: &str,
+        path: &str,
+        layout: &Layout,
+        env: &[(String, String)],
+    ) -> Result<()>;
+    fn kill_session(&self, name: &str) -> Result<()>;
+    /// Moves the client ws runs in to `name`.
+    fn switch_client(&self, name: &str) -> Result<()>;
+    /// Replaces the ws process with a client attached to `name`.
+    fn exec_attach(&self, name: &str) -> Result<()>;
+    /// Shell command attaching to `name`, for the print and terminal modes.
+    fn attach_command(&self, name: &str) -> String;
+    /// Shell command printing what `name` currently shows, for the picker's
+    /// preview.
+    fn preview_command(&self, _name: &str) -> Option<String> {
+        None
+    }
+
+    /// Switches to `name` when inside the multiplexer, otherwise attaches
+    /// to it as `attach.mode` says.
+    fn switch_or_attach(&self, name: &str, attach: &AttachConfig) -> Result<()> {
+        if self.is_inside() {
+            return timings::time("switch", || self.switch_client(name));
+        }
+
+        match attach.mode {
+            AttachMode::Exec => self.exec_attach(name),
+            AttachMode::Print => {
+                println!("{}", self.attach_command(name));
+                Ok(())
+            }
+            AttachMode::Terminal => {
+                let terminal = attach
+                    .terminal
+                    .as_deref()
+                    .ok_or("attach.mode = \"terminal\" needs attach.terminal in the config")?;
+                let command = terminal.replace("{command}", &self.attach_command(name));
+                Command::new("sh").args(["-c", &command]).spawn()?;
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Multiplexer for TmuxClient {
+    fn is_inside(&self) -> bool {
+        Self::is_in_tmux()
+    }
+
+    fn current_session(&self) -> Result<String> {
+        timings::time("tmux queries", Self::current_session)
+    }
+
+    fn list_sessions(&self) -> Result<Vec<SessionInfo>> {
+        timings::time("tmux queries", Self::list_sessions)
+    }
+
+    fn has_session(&self, name: &str) -> Result<bool> {
+        timings::time("tmux queries", || Self::has_session(name))
+    }
+
+    fn create_session(
+        &self,
+        name: &str,
+        path: &str,
+        layout: &Layout,
+        env: &[(String, String)],
+    ) -> Result<()> {
+        Self::create_session(name, path, layout, env)
+    }
+
+    fn kill_session(&self, name: &str) -> Result<()> {
+        Self::kill_session(name)
+    }
+
+    fn switch_client(&self, name: &str) -> Result<()> {
+        Self::switch_client(name)
+    }
+
+    fn exec_attach(&self, name: &str) -> Result<()> {
+        Self::exec_attach(name)
+    }
+
+    fn attach_command(&self, name: &str) -> String {
+        Self::attach_command(name)
+    }
+
+    fn preview_command(&self, name: &str) -> Option<String> {
+        let mut args: Vec<String> = TMUX_SOCKET
+            .get()
+            .map(|socket| socket.args().to_vec())
+            .unwrap_or_default();
+        args.extend([
+            "capture-pane".to_string(),
+            "-ep".to_string(),
+            "-t".to_string(),
+            format!("{}:", session_target(name)),
+        ]);
+        let args: Vec<String> = args.iter().map(|arg| shell_quote(arg)).collect();
+        Some(format!("tmux {}", args.join(" ")))
+    }
+}
+
+/// The configured multiplexer, tmux unless `backend` says otherwise.
+fn mux() -> &'static dyn Multiplexer {
+    match BACKEND.get() {
+        Some(Backend::Zellij) => &ZellijClient,
+        Some(Backend::Wezterm) => &WeztermClient,
+        _ => &TmuxClient,
+    }
+}
+
+/// Exact-match target for a session. Without the `=` prefix tmux falls back
+/// to prefix and pattern matching, so `api` could resolve to `apiserver`.
+fn session_target(name: &str) -> String {
+    format!("={}", name)
+}
+
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+fn window_target(session: &str, window: &str) -> String {
+    format!("={}:{}", session, window)
+}
+
+fn expand_project_template(template: &str, project: &ProjectInfo) -> String {
+    template
+        .replace("{alias}", &project.root)
+        .replace("{category}", &project.category)
+        .replace("{name}", &project.name)
+}
+
+/// Environment set on a project's session when it is created.
+fn session_env(project: &ProjectInfo, session: &SessionConfig) -> Result<Vec<(String, String)>> {
+    let mut env = Vec::new();
+
+    if session.history {
+        let history_file = match &session.history_file {
+            Some(template) => PathBuf::from(
+                shellexpand::tilde(&expand_project_template(template, project)).to_string(),
+            ),
+            None => State::state_path()
+                .with_file_name("history")
+                .join(expand_project_template(
+                    "{alias}-{category}-{name}",
+                    project,
+                )),
+        };
+        if let Some(parent) = history_file.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        // fish keeps its own history store and only accepts a name made of
+        // alphanumerics and underscores.
+        let fish_history: String = history_file
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default()
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect();
+
+        env.push((
+            "HISTFILE".to_string(),
+            history_file.to_string_lossy().to_string(),
+        ));
+        env.push(("fish_history".to_string(), fish_history));
+    }
+
+    // A broken .ws.toml shouldn't keep the project from opening.
+    match ProjectConfig::load(&project.path) {
+        Ok(project_config) => env.extend(project_config.env),
+        Err(e) => messages::warn(e),
+    }
+
+    Ok(env)
+}
+
+/// What direnv would set (`Some`) or unset (`None`) in the project, if it
+/// has an `.envrc`. direnv refuses `.envrc` files that weren't allowed.
+fn direnv_env(path: &str) -> Result<Vec<(String, Option<String>)>> {
+    if !Path::new(path).join(".envrc").is_file() {
+        return Ok(Vec::new());
+    }
+    let output = Command::new("direnv")
+        .args(["export", "json"])
+        .current_dir(path)
+        .output()
+        .map_err(|e| messages::RUN_FAILED.format(&[&"direnv", &e]))?;
+    if !output.status.success() {
+        return Err(format!(
+            "direnv failed in {}: {}",
+            tilde_path(path),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )
+        .into());
+    }
+    if output.stdout.iter().all(u8::is_ascii_whitespace) {
+        return Ok(Vec::new());
+    }
+
+    let vars: BTreeMap<String, Option<String>> = serde_json::from_slice(&output.stdout)
+        .map_err(|e| format!("Unexpected direnv output: {}", e))?;
+    // direnv's own bookkeeping only makes sense to its shell hook.
+    Ok(vars
+        .into_iter()
+        .filter(|(key, _)| !key.starts_with("DIRENV_"))
+        .collect())
+}
+
+fn notifications_enabled() -> bool {
+    !matches!(
+        std::env::var(NOTIFY_ENV).as_deref(),
+        Ok("0") | Ok("off") | Ok("false")
+    )
+}
+
+/// Whether the program `command` starts with can be found. When it can't,
+/// a warning says what is left out, once until it shows up again.
+fn tool_available(command: &str, degraded: &str) -> bool {
+    let Some(program) = command.split_whitespace().next() else {
+        return true;
+    };
+    let warned_file = State::state_path().with_file_name(MISSING_TOOLS_FILE);
+    let mut warned: BTreeSet<String> = fs::read_to_string(&warned_file)
+        .ok()
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default();
+
+    let found = find_program(program).is_some();
+    let changed = if found {
+        warned.remove(program)
+    } else if warned.insert(program.to_string()) {
+        messages::warn(messages::PROGRAM_MISSING.format(&[&program, &degraded]));
+        true
+    } else {
+        false
+    };
+    if changed && StateMode::current().writes() {
+        if let Some(parent) = warned_file.parent() {
+            fs::create_dir_all(parent).ok();
+        }
+        if let Ok(json) = serde_json::to_string(&warned) {
+            fs::write(&warned_file, json).ok();
+        }
+    }
+    found
+}
+
+/// Where `program` is: itself if it contains a `/`, else the first
+/// executable of that name on `PATH`.
+fn find_program(program: &str) -> Option<PathBuf> {
+    let executable = |path: &Path| {
+        use std::os::unix::fs::PermissionsExt;
+        path.metadata()
+            .is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+    };
+    if program.contains('/') {
+        let path = PathBuf::from(shellexpand::tilde(program).as_ref());
+        return executable(&path).then_some(path);
+    }
+    std::env::split_paths(&std::env::var_os("PATH")?)
+        .map(|dir| dir.join(program))
+        .find(|path| executable(path))
+}
+
+/// Surfaces a transient message in the tmux status line. Notifications are
+/// best effort: outside tmux, or with `WS_NOTIFY=off`, they are dropped.
+fn notify(message: &str) {
+    if notifications_enabled() && TmuxClient::is_in_tmux() {
+        TmuxClient::display_message(message).ok();
+    }
+}
+
+/// Prints the one-line result of a command that changed something, per
+/// `output.summary`. With `--attach print` stdout carries the attach command,
+/// so the summary goes to stderr instead.
+fn report(message: &str, config: &Config) {
+    let to_tmux = match config.output.summary {
+        SummaryMode::Off => return,
+        SummaryMode::Auto => TmuxClient::is_in_tmux(),
+        SummaryMode::Tmux => true,
+        SummaryMode::Stdout => false,
+    };
+    if to_tmux && TmuxClient::display_message(message).is_ok() {
+        return;
+    }
+
+    if config.attach.mode == AttachMode::Print {
+        eprintln!("{}", message);
+    } else {
+        println!("{}", message);
+    }
+}
+
+/// What a command did, printed on one line by `--json` in place of the
+/// human-readable report.
+#[derive(Debug, Serialize)]
+struct CommandResult {
+    action: &'static str,
+    /// Sessions or paths the command acted on.
+    targets: Vec<String>,
+    /// One "target: message" entry per failure.
+    errors: Vec<String>,
+}
+
+impl CommandResult {
+    fn new(action: &'static str) -> Self {
+        Self {
+            action,
+            targets: Vec::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    fn print(&self) -> Result<()> {
+        println!("{}", serde_json::to_string(self)?);
+        Ok(())
+    }
+}
+
+/// Runs the `on_switch` integrations for a session ws is about to switch to.
+/// Sessions ws doesn't know a directory for are skipped.
+fn announce_switch(session: &str, state: &State, config: &Config) {
+    run_hook(Hook::Attach, session, state, config);
+    let Some(path) = state.sessions.get(session) else {
+        return;
+    };
+
+    if config.on_switch.osc7 {
+        if let Err(e) = emit_osc7(path, &config.attach) {
+            messages::warn(messages::TERMINAL_DIRECTORY_FAILED.format(&[&e]));
+        }
+    }
+
+    if let Some(template) = &config.on_switch.title {
+        let project = state.project_for_path(path);
+        let title = match &project {
+            Some(project) => expand_project_template(template, project),
+            None => template.clone(),
+        }
+        .replace("{session}", session);
+        if let Err(e) = emit_title(&title, &config.attach) {
+            messages::warn(messages::TERMINAL_TITLE_FAILED.format(&[&e]));
+        }
+    }
+
+    if let Some(hook) = &config.on_switch.command {
+        let status = Command::new("sh")
+            .args(["-c", hook])
+            .env("WS_SESSION", session)
+            .env("WS_PROJECT_PATH", path)
+            .status();
+        match status {
+            Ok(status) if status.success() => {}
+            Ok(status) => messages::warn(messages::ON_SWITCH_EXITED.format(&[&status])),
+            Err(e) => messages::warn(messages::ON_SWITCH_FAILED.format(&[&e])),
+        }
+    }
+}
+
+/// A session lifecycle event with a command in `[hooks]`.
+#[derive(Clone, Copy)]
+enum Hook {
+    Create,
+    Attach,
+    Kill,
+}
+
+impl Hook {
+    /// The hook's key in `[hooks]` and the command configured there.
+    fn setting(self, hooks: &HooksConfig) -> (&'static str, Option<&str>) {
+        let (key, command) = match self {
+            Self::Create => ("on_session_create", &hooks.on_session_create),
+            Self::Attach => ("on_session_attach", &hooks.on_session_attach),
+            Self::Kill => ("on_session_kill", &hooks.on_session_kill),
+        };
+        (key, command.as_deref())
+    }
+}
+
+/// Runs the `[hooks]` command for `hook`, if there is one, in the session's
+/// project directory. Failures are warned about but don't stop ws.
+fn run_hook(hook: Hook, session: &str, state: &State, config: &Config) {
+    let (key, command) = hook.setting(&config.hooks);
+    let Some(command) = command else {
+        return;
+    };
+    let mut process = Command::new("sh");
+    process.args(["-c", command]).env("WS_SESSION", session);
+    if let Some(path) = state.sessions.get(session) {
+        process.env("WS_PROJECT_PATH", path);
+        if Path::new(path).is_dir() {
+            process.current_dir(path);
+        }
+    }
+    match process.status() {
+        Ok(status) if status.success() => {}
+        Ok(status) => messages::warn(messages::HOOK_EXITED.format(&[&key, &status])),
+        Err(e) => messages::warn(messages::HOOK_FAILED.format(&[&key, &e])),
+    }
+}
+
+/// Reports the project directory to the outer terminal with OSC 7.
+fn emit_osc7(path: &str, attach: &AttachConfig) -> Result<()> {
+    let encoded: String = path
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'/' | b'-' | b'_' | b'.' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect();
+    let sequence = format!("\x1b]7;file://{}{}\x1b\\", inventory::hostname(), encoded);
+    write_to_terminal(&sequence, attach)
+}
+
+/// Sets the terminal's window and tab title with OSC 0.
+fn emit_title(title: &str, attach: &AttachConfig) -> Result<()> {
+    let title: String = title.chars().filter(|c| !c.is_control()).collect();
+    write_to_terminal(&format!("\x1b]0;{}\x1b\\", title), attach)
+}
+
+/// Writes an escape sequence to the outer terminal. Inside tmux the pane is
+/// not the terminal, so it goes to the invoking client's tty; outside tmux
+/// only when ws is about to exec into tmux on this terminal.
+fn write_to_terminal(sequence: &str, attach: &AttachConfig) -> Result<()> {
+    if TmuxClient::is_in_tmux() {
+        let client = TmuxClient::invoking_client().ok_or("no tmux client")?;
+        if !client.starts_with("/dev/") {
+            return Err(format!("client {} has no tty", client).into());
+        }
+        fs::OpenOptions::new()
+            .write(true)
+            .open(&client)?
+            .write_all(sequence.as_bytes())?;
+    } else if attach.mode == AttachMode::Exec && std::io::stdout().is_terminal() {
+        let mut stdout = std::io::stdout();
+        stdout.write_all(sequence.as_bytes())?;
+        stdout.flush()?;
+    }
+    Ok(())
+}
+
+/// Switches or attaches to `session`, then reports it. A failed switch
+/// reports nothing, and neither does attaching by exec, which replaces ws.
+fn switch_to(session: &str, created: bool, state: &State, config: &Config) -> Result<()> {
+    switch_reporting(session, &switched_message(session, created), state, config)
+}
+
+/// [`switch_to`] for commands that report more than the switch.
+fn switch_reporting(session: &str, message: &str, state: &State, config: &Config) -> Result<()> {
+    announce_switch(session, state, config);
+    mux().switch_or_attach(session, &config.attach)?;
+    report(message, config);
+    Ok(())
+}
+
+fn switched_message(session: &str, created: bool) -> String {
+    if created {
+        messages::SWITCHED_CREATED.format(&[&session])
+    } else {
+        messages::SWITCHED.format(&[&session])
+    }
+}
+
+fn new_projects_message(projects: &[ProjectInfo], template: &str) -> String {
+    let names: Vec<String> = projects
+        .iter()
+        .take(NOTIFY_MAX_NAMES)
+        .map(|p| p.label(template))
+        .collect();
+    let more = projects.len().saturating_sub(NOTIFY_MAX_NAMES);
+
+    if more > 0 {
+        format!(
+            "ws: {} new projects: {} (+{} more)",
+            projects.len(),
+            names.join(", "),
+            more
+        )
+    } else if projects.len() == 1 {
+        format!("ws: new project: {}", names[0])
+    } else {
+        format!("ws: {} new projects: {}", projects.len(), names.join(", "))
+    }
+}
+
+/// A project's stable ID: a hash of its canonical path, so it survives
+/// changes to the configured roots, aliases or symlinks. Paths that don't
+/// exist (yet) are hashed as given. FNV-1a keeps IDs the same across builds,
+/// which `DefaultHasher` does not promise.
+fn project_id(path: &Path) -> String {
+    let canonical = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    let hash = canonical
+        .as_os_str()
+        .as_encoded_bytes()
+        .iter()
+        .fold(0xcbf29ce484222325u64, |hash, &byte| {
+            (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+        });
+    format!("{:016x}", hash)
+}
+
+/// The kind of project at `path`, going by the first of `PROJECT_MARKERS`
+/// it contains.
+fn project_type(path: &Path) -> Option<&'static str> {
+    PROJECT_MARKERS
+        .iter()
+        .find(|(_, files)| files.iter().any(|file| path.join(file).exists()))
+        .map(|(kind, _)| *kind)
+}
+
+/// tmux shows a start command that was passed as one argument in double
+/// quotes, with `"`, `\\` and `$` escaped.
+fn unquote_start_command(command: &str) -> String {
+    let Some(inner) = command
+        .strip_prefix('"')
+        .and_then(|rest| rest.strip_suffix('"'))
+    else {
+        return command.to_string();
+    };
+
+    let mut unquoted = String::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => unquoted.extend(chars.next()),
+            c => unquoted.push(c),
+        }
+    }
+    unquoted
+}
+
+fn root_paths(roots: &[WorkspaceRoot]) -> Vec<String> {
+    roots.iter().map(WorkspaceRoot::cache_key).collect()
+}
+
+/// A path that could not be read during a scan. `whole_root` is set when
+/// nothing of the root could be listed.
+struct ScanError {
+    path: String,
+    root: String,
+    whole_root: bool,
+    message: String,
+}
+
+impl ScanError {
+    /// Whether a previously cached project may be hidden behind this error.
+    fn covers(&self, project: &ProjectInfo) -> bool {
+        if self.whole_root {
+            project.root == self.root
+        } else {
+            Path::new(&project.path).starts_with(&self.path)
+        }
+    }
+}
+
+/// Outcome of a rescan: the projects that were not cached before, and the
+/// paths that could not be read.
+struct Refresh {
+    new_projects: Vec<ProjectInfo>,
+    errors: Vec<ScanError>,
+}
+
+/// Scans one root. Unreadable directories are collected instead of aborting
+/// the scan, so one bad category doesn't hide the rest of the workspace.
+fn scan_projects(root: &WorkspaceRoot, scan: &ScanConfig) -> (Vec<ProjectInfo>, Vec<ScanError>) {
+    if root.is_list() {
+        return match list_projects(root) {
+            Ok(projects) => (projects, Vec::new()),
+            Err(e) => (
+                Vec::new(),
+                vec![ScanError {
+                    path: root.path.clone(),
+                    root: root.alias.clone(),
+                    whole_root: true,
+                    message: e.to_string(),
+                }],
+            ),
+        };
+    }
+
+    let mut projects = Vec::new();
+    let mut worktrees = Vec::new();
+    let mut errors = Vec::new();
+    let scan = scan.for_root(Path::new(&root.path));
+    let excluded = |path: &Path| {
+        let relative = path.strip_prefix(&root.path).unwrap_or(path);
+        scan.is_excluded(&relative.to_string_lossy())
+    };
+
+    let (min_depth, max_depth) = scan_depths(root, &scan);
+
+    // min_depth is applied by hand: walkdir does not pass entries above
+    // min_depth through filter_entry, so excluded categories would survive.
+    let mut walk = WalkDir::new(&root.path)
+        .max_depth(max_depth)
+        .into_iter()
+        .filter_entry(|e| e.depth() == 0 || !excluded(e.path()));
+    while let Some(entry) = walk.next() {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                let path = e
+                    .path()
+                    .map_or_else(|| root.path.clone(), |p| p.display().to_string());
+                errors.push(ScanError {
+                    whole_root: e.depth() == 0,
+                    path,
+                    root: root.alias.clone(),
+                    message: e
+                        .io_error()
+                        .map_or_else(|| e.to_string(), |io| io.to_string()),
+                });
+                continue;
+            }
+        };
+        if !entry.file_type().is_dir() {
+            continue;
+        }
+        if entry.depth() < min_depth {
+            continue;
+        }
+        let project = match scan.mode {
+            ScanMode::Depth => scanned_project(entry.path(), root),
+            ScanMode::Markers if has_marker(entry.path(), &scan.markers) => {
+                walk.skip_current_dir();
+                scanned_project(entry.path(), root)
+            }
+            ScanMode::Markers => None,
+        };
+        let Some(mut project) = project else {
+            continue;
+        };
+        project.root = root.alias.clone();
+        // Each linked worktree is a project of its own, named like the
+        // sessions `ws branch` opens; a bare repository only has those.
+        for (branch, path) in worktree::linked_worktrees(entry.path()) {
+            worktrees.push(ProjectInfo {
+                id: project_id(&path),
+                path: path.to_string_lossy().to_string(),
+                category: project.category.clone(),
+                name: format!("{}@{}", project.name, branch),
+                root: project.root.clone(),
+                git: None,
+            });
+        }
+        if !worktree::is_bare(entry.path()) {
+            projects.push(project);
+        }
+    }
+    // Worktrees that are in the workspace themselves are already listed.
+    for worktree in worktrees {
+        if !projects.iter().any(|p| p.path == worktree.path) {
+            projects.push(worktree);
+        }
+    }
+
+    (projects, errors)
+}
+
+/// The levels below `root` projects are found at.
+fn scan_depths(root: &WorkspaceRoot, scan: &ScanConfig) -> (usize, usize) {
+    let (min_depth, max_depth) = match scan.mode {
+        ScanMode::Depth => (PROJECT_SCAN_MIN_DEPTH, PROJECT_SCAN_MAX_DEPTH),
+        ScanMode::Markers => (1, scan.max_depth),
+    };
+    let min_depth = root.min_depth.unwrap_or(min_depth).max(1);
+    let max_depth = root.max_depth.unwrap_or(max_depth).max(min_depth);
+    (min_depth, max_depth)
+}
+
+/// What `ws daemon` watches so that every change a scan would notice
+/// touches one of them: list-file roots themselves, and the directories
+/// above the deepest project level, where projects are added or removed.
+/// In marker mode, projects' own directories are left out.
+fn watched_paths(config: &Config) -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+    for root in &config.workspaces {
+        if root.is_list() {
+            paths.push(PathBuf::from(&root.path));
+            continue;
+        }
+        let scan = config.scan.for_root(Path::new(&root.path));
+        let (_, max_depth) = scan_depths(root, &scan);
+        let mut walk = WalkDir::new(&root.path)
+            .max_depth(max_depth - 1)
+            .into_iter()
+            .filter_entry(|e| {
+                let relative = e.path().strip_prefix(&root.path).unwrap_or(e.path());
+                e.depth() == 0 || !scan.is_excluded(&relative.to_string_lossy())
+            });
+        while let Some(entry) = walk.next() {
+            let Ok(entry) = entry else {
+                continue;
+            };
+            if !entry.file_type().is_dir() {
+                continue;
+            }
+            if entry.depth() > 0
+                && scan.mode == ScanMode::Markers
+                && has_marker(entry.path(), &scan.markers)
+            {
+                walk.skip_current_dir();
+                continue;
+            }
+            paths.push(entry.into_path());
+        }
+    }
+    paths
+}
+
+fn has_marker(path: &Path, markers: &[String]) -> bool {
+    markers.iter().any(|marker| path.join(marker).exists()) || worktree::is_bare(path)
+}
+
+/// A project found by the scan: the path from the root to its parent is its
+/// category, or the root's alias for projects at the top of the root.
+fn scanned_project(path: &Path, root: &WorkspaceRoot) -> Option<ProjectInfo> {
+    let mut project = ProjectInfo::from_path(path)?;
+    let parent = path.parent()?.strip_prefix(&root.path).ok()?;
+    project.category = if parent.as_os_str().is_empty() {
+        root.alias.clone()
+    } else {
+        parent.to_string_lossy().to_string()
+    };
+    Some(project)
+}
+
+/// Projects from a root that is a file of paths, e.g. the output of
+/// `ghq list -p` or a repo manifest. Paths that aren't directories are
+/// skipped.
+fn list_projects(root: &WorkspaceRoot) -> Result<Vec<ProjectInfo>> {
+    let contents = fs::read_to_string(&root.path)
+        .map_err(|e| messages::READ_FAILED.format(&[&root.path, &e]))?;
+    let lines = if contents.trim_start().starts_with('[') {
+        let paths: Vec<String> = serde_json::from_str(&contents)
+            .map_err(|e| format!("Invalid path list {}: {}", root.path, e))?;
+        paths.join("\n")
+    } else {
+        contents
+    };
+
+    let mut projects = read_projects(lines.as_bytes())?;
+    projects.retain(|p| Path::new(&p.path).is_dir());
+    for project in &mut projects {
+        project.root = root.alias.clone();
+    }
+    Ok(projects)
+}
+
+/// The project `query` names: category/name or root/category/name, or just
+/// the name if that is unique.
+fn find_project_in<'a>(projects: &'a [ProjectInfo], query: &str) -> Result<&'a ProjectInfo> {
+    let exact: Vec<&ProjectInfo> = projects
+        .iter()
+        .filter(|p| p.display_name() == query || p.qualified_name() == query)
+        .collect();
+    let matches: Vec<&ProjectInfo> = if exact.is_empty() {
+        projects.iter().filter(|p| p.name == query).collect()
+    } else {
+        exact
+    };
+
+    match matches.as_slice() {
+        [] => Err(messages::NO_PROJECT_NAMED.format(&[&query]).into()),
+        [project] => Ok(project),
+        _ => {
+            let names: Vec<String> = matches.iter().map(|p| p.qualified_name()).collect();
+            Err(messages::AMBIGUOUS_PROJECT
+                .format(&[&query, &names.join(", ")])
+                .into())
+        }
+    }
+}
+
+/// Projects in each root's `.archive`, laid out like the root itself (so
+/// archived categories show up too).
+fn archived_projects(config: &Config) -> Vec<ProjectInfo> {
+    let mut projects = Vec::new();
+    for root in config.workspaces.iter().filter(|root| !root.is_list()) {
+        let archive = Path::new(&root.path).join(ARCHIVE_DIR);
+        let entries = WalkDir::new(&archive)
+            .min_depth(2)
+            .max_depth(2)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_entry(|e| !e.file_name().to_string_lossy().starts_with('.'))
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_type().is_dir());
+        for entry in entries {
+            if let Some(mut project) = ProjectInfo::from_path(entry.path()) {
+                project.root = root.alias.clone();
+                projects.push(project);
+            }
+        }
+    }
+    projects
+}
+
+/// Reads candidate project paths, one per line, for pickers fed by other
+/// tools (`fd`, `ghq list -p`, ...). Blank lines are skipped.
+fn read_projects(reader: impl BufRead) -> Result<Vec<ProjectInfo>> {
+    let mut projects = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let path = PathBuf::from(shellexpand::tilde(line).to_string());
+        let path = path.canonicalize().unwrap_or(path);
+        match ProjectInfo::from_path(&path) {
+            Some(project) => projects.push(project),
+            None => eprintln!("{}", messages::INVALID_PATH_SKIPPED.format(&[&line])),
+        }
+    }
+    Ok(projects)
+}
+
+/// Recent shell directories that are not already reachable as projects.
+fn recent_directories(
+    roots: &[WorkspaceRoot],
+    projects: &[ProjectInfo],
+    limit: usize,
+) -> Vec<ProjectInfo> {
+    let home = dirs::home_dir();
+
+    recent_dirs::load()
+        .into_iter()
+        .filter(|dir| !roots.iter().any(|root| dir.starts_with(&root.path)))
+        .filter(|dir| Some(dir) != home.as_ref())
+        .filter(|dir| !projects.iter().any(|p| Path::new(&p.path) == dir))
+        .filter_map(|dir| ProjectInfo::from_path(&dir))
+        .take(limit)
+        .collect()
+}
+
+fn tilde_path(path: &str) -> String {
+    match dirs::home_dir().and_then(|home| {
+        Path::new(path)
+            .strip_prefix(&home)
+            .ok()
+            .map(|rest| rest.to_path_buf())
+    }) {
+        Some(rest) => format!("~/{}", rest.display()),
+        None => path.to_string(),
+    }
+}
+
+struct Picker;
+
+/// Which rows a picker returns.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Selection {
+    /// The highlighted one.
+    One,
+    /// Those marked with tab, else the highlighted one.
+    Many,
+    /// Those still marked: every row starts out marked, and unmarking all of
+    /// them is a valid answer.
+    AllMarked,
+}
+
+/// What a picker lists: a label per row, with optional hints (shown dimmed
+/// after the label) and preview commands. The rows in `headers` title the
+/// section below them; they are never numbered or returned.
+#[derive(Default)]
+struct PickerList<'a> {
+    items: &'a [String],
+    hints: &'a [String],
+    previews: &'a [String],
+    headers: &'a [usize],
+}
+
+/// A picker row. skim hands back the row itself, so the selection is
+/// resolved by index and never depends on labels being unique.
+struct PickerRow {
+    index: usize,
+    label: String,
+    /// Character offset where the dimmed hint starts, if there is one.
+    hint_start: Option<usize>,
+    /// Shell command whose output the preview pane shows.
+    preview: Option<String>,
+    /// Section headers are dimmed and match no query, so they disappear
+    /// as soon as something is typed.
+    header: bool,
+}
+
+impl SkimItem for PickerRow {
+    fn text(&self) -> Cow<'_, str> {
+        if self.header {
+            return Cow::Borrowed("");
+        }
+        Cow::Borrowed(&self.label)
+    }
+
+    fn display<'a>(&'a self, context: DisplayContext<'a>) -> AnsiString<'a> {
+        let dim = Attr {
+            effect: Effect::DIM,
+            ..Attr::default()
+        };
+        if self.header {
+            let width = self.label.chars().count() as u32;
+            return AnsiString::new_str(&self.label, vec![(dim, (0, width))]);
+        }
+        let mut line = AnsiString::from(context);
+        if let Some(start) = self.hint_start {
+            line.override_attrs(vec![(
+                dim,
+                (start as u32, self.label.chars().count() as u32),
+            )]);
+        }
+        line
+    }
+
+    fn output(&self) -> Cow<'_, str> {
+        Cow::Owned(self.index.to_string())
+    }
+
+    fn preview(&self, _context: PreviewContext) -> ItemPreview {
+        match &self.preview {
+            Some(command) => ItemPreview::Command(command.clone()),
+            None => ItemPreview::Text(String::new()),
+        }
+    }
+}
+
+impl Picker {
+    /// Shows `items` and returns the index of the chosen one, along with the
+    /// action it was accepted with: `actions` maps keys to action names.
+    /// Non-empty `hints` are shown dimmed after their item. With
+    /// `quick_select`, the first nine rows (headers excluded) are numbered
+    /// and the digit keys accept them directly while the query is empty.
+    fn pick(
+        items: &[String],
+        hints: &[String],
+        prompt: &str,
+        quick_select: bool,
+        actions: &[(&str, &str)],
+    ) -> Option<(usize, Option<String>)> {
+        let list = PickerList {
+            items,
+            hints,
+            ..PickerList::default()
+        };
+        Self::pick_one(&list, prompt, quick_select, actions)
+    }
+
+    /// `pick` over a full `PickerList`, e.g. with previews.
+    fn pick_one(
+        list: &PickerList,
+        prompt: &str,
+        quick_select: bool,
+        actions: &[(&str, &str)],
+    ) -> Option<(usize, Option<String>)> {
+        let (indices, action) = timings::time("picker", || {
+            Self::run(list, prompt, quick_select, actions, Selection::One)
+        })?;
+        Some((*indices.first()?, action))
+    }
+
+    /// Like `pick`, but tab marks several rows; their indices are returned in
+    /// list order. Without marks the highlighted row is returned. Non-empty
+    /// previews are shell commands whose output is shown next to the list
+    /// for the highlighted row.
+    fn pick_multi(
+        list: &PickerList,
+        prompt: &str,
+        quick_select: bool,
+        actions: &[(&str, &str)],
+    ) -> Option<(Vec<usize>, Option<String>)> {
+        timings::time("picker", || {
+            Self::run(list, prompt, quick_select, actions, Selection::Many)
+        })
+    }
+
+    /// Shows `items` with all of them marked and returns the indices still
+    /// marked when the list is accepted. Tab toggles a row.
+    fn pick_many(items: &[String], hints: &[String], prompt: &str) -> Option<Vec<usize>> {
+        let list = PickerList {
+            items,
+            hints,
+            ..PickerList::default()
+        };
+        let (indices, _) = timings::time("picker", || {
+            Self::run(&list, prompt, false, &[], Selection::AllMarked)
+        })?;
+        Some(indices)
+    }
+
+    fn run(
+        list: &PickerList,
+        prompt: &str,
+        quick_select: bool,
+        actions: &[(&str, &str)],
+        selection: Selection,
+    ) -> Option<(Vec<usize>, Option<String>)> {
+        let (indices, action) = if Self::is_plain() {
+            Self::run_plain(list, prompt, actions, selection)?
+        } else {
+            Self::run_full_screen(list, prompt, quick_select, actions, selection)?
+        };
+        // A header can still be highlighted and accepted.
+        let indices: Vec<usize> = indices
+            .into_iter()
+            .filter(|index| !list.headers.contains(index))
+            .collect();
+        (!indices.is_empty() || selection == Selection::AllMarked).then_some((indices, action))
+    }
+
+    fn run_full_screen(
+        list: &PickerList,
+        prompt: &str,
+        quick_select: bool,
+        actions: &[(&str, &str)],
+        selection: Selection,
+    ) -> Option<(Vec<usize>, Option<String>)> {
+        let PickerList {
+            items,
+            hints,
+            previews,
+            headers,
+        } = *list;
+        let multi = selection != Selection::One;
+        let all_marked = selection == Selection::AllMarked;
+        let numbered: Vec<usize> = if !quick_select {
+            Vec::new()
+        } else {
+            Self::numbered_rows(items, headers)
+                .into_iter()
+                .take(QUICK_SELECT_ROWS)
+                .collect()
+        };
+
+        let labels: Vec<String> = if numbered.is_empty() {
+            items.to_vec()
+        } else {
+            items
+                .iter()
+                .enumerate()
+                .map(
+                    |(index, item)| match numbered.iter().position(|&n| n == index) {
+                        Some(position) => format!("{} {}", position + 1, item),
+                        None => format!("  {}", item),
+                    },
+                )
+                .collect()
+        };
+
+        // skim's bind parser can't nest parentheses, hence the brackets.
+        let binds: Vec<String> = (1..=numbered.len())
+            .map(|n| format!("{}:if-query-empty[accept({})]", n, n))
+            .chain(
+                actions
+                    .iter()
+                    .map(|(key, action)| format!("{}:accept({})", key, action)),
+            )
+            .collect();
+
+        // Keys beyond enter and esc are listed in a header line, since the
+        // picker gives no other hint that they exist.
+        let mut keys: Vec<String> = Vec::new();
+        if all_marked {
+            keys.push("tab: keep/kill".to_string());
+        } else if multi {
+            keys.push("tab: mark".to_string());
+        }
+        keys.extend(
+            actions
+                .iter()
+                .map(|(key, action)| format!("{}: {}", key, action)),
+        );
+        if !numbered.is_empty() {
+            keys.push(format!("1-{}: pick row", numbered.len()));
+        }
+        let header =
+            (!keys.is_empty()).then(|| format!("enter: select  {}  esc: cancel", keys.join("  ")));
+
+        if Self::finder() == Finder::Fzf {
+            let mut options = Vec::new();
+            if multi {
+                options.push("--multi".to_string());
+            }
+            if all_marked {
+                options.push("--bind=load:select-all".to_string());
+            }
+            if let Some(header) = &header {
+                options.push(format!("--header={}", header));
+            }
+            options.push("--tiebreak=index".to_string());
+            let list = PickerList {
+                items: &labels,
+                ..*list
+            };
+            return Self::run_fzf(&list, prompt, &numbered, actions, options, all_marked);
+        }
+
+        let previewing = previews.iter().any(|preview| !preview.is_empty());
         let options = SkimOptionsBuilder::default()
             .height(Some("100%"))
-            .multi(false)
+            .multi(multi)
             .color(Some("bw"))
             .layout("reverse")
             .prompt(Some(prompt))
+            // Equally good matches keep the list's order, so the most
+            // frecent projects stay on top while typing.
+            .tiebreak(Some("score".to_string()))
+            .header(header.as_deref())
+            .selector(all_marked.then(|| {
+                Rc::new(DefaultSkimSelector::default().first_n(items.len())) as Rc<dyn Selector>
+            }))
+            .preview(previewing.then_some(""))
+            .bind(binds.iter().map(|b| b.as_str()).collect())
             .build()
             .unwrap();
 
-        let item_reader = SkimItemReader::default();
-        let items_str = items.join("\n");
-        let skim_items = item_reader.of_bufread(Cursor::new(items_str));
+        let (sender, receiver): (SkimItemSender, SkimItemReceiver) = unbounded();
+        for (index, label) in labels.into_iter().enumerate() {
+            let preview = previews
+                .get(index)
+                .filter(|preview| !preview.is_empty())
+                .cloned();
+            let header = headers.contains(&index);
+            let row = match hints.get(index).filter(|hint| !hint.is_empty()) {
+                Some(hint) => PickerRow {
+                    index,
+                    hint_start: Some(label.chars().count() + 2),
+                    label: format!("{}  {}", label, hint),
+                    preview,
+                    header,
+                },
+                None => PickerRow {
+                    index,
+                    label,
+                    hint_start: None,
+                    preview,
+                    header,
+                },
+            };
+            sender.send(Arc::new(row)).ok()?;
+        }
+        drop(sender);
+
+        let output = Skim::run_with(&options, Some(receiver))?;
+
+        if output.is_abort {
+            return None;
+        }
+
+        let mut action = None;
+        if let Event::EvActAccept(Some(key)) = &output.final_event {
+            if let Ok(n) = key.parse::<usize>() {
+                return Some((vec![numbered.get(n.checked_sub(1)?).copied()?], None));
+            }
+            action = Some(key.clone());
+        }
+
+        let mut indices: Vec<usize> = output
+            .selected_items
+            .iter()
+            .filter_map(|item| item.output().parse().ok())
+            .collect();
+        indices.sort_unstable();
+        if indices.is_empty() && !all_marked {
+            return None;
+        }
+        Some((indices, action))
+    }
+
+    fn is_plain() -> bool {
+        PLAIN_PICKER.get().copied().unwrap_or(false)
+    }
+
+    fn finder() -> Finder {
+        FINDER.get().copied().unwrap_or_default()
+    }
+
+    /// `run` with fzf. Digits pick numbered rows through fzf's `transform`
+    /// while the query is empty, and are typed into it otherwise.
+    fn run_fzf(
+        list: &PickerList,
+        prompt: &str,
+        numbered: &[usize],
+        actions: &[(&str, &str)],
+        mut options: Vec<String>,
+        allow_empty: bool,
+    ) -> Option<(Vec<usize>, Option<String>)> {
+        if !actions.is_empty() {
+            let keys: Vec<&str> = actions.iter().map(|(key, _)| *key).collect();
+            options.push(format!("--expect={}", keys.join(",")));
+        }
+        for (n, index) in numbered.iter().enumerate() {
+            options.push(format!(
+                "--bind={}:transform:[ -z {{q}} ] && echo 'pos({})+accept' || echo 'put({})'",
+                n + 1,
+                index + 1,
+                n + 1
+            ));
+        }
+
+        let (key, indices) = Self::fzf(list, prompt, &options)?;
+        if indices.is_empty() && !allow_empty {
+            return None;
+        }
+        let action = actions
+            .iter()
+            .find(|(action_key, _)| Some(*action_key) == key.as_deref())
+            .map(|(_, action)| action.to_string());
+        Some((indices, action))
+    }
+
+    /// Runs fzf over `items`, each line carrying its index and preview
+    /// command in hidden fields, and returns the `--expect` key it was
+    /// accepted with (if any) and the chosen indices in list order. `None`
+    /// when it was cancelled or can't be run.
+    fn fzf(
+        list: &PickerList,
+        prompt: &str,
+        options: &[String],
+    ) -> Option<(Option<String>, Vec<usize>)> {
+        let PickerList {
+            items,
+            hints,
+            previews,
+            headers,
+        } = *list;
+        let mut command = Command::new("fzf");
+        command
+            .args(["--ansi", "--delimiter=\t", "--with-nth=2"])
+            .arg(format!("--prompt={}", prompt))
+            .args(options);
+        if previews.iter().any(|preview| !preview.is_empty()) {
+            command.arg("--preview=sh -c {3}");
+        }
+        let mut child = command
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .spawn()
+            .map_err(|e| eprintln!("{}", messages::FZF_FAILED.format(&[&e])))
+            .ok()?;
+
+        let mut input = String::new();
+        for (index, item) in items.iter().enumerate() {
+            if headers.contains(&index) {
+                input.push_str(&format!("{}\t\x1b[2m{}\x1b[0m", index, item));
+            } else {
+                input.push_str(&format!("{}\t{}", index, item));
+            }
+            if let Some(hint) = hints.get(index).filter(|hint| !hint.is_empty()) {
+                input.push_str(&format!("  \x1b[2m{}\x1b[0m", hint));
+            }
+            if let Some(preview) = previews.get(index) {
+                input.push_str(&format!("\t{}", preview));
+            }
+            input.push('\n');
+        }
+        // fzf may exit before reading everything, e.g. on esc.
+        child.stdin.take()?.write_all(input.as_bytes()).ok();
+
+        let output = child.wait_with_output().ok()?;
+        if !output.status.success() {
+            return None;
+        }
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let mut lines = stdout.lines();
+        let expects = options.iter().any(|o| o.starts_with("--expect="));
+        let key = if expects {
+            lines
+                .next()
+                .filter(|key| !key.is_empty())
+                .map(str::to_string)
+        } else {
+            None
+        };
+        let mut indices: Vec<usize> = lines
+            .filter_map(|line| line.split('\t').next()?.parse().ok())
+            .collect();
+        indices.sort_unstable();
+        Some((key, indices))
+    }
+
+    /// The line-based picker: rows are printed numbered (headers left
+    /// unnumbered) to stderr and the choice is read from the terminal, so it
+    /// works with screen readers and with `--stdin`. An action name may
+    /// follow the numbers; other text lists the rows containing it.
+    fn run_plain(
+        list: &PickerList,
+        prompt: &str,
+        actions: &[(&str, &str)],
+        selection: Selection,
+    ) -> Option<(Vec<usize>, Option<String>)> {
+        let PickerList {
+            items,
+            hints,
+            headers,
+            ..
+        } = *list;
+        Self::print_rows(items, hints, headers);
+        if selection == Selection::AllMarked {
+            eprintln!("{}", messages::ALL_SELECTED.text());
+            let excluded = Self::read_numbers(prompt, items, true, true)?;
+            let kept = (0..items.len())
+                .filter(|index| !excluded.contains(index))
+                .collect();
+            return Some((kept, None));
+        }
+        let multi = selection == Selection::Many;
+        let names: Vec<&str> = actions.iter().map(|(_, action)| *action).collect();
+        if multi {
+            eprintln!("{}", messages::ENTER_NUMBERS.text());
+        } else {
+            eprintln!("{}", messages::ENTER_NUMBER.text());
+        }
+        if !names.is_empty() {
+            eprintln!("{}", messages::ADD_ACTION.format(&[&names.join(", ")]));
+        }
+
+        loop {
+            let line = Self::read_line(prompt)?;
+            let mut words: Vec<&str> = line.split_whitespace().collect();
+            if words.is_empty() {
+                return None;
+            }
+            let action = match words.last() {
+                Some(word) if words.len() > 1 && names.contains(word) => words.pop(),
+                _ => None,
+            };
+            if words.iter().all(|w| w.parse::<usize>().is_ok()) {
+                match Self::parse_numbers(&words, items, headers, multi) {
+                    Ok(indices) => return Some((indices, action.map(str::to_string))),
+                    Err(message) => eprintln!("{}", message),
+                }
+                continue;
+            }
+
+            let query = line.trim().to_lowercase();
+            let mut found = false;
+            for (number, &index) in Self::numbered_rows(items, headers).iter().enumerate() {
+                if items[index].to_lowercase().contains(&query) {
+                    Self::print_row(number + 1, &items[index], hints.get(index));
+                    found = true;
+                }
+            }
+            if !found {
+                eprintln!("{}", messages::NOTHING_MATCHES.format(&[&line.trim()]));
+            }
+        }
+    }
+
+    /// Indices of the rows that get a number, i.e. all but headers.
+    fn numbered_rows(items: &[String], headers: &[usize]) -> Vec<usize> {
+        (0..items.len())
+            .filter(|index| !headers.contains(index))
+            .collect()
+    }
+
+    fn print_rows(items: &[String], hints: &[String], headers: &[usize]) {
+        let mut number = 0;
+        for (index, item) in items.iter().enumerate() {
+            if headers.contains(&index) {
+                eprintln!("{}:", item);
+            } else {
+                number += 1;
+                Self::print_row(number, item, hints.get(index));
+            }
+        }
+    }
+
+    fn print_row(number: usize, item: &str, hint: Option<&String>) {
+        match hint.filter(|hint| !hint.is_empty()) {
+            Some(hint) => eprintln!("{}. {} ({})", number, item, hint),
+            None => eprintln!("{}. {}", number, item),
+        }
+    }
+
+    /// Reads row numbers until they are valid; `None` on cancel. An empty
+    /// line is an empty selection if `allow_empty`, otherwise a cancel.
+    fn read_numbers(
+        prompt: &str,
+        items: &[String],
+        multi: bool,
+        allow_empty: bool,
+    ) -> Option<Vec<usize>> {
+        loop {
+            let line = Self::read_line(prompt)?;
+            let words: Vec<&str> = line.split_whitespace().collect();
+            if words.is_empty() {
+                return allow_empty.then(Vec::new);
+            }
+            match Self::parse_numbers(&words, items, &[], multi) {
+                Ok(indices) => return Some(indices),
+                Err(message) => eprintln!("{}", message),
+            }
+        }
+    }
+
+    /// Maps typed row numbers to item indices, in list order.
+    fn parse_numbers(
+        words: &[&str],
+        items: &[String],
+        headers: &[usize],
+        multi: bool,
+    ) -> std::result::Result<Vec<usize>, String> {
+        if words.len() > 1 && !multi {
+            return Err(messages::ENTER_SINGLE_NUMBER.text().to_string());
+        }
+        let rows = Self::numbered_rows(items, headers);
+        let mut indices = Vec::new();
+        for word in words {
+            let index = word
+                .parse::<usize>()
+                .ok()
+                .and_then(|n| rows.get(n.checked_sub(1)?))
+                .ok_or_else(|| messages::NO_ROW.format(&[word, &rows.len()]))?;
+            if !indices.contains(index) {
+                indices.push(*index);
+            }
+        }
+        indices.sort_unstable();
+        Ok(indices)
+    }
+
+    /// Prompts on stderr and reads a line from the terminal, falling back to
+    /// stdin; `None` at end of input.
+    fn read_line(prompt: &str) -> Option<String> {
+        eprint!("{}", prompt);
+        std::io::stderr().flush().ok();
+        let mut line = String::new();
+        let read = match fs::File::open("/dev/tty") {
+            Ok(tty) => std::io::BufReader::new(tty).read_line(&mut line),
+            Err(_) => std::io::stdin().read_line(&mut line),
+        };
+        match read {
+            Ok(0) | Err(_) => None,
+            Ok(_) => Some(line),
+        }
+    }
+}
+
+/// Where the picker's projects come from.
+enum ProjectSource {
+    /// The configured roots, through the cache.
+    Workspace,
+    /// Paths read from stdin.
+    Stdin,
+    /// The roots' `.archive` directories.
+    Archived,
+    /// A directory scanned like a root, bypassing the cache.
+    Within(PathBuf),
+    /// At most this many of the cached projects opened before, most
+    /// frecent first, as they are.
+    Recent(usize),
+}
+
+/// Keeps a second `ws pick`, say from a double-tapped key binding, from
+/// opening another picker while one is up: both would read the same
+/// terminal and then switch one after the other. The lock file holds the
+/// tmux pane of the picker holding it, for the second one to focus.
+struct PickGuard {
+    _file: fs::File,
+}
+
+impl PickGuard {
+    /// The guard, or `None` if another picker holds it; that picker's pane
+    /// is focused then, if it has one.
+    fn acquire() -> Result<Option<Self>> {
+        let path = State::state_path().with_file_name(PICK_LOCK_FILE);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut file = fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)?;
+        match file.try_lock() {
+            Ok(()) => {
+                file.set_len(0)?;
+                writeln!(file, "{}", std::env::var("TMUX_PANE").unwrap_or_default())?;
+                Ok(Some(Self { _file: file }))
+            }
+            Err(fs::TryLockError::WouldBlock) => {
+                let mut pane = String::new();
+                file.read_to_string(&mut pane).ok();
+                let pane = pane.trim();
+                // A picker in a popup has no pane, and one outside tmux no
+                // server to ask.
+                if !pane.is_empty() && TmuxClient::is_in_tmux() {
+                    TmuxClient::focus_pane(pane).ok();
+                }
+                Ok(None)
+            }
+            Err(fs::TryLockError::Error(e)) => Err(e.into()),
+        }
+    }
+}
+
+fn handle_pick_command(source: ProjectSource, output: PickOutput, config: &Config) -> Result<()> {
+    // Only pickers that switch need the guard; the background refresh runs
+    // while its parent holds it.
+    let _guard = if output == PickOutput::Open
+        && StateMode::current() == StateMode::Disk
+        && std::env::var_os(BACKGROUND_REFRESH_ENV).is_none()
+    {
+        match PickGuard::acquire()? {
+            Some(guard) => Some(guard),
+            None => return Ok(()),
+        }
+    } else {
+        None
+    };
+    let mut state = State::load();
+    let projects = match &source {
+        ProjectSource::Workspace if std::env::var_os(BACKGROUND_REFRESH_ENV).is_some() => {
+            // Another process may have refreshed the cache in the meantime.
+            state.ensure_cache_valid(config)?;
+            return state.save();
+        }
+        ProjectSource::Workspace => {
+            // An expired cache is shown as it is while a copy of this
+            // command rescans; the next pick gets the result.
+            let stale = StateMode::current().writes() && state.cache_stale(&config.workspaces);
+            if !stale || spawn_background_refresh().is_err() {
+                state.ensure_cache_valid(config)?;
+            }
+            state.cache.projects.clone()
+        }
+        ProjectSource::Stdin => read_projects(std::io::stdin().lock())?,
+        ProjectSource::Archived => archived_projects(config),
+        ProjectSource::Within(dir) => {
+            let dir = dir
+                .canonicalize()
+                .map_err(|e| format!("Cannot scan {}: {}", dir.display(), e))?;
+            let (projects, errors) =
+                scan_projects(&config.root(&dir.to_string_lossy()), &config.scan);
+            for error in errors {
+                messages::warn(messages::CANNOT_READ.format(&[&error.path, &error.message]));
+            }
+            projects
+        }
+        ProjectSource::Recent(_) => state.cache.projects.clone(),
+    };
+
+    // Outside the multiplexer its sessions are listed too, for attaching to.
+    let in_tmux = mux().is_inside();
+    let mut sessions = mux().list_sessions().unwrap_or_default();
+
+    if TmuxClient::is_in_tmux() {
+        let live: Vec<String> = sessions.iter().map(|s| s.name.clone()).collect();
+        let started = timings::time("tmux queries", TmuxClient::server_start_time);
+        let lost = state.lost_sessions(&live, started);
+        if !lost.is_empty() {
+            // The automatic snapshot brings back windows and panes as well.
+            let command = if state.snapshots.contains_key(AUTO_SNAPSHOT) {
+                "ws restore"
+            } else {
+                "ws resume"
+            };
+            notify(&format!(
+                "ws: tmux was restarted, `{}` recreates {} session(s)",
+                command,
+                lost.len()
+            ));
+        }
+    }
+
+    // Recent directories and org repositories only join the workspace's own
+    // projects.
+    let workspace_list = matches!(source, ProjectSource::Workspace);
+    let mut directories = if config.recent_dirs.enabled && workspace_list {
+        recent_directories(&config.workspaces, &projects, config.recent_dirs.limit)
+    } else {
+        Vec::new()
+    };
+
+    let (org_projects, remotes) = if workspace_list {
+        org_items(&mut state, config, &projects)
+    } else {
+        (Vec::new(), Vec::new())
+    };
+
+    let mut projects: Vec<ProjectInfo> = projects.into_iter().chain(org_projects).collect();
+    let mut pinned = if workspace_list {
+        state.pinned.clone()
+    } else {
+        Vec::new()
+    };
+    projects.retain(|p| !pinned.iter().any(|pin| pin.path == p.path));
+    let recent = matches!(source, ProjectSource::Recent(_));
+    let sort = if recent {
+        ProjectSort::Frecency
+    } else {
+        config.picker.sort
+    };
+    match sort {
+        ProjectSort::Frecency => {
+            let now = current_timestamp();
+            projects.sort_by(|a, b| {
+                state
+                    .frecency(&b.id, now)
+                    .total_cmp(&state.frecency(&a.id, now))
+            });
+            sessions.sort_by(|a, b| {
+                state
+                    .session_frecency(&b.name, now)
+                    .total_cmp(&state.session_frecency(&a.name, now))
+            });
+        }
+        ProjectSort::History => {
+            let visited = state.visited_paths();
+            projects.sort_by_key(|p| !visited.contains(p.path.as_str()));
+        }
+        ProjectSort::Alphabetical => {}
+    }
+    // Projects with a running session are already there as the session.
+    if let ProjectSource::Recent(limit) = source {
+        let now = current_timestamp();
+        let live: HashSet<&str> = sessions
+            .iter()
+            .filter_map(|s| state.sessions.get(&s.name))
+            .map(String::as_str)
+            .collect();
+        projects = projects
+            .into_iter()
+            .filter(|p| state.frecency(&p.id, now) > 0.0 && !live.contains(p.path.as_str()))
+            .filter(|p| Path::new(&p.path).is_dir())
+            .take(limit)
+            .collect();
+    }
+
+    if config.picker.git_status
+        && !recent
+        && tool_available("git", "the picker shows no git status")
+    {
+        let mut local: Vec<&mut ProjectInfo> = pinned
+            .iter_mut()
+            .chain(&mut projects)
+            .chain(&mut directories)
+            .collect();
+        let paths: Vec<String> = local.iter().map(|p| p.path.clone()).collect();
+        let paths: Vec<&str> = paths.iter().map(String::as_str).collect();
+        for (project, status) in local.iter_mut().zip(git_status::read_all(&paths)) {
+            project.git = status;
+        }
+    }
+
+    let home = if config.home.enabled && workspace_list {
+        vec![SelectableItem::Home(home_path(config))]
+    } else {
+        Vec::new()
+    };
+    let mut sections: Vec<(&str, Vec<SelectableItem>)> = vec![
+        ("home", home),
+        (
+            "pinned",
+            pinned.into_iter().map(SelectableItem::Project).collect(),
+        ),
+        (
+            "sessions",
+            sessions
+                .iter()
+                .map(|s| SelectableItem::Session(s.name.clone()))
+                .collect(),
+        ),
+        (
+            "projects",
+            projects.into_iter().map(SelectableItem::Project).collect(),
+        ),
+        ("to clone", remotes),
+        (
+            "recent directories",
+            directories
+                .into_iter()
+                .map(SelectableItem::Directory)
+                .collect(),
+        ),
+    ];
+    if sections.iter().all(|(_, items)| items.is_empty()) {
+        state.save()?;
+        return Err(nothing_to_pick_message(&source, config).into());
+    }
+
+    if config.picker.exclude_current && in_tmux {
+        if let Ok(current) = mux().current_session() {
+            let (sessions, path) = current_project_sessions(&state, &current);
+            for (_, section) in &mut sections {
+                section.retain(|item| match item {
+                    SelectableItem::Session(name) => !sessions.contains(name),
+                    _ => path.is_none() || item.path() != path.as_deref(),
+                });
+            }
+        }
+        if sections.iter().all(|(_, items)| items.is_empty()) {
+            state.save()?;
+            return Err(messages::NOTHING_BESIDES_CURRENT.text().into());
+        }
+    }
+
+    let paused: Vec<&str> = sessions
+        .iter()
+        .filter(|s| s.paused)
+        .map(|s| s.name.as_str())
+        .collect();
+
+    // Empty sections are left out, and each section after the first starts
+    // with a header row that maps to no item. The first has none so that
+    // the picker opens on an item.
+    let mut selectable_items = Vec::new();
+    let mut display_strings = Vec::new();
+    let mut rows = Vec::new();
+    let mut headers = Vec::new();
+    for (title, section) in sections.into_iter().filter(|(_, items)| !items.is_empty()) {
+        if !selectable_items.is_empty() {
+            headers.push(display_strings.len());
+            display_strings.push(title.to_string());
+            rows.push(None);
+        }
+        for item in section {
+            let mut label = item.to_display_string(&config.display.project);
+            if matches!(&item, SelectableItem::Session(name) if paused.contains(&name.as_str())) {
+                label.push_str(" (paused)");
+            }
+            display_strings.push(label);
+            rows.push(Some(selectable_items.len()));
+            selectable_items.push(item);
+        }
+    }
+
+    // Projects with the same category/name in different roots would show up
+    // as identical rows, so those get their path as a hint, after the git
+    // status if there is one.
+    let hints: Vec<String> = rows
+        .iter()
+        .zip(&display_strings)
+        .map(|(row, label)| {
+            let item = row.map(|index| &selectable_items[index]);
+            let mut hint = item
+                .and_then(SelectableItem::git_status)
+                .map(GitStatus::to_string)
+                .unwrap_or_default();
+            match item.and_then(SelectableItem::path) {
+                Some(path) if display_strings.iter().filter(|s| *s == label).count() > 1 => {
+                    if !hint.is_empty() {
+                        hint.push_str("  ");
+                    }
+                    hint.push_str(&tilde_path(path));
+                }
+                _ => {}
+            }
+            hint
+        })
+        .collect();
+
+    let actions: Vec<(&str, &str)> = if config.picker.app.is_some() {
+        vec![(APP_KEY, APP_ACTION)]
+    } else {
+        Vec::new()
+    };
+
+    let previews: Vec<String> = if config.picker.preview {
+        let git = tool_available("git", "previews show no git status");
+        rows.iter()
+            .map(|row| {
+                row.and_then(|index| preview_command(&selectable_items[index], &state, config, git))
+                    .unwrap_or_default()
+            })
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    // Cancelling still saves, so a refreshed cache or org listing is kept.
+    // It selects nothing, which --json reports as an empty result.
+    let list = PickerList {
+        items: &display_strings,
+        hints: &hints,
+        previews: &previews,
+        headers: &headers,
+    };
+    let (selected_rows, action) =
+        Picker::pick_multi(&list, "> ", config.picker.quick_select, &actions).unwrap_or_default();
+
+    let selected: Vec<SelectableItem> = selected_rows
+        .iter()
+        .filter_map(|&row| rows.get(row).copied().flatten())
+        .filter_map(|index| selectable_items.get(index).cloned())
+        .collect();
+
+    match output {
+        PickOutput::Open => {}
+        PickOutput::Print => {
+            for item in &selected {
+                println!("{}", item.print_value());
+            }
+            return state.save();
+        }
+        PickOutput::Json => {
+            let mut result = CommandResult::new("pick");
+            result.targets = selected
+                .iter()
+                .map(|item| item.print_value().to_string())
+                .collect();
+            result.print()?;
+            return state.save();
+        }
+    }
+    let Some(item) = selected.first() else {
+        return state.save();
+    };
+
+    if let (Some(APP_ACTION), Some(app)) = (action.as_deref(), &config.picker.app) {
+        for item in &selected {
+            launch_app(app, &item_directory(item, &state)?, config)?;
+        }
+        return state.save();
+    }
+
+    if selected.len() > 1 {
+        let batch_actions = BatchAction::available(&selected, config);
+        let labels: Vec<String> = batch_actions.iter().map(|a| a.label()).collect();
+        let prompt = format!("{} selected> ", selected.len());
+        return match Picker::pick(&labels, &[], &prompt, false, &[]) {
+            Some((index, _)) => batch_actions[index].run(selected, &mut state, config),
+            None => state.save(),
+        };
+    }
+
+    switch_to_selection(item.clone(), &mut state, config)
+}
+
+/// Runs this `ws pick` again in the background, with `BACKGROUND_REFRESH_ENV`
+/// set so it only refreshes the cache. The same arguments give it the same
+/// config, workspace and depth overrides. It gets its own process group so
+/// that closing a popup or split ws runs in doesn't kill it.
+fn spawn_background_refresh() -> Result<()> {
+    use std::process::Stdio;
+    Command::new(std::env::current_exe()?)
+        .args(std::env::args_os().skip(1))
+        .env(BACKGROUND_REFRESH_ENV, "1")
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .process_group(0)
+        .spawn()?;
+    Ok(())
+}
+
+/// Why `ws pick` has nothing to show, and what to do about it.
+fn nothing_to_pick_message(source: &ProjectSource, config: &Config) -> String {
+    match source {
+        ProjectSource::Workspace => {
+            let missing: Vec<String> = config
+                .workspaces
+                .iter()
+                .filter(|root| !Path::new(&root.path).exists())
+                .map(|root| tilde_path(&root.path))
+                .collect();
+            if !missing.is_empty() {
+                return format!(
+                    "No projects: {} does not exist; create it or point [[workspaces]] in \
+                     the config elsewhere",
+                    missing.join(", ")
+                );
+            }
+            let root = config
+                .workspaces
+                .first()
+                .map_or_else(|| "<root>".to_string(), |root| tilde_path(&root.path));
+            match config.scan.mode {
+                ScanMode::Depth => format!(
+                    "No projects or sessions yet; projects are directories like \
+                     {}/<category>/<project>, and `ws new <category>/<project>` creates one",
+                    root
+                ),
+                ScanMode::Markers => format!(
+                    "No projects or sessions yet; no directory under {} has one of the \
+                     markers ({})",
+                    root,
+                    config.scan.markers.join(", ")
+                ),
+            }
+        }
+        ProjectSource::Stdin => messages::NO_PROJECT_PATHS_ON_STDIN.text().to_string(),
+        ProjectSource::Archived => messages::NO_ARCHIVED_PROJECTS.text().to_string(),
+        ProjectSource::Within(dir) => messages::NO_PROJECTS_IN.format(&[&dir.display()]),
+        ProjectSource::Recent(_) => messages::NO_RECENT_PROJECTS.text().to_string(),
+    }
+}
+
+/// Opens `item` through `handle_selection`, saves state and switches to (or
+/// attaches) its session.
+fn switch_to_selection(item: SelectableItem, state: &mut State, config: &Config) -> Result<()> {
+    let (session_name, created) =
+        timings::time("switch", || handle_selection(item, state, config))?;
+    state.save()?;
+    switch_to(&session_name, created, state, config)
+}
+
+/// Lets the user pick one of the project's recent files, then opens it in
+/// the editor window of the project's session and switches there.
+fn handle_files_command(query: Option<&str>, config: &Config) -> Result<()> {
+    let mut state = State::load();
+    let project = target_project(query, &mut state, config)?;
+    let opened = state
+        .recent_files
+        .get(&project.id)
+        .cloned()
+        .unwrap_or_default();
+    let files = recent_files::load(Path::new(&project.path), &opened, RECENT_FILES_LISTED);
+    if files.is_empty() {
+        state.save()?;
+        return Err(messages::NO_RECENT_FILES
+            .format(&[&project.display_name()])
+            .into());
+    }
+
+    let now = SystemTime::now();
+    let labels: Vec<String> = files
+        .iter()
+        .map(|(path, _)| {
+            path.strip_prefix(&project.path)
+                .unwrap_or(path)
+                .to_string_lossy()
+                .to_string()
+        })
+        .collect();
+    let hints: Vec<String> = files
+        .iter()
+        .map(|(_, modified)| {
+            format_age(
+                now.duration_since(*modified)
+                    .map_or(0, |age| age.as_secs() as i64),
+            )
+        })
+        .collect();
+    let Some((index, _)) = Picker::pick(&labels, &hints, "file> ", config.picker.quick_select, &[])
+    else {
+        return state.save();
+    };
+    let file = files[index].0.to_string_lossy().to_string();
+
+    let (session_name, created) = open_project(&project, &mut state, config)?;
+    TmuxClient::open_file(&session_name, &file, None, &config.layout, created)?;
+    let recent = state.recent_files.entry(project.id.clone()).or_default();
+    recent.retain(|f| f != &file);
+    recent.insert(0, file);
+    recent.truncate(RECENT_FILES_KEPT);
+    state.save()?;
+
+    switch_to(&session_name, created, &state, config)
+}
+
+/// Lists the TODO and FIXME comments of the workspace's projects (or one
+/// project) in the picker, with the lines around each in the preview, then
+/// opens the chosen one at its line in the project's editor window.
+fn handle_todos_command(query: Option<&str>, refresh: bool, config: &Config) -> Result<()> {
+    let mut state = State::load();
+    state.ensure_cache_valid(config)?;
+    let projects: Vec<ProjectInfo> = match query {
+        Some(query) => vec![state.find_project(query)?.clone()],
+        None => state.cache.projects.clone(),
+    };
+    let paths: Vec<&str> = projects.iter().map(|p| p.path.as_str()).collect();
+    let cache_file = State::state_path().with_file_name(TODOS_CACHE_FILE);
+    let found = todos::cached(
+        &paths,
+        &cache_file,
+        refresh,
+        StateMode::current().writes(),
+        current_timestamp(),
+    )?;
+
+    let todos: Vec<(&ProjectInfo, todos::Todo)> = projects
+        .iter()
+        .zip(found)
+        .flat_map(|(project, todos)| todos.into_iter().map(move |todo| (project, todo)))
+        .collect();
+    if todos.is_empty() {
+        state.save()?;
+        return Err(match query {
+            Some(_) => messages::NO_TODOS_IN.format(&[&projects[0].display_name()]),
+            None => messages::NO_TODOS.text().to_string(),
+        }
+        .into());
+    }
+
+    let labels: Vec<String> = todos
+        .iter()
+        .map(|(project, todo)| {
+            let location = format!("{}:{}", todo.file, todo.line);
+            if query.is_some() {
+                location
+            } else {
+                format!("{} {}", project.label(&config.display.project), location)
+            }
+        })
+        .collect();
+    let hints: Vec<String> = todos.iter().map(|(_, todo)| todo.text.clone()).collect();
+    // The line itself is marked with `>`.
+    let previews: Vec<String> = todos
+        .iter()
+        .map(|(project, todo)| {
+            let file = Path::new(&project.path).join(&todo.file);
+            format!(
+                "awk -v l={} 'NR >= l - {} && NR <= l + {} \
+                 {{ printf \"%s%5d  %s\\n\", NR == l ? \">\" : \" \", NR, $0 }}' {}",
+                todo.line,
+                TODO_PREVIEW_CONTEXT,
+                TODO_PREVIEW_CONTEXT * 2,
+                shell_quote(&file.to_string_lossy())
+            )
+        })
+        .collect();
+    let list = PickerList {
+        items: &labels,
+        hints: &hints,
+        previews: &previews,
+        headers: &[],
+    };
+    let Some((index, _)) = Picker::pick_one(&list, "todo> ", config.picker.quick_select, &[])
+    else {
+        return state.save();
+    };
+    let (project, todo) = &todos[index];
+    let file = Path::new(&project.path).join(&todo.file);
+
+    let (session_name, created) = open_project(project, &mut state, config)?;
+    TmuxClient::open_file(
+        &session_name,
+        &file.to_string_lossy(),
+        Some(todo.line),
+        &config.layout,
+        created,
+    )?;
+    state.save()?;
+
+    switch_to(&session_name, created, &state, config)
+}
+
+/// Switches to the live session or workspace project `query` names, for
+/// key bindings and scripts. Exact names win; otherwise it must be the
+/// prefix of exactly one session or project.
+fn handle_switch_command(query: &str, config: &Config) -> Result<()> {
+    let mut state = State::load();
+    state.ensure_cache_valid(config)?;
+    let sessions: Vec<String> = mux()
+        .list_sessions()
+        .unwrap_or_default()
+        .into_iter()
+        .map(|s| s.name)
+        .collect();
+
+    let item = resolve_switch_target(query, &sessions, &state, config)?;
+    switch_to_selection(item, &mut state, config)
+}
+
+fn resolve_switch_target(
+    query: &str,
+    sessions: &[String],
+    state: &State,
+    config: &Config,
+) -> Result<SelectableItem> {
+    if sessions.iter().any(|s| s == query) {
+        return Ok(SelectableItem::Session(query.to_string()));
+    }
+    let projects = &state.cache.projects;
+    let exact = projects
+        .iter()
+        .any(|p| p.name == query || p.display_name() == query || p.qualified_name() == query);
+    if exact {
+        return Ok(SelectableItem::Project(
+            find_project_in(projects, query)?.clone(),
+        ));
+    }
+
+    // A session and the project it was opened from are one match.
+    let matched_sessions: Vec<&String> = sessions.iter().filter(|s| s.starts_with(query)).collect();
+    let session_paths: Vec<&str> = matched_sessions
+        .iter()
+        .filter_map(|s| state.project_for_session(s, &config.session))
+        .map(|p| p.path.as_str())
+        .collect();
+    let matched_projects: Vec<&ProjectInfo> = projects
+        .iter()
+        .filter(|p| {
+            p.name.starts_with(query)
+                || p.display_name().starts_with(query)
+                || p.qualified_name().starts_with(query)
+        })
+        .filter(|p| !session_paths.contains(&p.path.as_str()))
+        .collect();
+
+    match (matched_sessions.as_slice(), matched_projects.as_slice()) {
+        ([], []) => Err(messages::NOTHING_MATCHING.format(&[&query]).into()),
+        ([session], []) => Ok(SelectableItem::Session(session.to_string())),
+        ([], [project]) => Ok(SelectableItem::Project((*project).clone())),
+        _ => {
+            let names: Vec<String> = matched_sessions
+                .iter()
+                .map(|s| s.to_string())
+                .chain(matched_projects.iter().map(|p| p.qualified_name()))
+                .collect();
+            Err(messages::AMBIGUOUS_PROJECT
+                .format(&[&query, &names.join(", ")])
+                .into())
+        }
+    }
+}
+
+/// Shell command for the picker's preview of `item`: the one
+/// `[picker.previews]` configures for its kind, else the built-in one.
+/// `git` is whether git is there for the built-in one's status.
+fn preview_command(
+    item: &SelectableItem,
+    state: &State,
+    config: &Config,
+    git: bool,
+) -> Option<String> {
+    let previews = &config.picker.previews;
+    let (configured, name) = match item {
+        SelectableItem::Session(name) => (&previews.session, name.as_str()),
+        SelectableItem::Project(project) => (&previews.project, project.name.as_str()),
+        SelectableItem::Directory(project) => (&previews.directory, project.name.as_str()),
+        SelectableItem::Remote(project, _) => (&previews.clone, project.name.as_str()),
+        SelectableItem::Home(_) => (&previews.home, config.home.session.as_str()),
+    };
+    let Some(command) = configured else {
+        return builtin_preview_command(item, config, git);
+    };
+    if command.trim().is_empty() {
+        return None;
+    }
+
+    let path = match item {
+        SelectableItem::Session(name) => state.sessions.get(name).map(String::as_str),
+        _ => item.path(),
+    };
+    let command = command
+        .replace("{path}", &shell_quote(path.unwrap_or_default()))
+        .replace("{name}", &shell_quote(name));
+    match path.filter(|path| Path::new(path).is_dir()) {
+        Some(path) => Some(format!("cd {} && {}", shell_quote(path), command)),
+        None => Some(command),
+    }
+}
+
+/// The built-in preview: the screen of a session, or a project's git status
+/// and the start of its README, after its GitHub health with
+/// `picker.health`.
+fn builtin_preview_command(item: &SelectableItem, config: &Config, git: bool) -> Option<String> {
+    match item {
+        SelectableItem::Session(name) => mux().preview_command(name),
+        SelectableItem::Project(project) | SelectableItem::Directory(project) => {
+            let mut command = format!("cd {} || exit; ", shell_quote(&project.path));
+            if config.picker.health && health::available() {
+                if let Ok(ws) = std::env::current_exe() {
+                    command.push_str(&format!(
+                        "{} health --path . 2>/dev/null && echo; ",
+                        shell_quote(&ws.to_string_lossy())
+                    ));
+                }
+            }
+            if git {
+                command.push_str("git status --short --branch 2>/dev/null; ");
+            }
+            command.push_str(&format!(
+                "for f in README.md README readme.md README.rst README.txt; do \
+                 [ -f \"$f\" ] && {{ echo; head -n {} \"$f\"; break; }}; done",
+                PREVIEW_README_LINES
+            ));
+            Some(command)
+        }
+        SelectableItem::Home(path) => Some(format!(
+            "cd {} || exit; head -n {} {} 2>/dev/null",
+            shell_quote(path),
+            PREVIEW_README_LINES,
+            config
+                .home
+                .notes
+                .iter()
+                .map(|file| shell_quote(file))
+                .collect::<Vec<_>>()
+                .join(" ")
+        )),
+        SelectableItem::Remote(..) => None,
+    }
+}
+
+/// The directory behind a picker item, cloning remote repositories first.
+fn item_directory(item: &SelectableItem, state: &State) -> Result<String> {
+    match item {
+        SelectableItem::Session(name) => state
+            .sessions
+            .get(name)
+            .cloned()
+            .ok_or_else(|| format!("No project directory known for session '{}'", name).into()),
+        SelectableItem::Project(project) | SelectableItem::Directory(project) => {
+            Ok(project.path.clone())
+        }
+        SelectableItem::Remote(project, repo) => {
+            ensure_cloned(project, repo)?;
+            Ok(project.path.clone())
+        }
+        SelectableItem::Home(path) => Ok(path.clone()),
+    }
+}
+
+/// What can be done with several picker items at once.
+enum BatchAction {
+    /// Open a session for each item and switch to the first.
+    OpenAll,
+    /// Open each item in the `picker.app` editor.
+    OpenInApp(String),
+    /// Pin the selected projects that aren't pinned yet.
+    PinAll,
+    /// Move the selected projects into a category picked next, which adds
+    /// them to that category's profile.
+    AddToProfile,
+    /// Move the selected projects into their roots' archives, killing their
+    /// sessions.
+    ArchiveAll,
+    /// Kill the selected sessions and their groups.
+    KillSessions,
+}
+
+impl BatchAction {
+    fn available(items: &[SelectableItem], config: &Config) -> Vec<Self> {
+        let mut actions = vec![Self::OpenAll];
+        if let Some(app) = &config.picker.app {
+            actions.push(Self::OpenInApp(app.clone()));
+        }
+        if !Self::projects(items).is_empty() {
+            actions.extend([Self::PinAll, Self::AddToProfile, Self::ArchiveAll]);
+        }
+        if items
+            .iter()
+            .any(|item| matches!(item, SelectableItem::Session(_)))
+        {
+            actions.push(Self::KillSessions);
+        }
+        actions
+    }
+
+    fn label(&self) -> String {
+        match self {
+            Self::OpenAll => "open all".to_string(),
+            Self::OpenInApp(app) => format!("open all in {}", app),
+            Self::PinAll => "pin all".to_string(),
+            Self::AddToProfile => "add to profile".to_string(),
+            Self::ArchiveAll => "archive all".to_string(),
+            Self::KillSessions => "kill selected sessions".to_string(),
+        }
+    }
+
+    /// The projects among `items`; sessions and clone entries have none of
+    /// their own.
+    fn projects(items: &[SelectableItem]) -> Vec<&ProjectInfo> {
+        items
+            .iter()
+            .filter_map(|item| match item {
+                SelectableItem::Project(project) | SelectableItem::Directory(project) => {
+                    Some(project)
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn run(&self, items: Vec<SelectableItem>, state: &mut State, config: &Config) -> Result<()> {
+        let projects: Vec<ProjectInfo> = Self::projects(&items).into_iter().cloned().collect();
+        match self {
+            Self::OpenAll => {
+                // Last opened ends up most recent in history, so open the
+                // first item last and switch to it.
+                let mut opened = Vec::new();
+                for item in items.into_iter().rev() {
+                    opened.push(handle_selection(item, state, config)?.0);
+                }
+                opened.reverse();
+                state.save()?;
+
+                let message = format!("opened {}", opened.join(", "));
+                switch_reporting(&opened[0], &message, state, config)
+            }
+            Self::OpenInApp(app) => {
+                for item in &items {
+                    launch_app(app, &item_directory(item, state)?, config)?;
+                }
+                state.save()
+            }
+            Self::PinAll => {
+                let mut pinned = Vec::new();
+                for project in projects {
+                    if !state.is_pinned(&project) {
+                        pinned.push(project.display_name());
+                        state.pinned.push(project);
+                    }
+                }
+                state.save()?;
+                if pinned.is_empty() {
+                    report("all of them are pinned already", config);
+                } else {
+                    report(&format!("pinned {}", pinned.join(", ")), config);
+                }
+                Ok(())
+            }
+            Self::AddToProfile => {
+                let roots = projects
+                    .iter()
+                    .map(|project| workspace_root_of(project, config))
+                    .collect::<Result<Vec<_>>>()?;
+                let categories: Vec<(&WorkspaceRoot, String, PathBuf)> = list_categories(config)?
+                    .into_iter()
+                    .filter(|(root, _, _)| roots.iter().any(|r| r.path == root.path))
+                    .collect();
+                let labels: Vec<String> = categories
+                    .iter()
+                    .map(|(root, name, _)| format!("{}/{}", root.alias, name))
+                    .collect();
+                let Some((index, _)) = Picker::pick(&labels, &[], "profile> ", false, &[]) else {
+                    return state.save();
+                };
+                let (root, category, path) = &categories[index];
+                let mut moves = Vec::new();
+                for (project, project_root) in projects.iter().zip(&roots) {
+                    if project_root.path != root.path {
+                        return Err(format!(
+                            "{} is not in {}",
+                            project.display_name(),
+                            tilde_path(&root.path)
+                        )
+                        .into());
+                    }
+                    if &project.category == category {
+                        continue;
+                    }
+                    let destination = path.join(&project.name);
+                    if destination.exists() {
+                        return Err(messages::ALREADY_EXISTS
+                            .format(&[&destination.display()])
+                            .into());
+                    }
+                    moves.push((project, destination));
+                }
+                if moves.is_empty() {
+                    report(
+                        &format!("all of them are in {} already", labels[index]),
+                        config,
+                    );
+                    return Ok(());
+                }
+                let mut moved = Vec::new();
+                for (project, destination) in moves {
+                    // Keep what was moved so far recorded.
+                    if let Err(e) = move_project(project, &destination, state, config) {
+                        state.save()?;
+                        return Err(e);
+                    }
+                    moved.push(project.name.clone());
+                }
+                state.save()?;
+                report(
+                    &format!("moved {} to {}", moved.join(", "), labels[index]),
+                    config,
+                );
+                Ok(())
+            }
+            Self::ArchiveAll => {
+                let mut plans = Vec::new();
+                for project in &projects {
+                    let destination = archive_destination(project, config)?;
+                    plans.push((project, destination, project_sessions(project, state)));
+                }
+                let names: Vec<String> = projects.iter().map(|p| p.display_name()).collect();
+                let targets: Vec<String> = plans
+                    .iter()
+                    .flat_map(|(_, _, targets)| targets.iter().cloned())
+                    .collect();
+                let question = if targets.is_empty() {
+                    format!("Archive {}?", names.join(", "))
+                } else {
+                    format!(
+                        "Archive {} and kill {}?",
+                        names.join(", "),
+                        targets.join(", ")
+                    )
+                };
+                if !confirm(&question)? {
+                    return state.save();
+                }
+                let mut summaries = Vec::new();
+                for (project, destination, targets) in &plans {
+                    match archive_project(project, destination, targets, state, config) {
+                        Ok(summary) => summaries.push(summary),
+                        Err(e) => {
+                            state.save()?;
+                            return Err(e);
+                        }
+                    }
+                }
+                state.save()?;
+                report(&summaries.join("; "), config);
+                Ok(())
+            }
+            Self::KillSessions => {
+                let mut targets = Vec::new();
+                for item in &items {
+                    if let SelectableItem::Session(name) = item {
+                        for session in state.session_group(name) {
+                            if !targets.contains(&session) {
+                                targets.push(session);
+                            }
+                        }
+                    }
+                }
+                let outcome = kill_sessions(&targets, state, config)?;
+                state.save()?;
+                report(&outcome.message(), config);
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Sessions that belong to the same project as `current` (its group and any
+/// other session opened on the project's path) and that project's path.
+fn current_project_sessions(state: &State, current: &str) -> (Vec<String>, Option<String>) {
+    let mut sessions = state.session_group(current);
+    let path = state.sessions.get(current).cloned();
+    if let Some(path) = &path {
+        sessions.extend(
+            state
+                .sessions
+                .iter()
+                .filter(|(_, session_path)| *session_path == path)
+                .map(|(name, _)| name.clone()),
+        );
+    }
+    (sessions, path)
+}
+
+/// Org repositories for the picker: cloned ones that the workspace scan did
+/// not already find, and clone entries for the rest. Orgs that can't be
+/// listed are reported and skipped.
+fn org_items(
+    state: &mut State,
+    config: &Config,
+    projects: &[ProjectInfo],
+) -> (Vec<ProjectInfo>, Vec<SelectableItem>) {
+    let mut cloned = Vec::new();
+    let mut remotes = Vec::new();
+
+    for org in &config.orgs {
+        let repos = match state.org_repos(org, false) {
+            Ok(repos) => repos,
+            Err(e) => {
+                messages::warn(messages::LIST_REPOSITORIES_FAILED.format(&[&org.name, &e]));
+                continue;
+            }
+        };
+
+        for repo in repos {
+            let path = Path::new(&org.path).join(&repo.name);
+            let Some(project) = ProjectInfo::from_path(&path) else {
+                continue;
+            };
+            if path.is_dir() {
+                if !projects.iter().any(|p| p.path == project.path) {
+                    cloned.push(project);
+                }
+            } else {
+                remotes.push(SelectableItem::Remote(project, repo.clone()));
+            }
+        }
+    }
+
+    (cloned, remotes)
+}
+
+/// Prepares the selected item and returns the session to switch to, and
+/// whether it had to be created. State is updated but not saved, so callers
+/// can persist it before attaching.
+fn handle_selection(
+    item: SelectableItem,
+    state: &mut State,
+    config: &Config,
+) -> Result<(String, bool)> {
+    match item {
+        SelectableItem::Session(name) => {
+            state.push_history(name.clone(), &config.history);
+            state.record_session_open(&name);
+            Ok((name, false))
+        }
+        SelectableItem::Project(project) | SelectableItem::Directory(project) => {
+            open_project(&project, state, config)
+        }
+        SelectableItem::Remote(project, repo) => {
+            ensure_cloned(&project, &repo)?;
+            open_project(&project, state, config)
+        }
+        SelectableItem::Home(path) => open_home(&path, state, config),
+    }
+}
+
+/// The home session's directory: `home.path`, else the first workspace root
+/// that is a directory, else the home directory.
+fn home_path(config: &Config) -> String {
+    config
+        .home
+        .path
+        .clone()
+        .or_else(|| {
+            config
+                .workspaces
+                .iter()
+                .find(|root| !root.is_list())
+                .map(|root| root.path.clone())
+        })
+        .or_else(|| dirs::home_dir().map(|home| home.to_string_lossy().to_string()))
+        .unwrap_or_else(|| ".".to_string())
+}
+
+/// Ensures the home session exists and records it in `state`, like
+/// `open_project` does for a project.
+fn open_home(path: &str, state: &mut State, config: &Config) -> Result<(String, bool)> {
+    let session_name = naming::sanitize(&config.home.session)?;
+    let created = !mux().has_session(&session_name)?;
+    if created {
+        mux().create_session(&session_name, path, &Layout::home(config)?, &[])?;
+    }
+    state
+        .sessions
+        .insert(session_name.clone(), path.to_string());
+    if created {
+        run_hook(Hook::Create, &session_name, state, config);
+    }
+    state.push_history(session_name.clone(), &config.history);
+    Ok((session_name, created))
+}
+
+fn ensure_cloned(project: &ProjectInfo, repo: &RemoteRepo) -> Result<()> {
+    let path = Path::new(&project.path);
+    if !path.exists() {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        org::clone_repo(repo, path)?;
+    }
+    Ok(())
+}
+
+/// Ensures the project's session exists and records it in `state`,
+/// returning the session name and whether it was created. Switching is left
+/// to the caller.
+fn open_project(
+    project: &ProjectInfo,
+    state: &mut State,
+    config: &Config,
+) -> Result<(String, bool)> {
+    let mut session_name =
+        naming::project_session_name(project, &state.cache.projects, &config.session)?;
+    // A session opened before another project's name started to collide
+    // with this one's keeps being used.
+    let undisambiguated = naming::sanitize(&naming::template_name(project, &config.session))?;
+    if session_name != undisambiguated
+        && state.sessions.get(&undisambiguated) == Some(&project.path)
+        && mux().has_session(&undisambiguated)?
+    {
+        session_name = undisambiguated;
+    }
+
+    let created = !mux().has_session(&session_name)?;
+    if created {
+        let env = session_env(project, &config.session)?;
+        mux().create_session(
+            &session_name,
+            &project.path,
+            &Layout::for_project(project, config)?,
+            &env,
+        )?;
+    }
+
+    state
+        .sessions
+        .insert(session_name.clone(), project.path.clone());
+    state.record_open(project);
+    if created {
+        run_hook(Hook::Create, &session_name, state, config);
+    }
+    if BACKEND
+        .get()
+        .is_none_or(|backend| *backend == Backend::Tmux)
+    {
+        update_status_options(&session_name, project).ok();
+        if created {
+            if let Err(e) = enforce_session_limit(&session_name, state, config) {
+                messages::warn(messages::MAX_SESSIONS_FAILED.format(&[&e]));
+            }
+        } else if let Ok(layout) = Layout::for_project(project, config) {
+            TmuxClient::rename_windows(&session_name, &layout).ok();
+        }
+    }
+
+    state.push_history(session_name.clone(), &config.history);
+    Ok((session_name, created))
+}
+
+fn handle_open_command(
+    query: &str,
+    window: Option<&str>,
+    app: Option<&str>,
+    with_session: bool,
+    config: &Config,
+) -> Result<()> {
+    let mut state = State::load();
+    state.ensure_cache_valid(config)?;
+
+    let project = state.find_project(query)?.clone();
+    if let Some(app) = app {
+        launch_app(app, &project.path, config)?;
+        if !with_session {
+            return state.save();
+        }
+    }
+
+    let (session_name, created) = open_project(&project, &mut state, config)?;
+
+    if let Some(window) = window {
+        TmuxClient::select_window(&session_name, window)?;
+    }
+
+    state.save()?;
+    switch_to(&session_name, created, &state, config)
+}
+
+/// Runs a `.ws.toml` task of the project (or the current session's project)
+/// in a window named after the task, creating the session if needed.
+fn handle_run_command(
+    query: Option<&str>,
+    task: Option<&str>,
+    pick: bool,
+    config: &Config,
+) -> Result<()> {
+    let mut state = State::load();
+    let project = target_project(query, &mut state, config)?;
+
+    let project_config = ProjectConfig::load(&project.path)?;
+    let task = match task {
+        Some(task) => task.to_string(),
+        None if pick => {
+            if project_config.tasks.is_empty() {
+                return Err(format!(
+                    "No tasks defined in {}",
+                    ProjectConfig::path(&project.path).display()
+                )
+                .into());
+            }
+            let names: Vec<String> = project_config.tasks.keys().cloned().collect();
+            let hints: Vec<String> = project_config.tasks.values().cloned().collect();
+            match Picker::pick(&names, &hints, "task> ", config.picker.quick_select, &[]) {
+                Some((index, _)) => names[index].clone(),
+                None => return Ok(()),
+            }
+        }
+        None => return Err("Missing task to run (or pass --pick)".into()),
+    };
+    let command = project_config.task(&project.path, &task)?;
+
+    let (session_name, _) = open_project(&project, &mut state, config)?;
+    TmuxClient::run_in_window(&session_name, &task, &project.path, command)?;
+    state.save()?;
+
+    if mux().is_inside() {
+        TmuxClient::select_window(&session_name, &task)?;
+        announce_switch(&session_name, &state, config);
+        mux().switch_client(&session_name)?;
+    }
+    report(
+        &format!("running {} in {}:{}", task, session_name, task),
+        config,
+    );
+    Ok(())
+}
+
+/// Opens a session on a branch of the project: the project's own session if
+/// the branch is checked out there, otherwise one on the branch's worktree,
+/// which is created if needed.
+fn handle_branch_command(branch: Option<&str>, query: Option<&str>, config: &Config) -> Result<()> {
+    let mut state = State::load();
+    let project = target_project(query, &mut state, config)?;
+
+    // From a branch session, the project is the repository's main checkout.
+    let repo = worktree::main_worktree(&project.path)?;
+    let same_path = |path: &str| fs::canonicalize(path).ok() == fs::canonicalize(&repo).ok();
+    let project = if same_path(&project.path) {
+        project
+    } else {
+        state
+            .cache
+            .projects
+            .iter()
+            .find(|p| same_path(&p.path))
+            .cloned()
+            .or_else(|| ProjectInfo::from_path(&repo))
+            .ok_or_else(|| format!("Invalid project path: {}", repo.display()))?
+    };
+
+    let branches = worktree::branches(&repo)?;
+    let branch = match branch {
+        Some(name) => branches
+            .iter()
+            .find(|b| b.name == name)
+            .ok_or_else(|| format!("No branch '{}' in {}", name, project.display_name()))?,
+        None => {
+            if branches.is_empty() {
+                return Err(messages::NO_BRANCHES
+                    .format(&[&project.display_name()])
+                    .into());
+            }
+            let names: Vec<String> = branches.iter().map(|b| b.name.clone()).collect();
+            let hints: Vec<String> = branches
+                .iter()
+                .map(|b| match &b.worktree {
+                    Some(path) => tilde_path(&path.to_string_lossy()),
+                    None if b.remote_only => "remote".to_string(),
+                    None => String::new(),
+                })
+                .collect();
+            match Picker::pick(&names, &hints, "branch> ", config.picker.quick_select, &[]) {
+                Some((index, _)) => &branches[index],
+                None => return Ok(()),
+            }
+        }
+    };
+
+    let target = match &branch.worktree {
+        Some(path) if same_path(&path.to_string_lossy()) => project.clone(),
+        existing => {
+            let path = match existing {
+                Some(path) => path.clone(),
+                None => {
+                    let path = worktree::worktree_path(&repo, &branch.name);
+                    worktree::add_worktree(&repo, &path, &branch.name)?;
+                    path
+                }
+            };
+            ProjectInfo {
+                id: project_id(&path),
+                path: path.to_string_lossy().to_string(),
+                category: project.category.clone(),
+                name: format!("{}@{}", project.name, branch.name),
+                root: project.root.clone(),
+                git: None,
+            }
+        }
+    };
+
+    let (session_name, created) = open_project(&target, &mut state, config)?;
+    state.save()?;
+    switch_to(&session_name, created, &state, config)
+}
+
+/// The project named by `query`, or the current session's when there is none.
+fn target_project(query: Option<&str>, state: &mut State, config: &Config) -> Result<ProjectInfo> {
+    match query {
+        Some(query) => {
+            state.ensure_cache_valid(config)?;
+            Ok(state.find_project(query)?.clone())
+        }
+        None => {
+            let session = mux()
+                .is_inside()
+                .then(|| mux().current_session().ok())
+                .flatten()
+                .ok_or("No project given and not in a tmux session")?;
+            let path = state
+                .sessions
+                .get(&session)
+                .ok_or_else(|| format!("Session '{}' was not opened by ws", session))?;
+            ProjectInfo::from_path(Path::new(path))
+                .ok_or_else(|| format!("Invalid project path: {}", path).into())
+        }
+    }
+}
+
+/// Prints the CI state and open pull request count of the default branch of
+/// a project's GitHub repository, cached in `health.json`.
+fn handle_health_command(query: Option<&str>, path: Option<&Path>, config: &Config) -> Result<()> {
+    if !health::available() {
+        return Err("ws health needs GITHUB_TOKEN to be set".into());
+    }
+    let path = match path {
+        Some(path) => path.to_string_lossy().to_string(),
+        None => {
+            let mut state = State::load();
+            let project = target_project(query, &mut state, config)?;
+            state.save()?;
+            project.path
+        }
+    };
+
+    let remote = inventory::git_remote(&path)
+        .ok_or_else(|| format!("{} has no origin remote", tilde_path(&path)))?;
+    let repo = health::github_repo(&remote)
+        .ok_or_else(|| format!("{} is not a GitHub repository", remote))?;
+    let cache_file = State::state_path().with_file_name(HEALTH_CACHE_FILE);
+    println!(
+        "{}",
+        health::cached(
+            &repo,
+            &cache_file,
+            StateMode::current().writes(),
+            current_timestamp()
+        )?
+    );
+    Ok(())
+}
+
+/// Converts another sessionizer's config, printing the ws config for it or
+/// appending it to the config file with `write`.
+fn handle_import_command(
+    from: ImportSource,
+    file: Option<&Path>,
+    write: bool,
+    config: &Config,
+) -> Result<()> {
+    let config_path = Config::path();
+    let import = match from {
+        ImportSource::Sesh => {
+            let file = file.map_or_else(import::sesh_config_path, Path::to_path_buf);
+            import::sesh(&file, &config_path.with_file_name(SESH_LIST_FILE))?
+        }
+        ImportSource::TSmartTmux => {
+            if file.is_some() {
+                return Err(
+                    "t-smart-tmux has no config file; its directories come from zoxide".into(),
+                );
+            }
+            import::t_smart_tmux()
+        }
+        ImportSource::Tmuxinator => import::tmuxinator(
+            &file.map_or_else(import::tmuxinator_dir, Path::to_path_buf),
+            &config_path.with_file_name(TMUXINATOR_LIST_FILE),
+            config,
+        )?,
+        ImportSource::Tmuxp => import::tmuxp(
+            &file.map_or_else(import::tmuxp_dir, Path::to_path_buf),
+            &config_path.with_file_name(TMUXP_LIST_FILE),
+            config,
+        )?,
+    };
+    let layout_names: Vec<&str> = import.layouts.iter().map(|(n, _)| n.as_str()).collect();
+    for warning in &import.warnings {
+        messages::warn(warning);
+    }
+
+    if !write {
+        print!("{}", import.snippet);
+        if let Some((list, paths)) = &import.list {
+            eprintln!(
+                "{}",
+                messages::IMPORT_LIST_HINT
+                    .format(&[&paths.len(), &tilde_path(&list.to_string_lossy())])
+            );
+        }
+        if !layout_names.is_empty() {
+            eprintln!(
+                "{}",
+                messages::IMPORT_LAYOUTS_HINT.format(&[
+                    &tilde_path(&config.layouts_dir.to_string_lossy()),
+                    &layout_names.join(", "),
+                ])
+            );
+        }
+        return Ok(());
+    }
+
+    // Layouts next, so projects never get a layout that isn't there. An
+    // existing layout of the same name is kept, since it may have been
+    // edited since an earlier import.
+    for (name, layout) in &import.layouts {
+        let path = config.layouts_dir.join(format!("{}.toml", name));
+        if path.exists() {
+            messages::warn(messages::LAYOUT_EXISTS.format(&[&tilde_path(&path.to_string_lossy())]));
+            continue;
+        }
+        fs::create_dir_all(&config.layouts_dir)?;
+        fs::write(&path, layout)
+            .map_err(|e| messages::WRITE_FAILED.format(&[&path.display(), &e]))?;
+        println!("Wrote {}", tilde_path(&path.to_string_lossy()));
+    }
+
+    // The list goes first so the config never names a missing file.
+    if let Some((list, paths)) = &import.list {
+        if let Some(parent) = list.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(list, paths.join("\n") + "\n")
+            .map_err(|e| messages::WRITE_FAILED.format(&[&list.display(), &e]))?;
+        println!(
+            "Wrote {} project paths to {}",
+            paths.len(),
+            tilde_path(&list.to_string_lossy())
+        );
+    }
+    if import.snippet.is_empty() {
+        println!(
+            "{} already has the imported config",
+            tilde_path(&config_path.to_string_lossy())
+        );
+        return Ok(());
+    }
+    if let Err(e) = Config::append(&config_path, &import.snippet) {
+        print!("{}", import.snippet);
+        return Err(format!("{}; add the config above by hand", e).into());
+    }
+    println!("Updated {}", tilde_path(&config_path.to_string_lossy()));
+    Ok(())
+}
+
+/// Prints the environment a project's sessions get, plus what direnv adds,
+/// as shell code to eval.
+fn handle_env_command(query: Option<&str>, shell: Option<EnvShell>, config: &Config) -> Result<()> {
+    let mut state = State::load();
+    let project = target_project(query, &mut state, config)?;
+    state.save()?;
+
+    let mut env: Vec<(String, Option<String>)> = session_env(&project, &config.session)?
+        .into_iter()
+        .map(|(key, value)| (key, Some(value)))
+        .collect();
+    match direnv_env(&project.path) {
+        Ok(vars) => env.extend(vars),
+        Err(e) => messages::warn(e),
+    }
+
+    let shell = shell.unwrap_or_else(|| {
+        let login_shell = std::env::var("SHELL").unwrap_or_default();
+        if Path::new(&login_shell).file_name() == Some("fish".as_ref()) {
+            EnvShell::Fish
+        } else {
+            EnvShell::Posix
+        }
+    });
+    for (key, value) in env {
+        // The output is eval'd, so a key must not be able to carry code.
+        if !is_env_name(&key) {
+            messages::warn(format!("skipping variable {:?}: not a valid name", key));
+            continue;
+        }
+        let line = match (shell, value) {
+            (EnvShell::Posix, Some(value)) => format!("export {}={}", key, shell_quote(&value)),
+            (EnvShell::Posix, None) => format!("unset {}", key),
+            (EnvShell::Fish, Some(value)) => format!("set -gx {} {}", key, shell_quote(&value)),
+            (EnvShell::Fish, None) => format!("set -e {}", key),
+        };
+        println!("{};", line);
+    }
+    Ok(())
+}
+
+/// Whether `name` is a portable environment variable name.
+fn is_env_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Starts a GUI editor on `path` in the background.
+fn launch_app(app: &str, path: &str, config: &Config) -> Result<()> {
+    let command = config.app(app)?.replace("{path}", &shell_quote(path));
+    Command::new("sh")
+        .args(["-c", &command])
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::null())
+        .spawn()
+        .map_err(|e| messages::RUN_FAILED.format(&[&command, &e]))?;
+    report(&format!("opened {} in {}", tilde_path(path), app), config);
+    Ok(())
+}
+
+fn handle_kill_command(json: bool, config: &Config) -> Result<()> {
+    let sessions = mux().list_sessions()?;
+    if sessions.is_empty() {
+        eprintln!("{}", messages::NO_SESSIONS_TO_KILL.text());
+        return if json {
+            CommandResult::new("kill").print()
+        } else {
+            Ok(())
+        };
+    }
+
+    let mut state = State::load();
+    let session_names: Vec<String> = sessions.iter().map(|s| s.name.clone()).collect();
+
+    let selected_index = match Picker::pick(
+        &session_names,
+        &[],
+        "kill> ",
+        config.picker.quick_select,
+        &[],
+    ) {
+        Some((idx, _)) => idx,
+        None if json => return CommandResult::new("kill").print(),
+        None => return Ok(()),
+    };
+
+    let selected = &session_names[selected_index];
+    let targets = state.session_group(selected);
+    let outcome = kill_sessions(&targets, &mut state, config)?;
+    state.save()?;
+    if json {
+        outcome.result().print()
+    } else {
+        report(&outcome.message(), config);
+        Ok(())
+    }
+}
+
+/// Sessions killed by `kill_sessions`, and where the client went.
+struct KillOutcome {
+    killed: Vec<String>,
+    /// Sessions that could not be killed, with the error.
+    failed: Vec<(String, String)>,
+    switched_to: Option<String>,
+}
+
+impl KillOutcome {
+    fn message(&self) -> String {
+        let mut message = match self.killed.as_slice() {
+            [] => messages::KILLED_NOTHING.text().to_string(),
+            [single] => messages::KILLED.format(&[single]),
+            killed => messages::KILLED_SESSIONS.format(&[&killed.len(), &killed.join(", ")]),
+        };
+        if !self.failed.is_empty() {
+            let failed: Vec<&str> = self.failed.iter().map(|(name, _)| name.as_str()).collect();
+            message.push_str(&messages::AND_FAILED_TO_KILL.format(&[&failed.join(", ")]));
+        }
+        if let Some(session) = &self.switched_to {
+            message.push_str(&messages::AND_NOW_IN.format(&[session]));
+        }
+        message
+    }
+
+    fn result(self) -> CommandResult {
+        let mut result = CommandResult::new("kill");
+        result.targets = self.killed;
+        result.errors = failures(self.failed);
+        result
+    }
+}
+
+fn failures(failed: Vec<(String, String)>) -> Vec<String> {
+    failed
+        .into_iter()
+        .map(|(target, error)| format!("{}: {}", target, error))
+        .collect()
+}
+
+/// Kills `targets`, moving the client to the latest other session in history
+/// first if it is on one of them.
+fn kill_sessions(targets: &[String], state: &mut State, config: &Config) -> Result<KillOutcome> {
+    let current = mux().current_session().ok();
+    let previous = state
+        .history
+        .iter()
+        .rev()
+        .find(|e| !targets.contains(&e.session))
+        .map(|e| e.session.clone());
+
+    let (killed, failed) = kill_all(targets, state, config);
+
+    let mut switched_to = None;
+    if current.as_ref().is_some_and(|c| killed.contains(c)) {
+        if let Some(prev) = previous {
+            if mux().switch_client(&prev).is_ok() {
+                announce_switch(&prev, state, config);
+                switched_to = Some(prev);
+            }
+        }
+    }
+
+    state.forget_sessions(&killed);
+    Ok(KillOutcome {
+        killed,
+        failed,
+        switched_to,
+    })
+}
+
+/// Kills `targets`, `KILL_CONCURRENCY` at a time, and returns the sessions
+/// that were killed and those that weren't, with why. Failures are printed
+/// as they happen, and so are kills of several targets on a terminal. The
+/// kill hook runs for each target first, one at a time.
+fn kill_all(
+    targets: &[String],
+    state: &State,
+    config: &Config,
+) -> (Vec<String>, Vec<(String, String)>) {
+    for target in targets {
+        run_hook(Hook::Kill, target, state, config);
+    }
+    let progress = targets.len() > 1 && std::io::stderr().is_terminal();
+    let done = AtomicUsize::new(0);
+    // Errors are turned into strings: the boxed ones can't leave a thread.
+    let results = parallel_map(targets, KILL_CONCURRENCY, |target| {
+        let result = mux().kill_session(target).map_err(|e| e.to_string());
+        let done = done.fetch_add(1, Ordering::Relaxed) + 1;
+        match &result {
+            Ok(()) if progress => eprintln!(
+                "{}",
+                messages::KILLED_PROGRESS.format(&[&done, &targets.len(), target])
+            ),
+            Ok(()) => {}
+            Err(e) => eprintln!(
+                "{}",
+                messages::KILL_FAILED_PROGRESS.format(&[&done, &targets.len(), target, e])
+            ),
+        }
+        result
+    });
+
+    let mut killed = Vec::new();
+    let mut failed = Vec::new();
+    for (target, result) in targets.iter().zip(results) {
+        match result.unwrap_or_else(|| Err("kill thread panicked".to_string())) {
+            Ok(()) => killed.push(target.clone()),
+            Err(e) => failed.push((target.clone(), e)),
+        }
+    }
+    (killed, failed)
+}
+
+fn handle_group_command(session: Option<String>, config: &Config) -> Result<()> {
+    let target = match session {
+        Some(session) => session,
+        None => TmuxClient::current_session()?,
+    };
+    if !TmuxClient::has_session(&target)? {
+        return Err(messages::NO_SESSION_NAMED.format(&[&target]).into());
+    }
+
+    let mut state = State::load();
+    let live: Vec<String> = TmuxClient::list_sessions()?
+        .into_iter()
+        .map(|s| s.name)
+        .collect();
+    let dead: Vec<String> = state
+        .session_group(&target)
+        .into_iter()
+        .filter(|s| !live.contains(s))
+        .collect();
+    state.forget_sessions(&dead);
+
+    let leader = state.session_group(&target).remove(0);
+    let name = (2..100)
+        .map(|n| format!("{}-{}", leader, n))
+        .find(|name| !TmuxClient::has_session(name).unwrap_or(true))
+        .ok_or("No free session name")?;
+
+    TmuxClient::create_grouped_session(&name, &leader)?;
+
+    state
+        .groups
+        .entry(leader.clone())
+        .or_default()
+        .push(name.clone());
+    if let Some(path) = state.sessions.get(&leader).cloned() {
+        state.sessions.insert(name.clone(), path);
+    }
+    state.push_history(name.clone(), &config.history);
+    state.save()?;
+
+    let message = format!("switched to {} (grouped with {})", name, leader);
+    switch_reporting(&name, &message, &state, config)
+}
+
+fn handle_back_command(config: &Config) -> Result<()> {
+    let mut state = State::load();
+
+    if let Some(previous) = state.previous_session() {
+        let previous = previous.to_string();
+        mux().switch_client(&previous)?;
+
+        report(&format!("back to {}", previous), config);
+        announce_switch(&previous, &state, config);
+        state.push_history(previous, &config.history);
+        state.save()?;
+    } else {
+        eprintln!("{}", messages::NO_PREVIOUS_SESSION.text());
+    }
+
+    Ok(())
+}
+
+/// Resumes the most recent session in history, e.g. from a fresh terminal or
+/// an SSH login. A session that has died is recreated from its project.
+fn handle_attach_last_command(config: &Config) -> Result<()> {
+    let mut state = State::load();
+    let last = state
+        .history
+        .last()
+        .map(|e| e.session.clone())
+        .ok_or(messages::NO_SESSION_IN_HISTORY.text())?;
+
+    let (session_name, created) = if mux().has_session(&last)? {
+        state.push_history(last.clone(), &config.history);
+        (last, false)
+    } else {
+        let path = state
+            .sessions
+            .get(&last)
+            .cloned()
+            .ok_or_else(|| format!("Session '{}' is gone and its project is unknown", last))?;
+        let project = state
+            .project_for_path(&path)
+            .filter(|p| Path::new(&p.path).is_dir())
+            .ok_or_else(|| {
+                format!(
+                    "Project of session '{}' no longer exists: {}",
+                    last,
+                    tilde_path(&path)
+                )
+            })?;
+        open_project(&project, &mut state, config)?
+    };
+
+    state.save()?;
+    switch_to(&session_name, created, &state, config)
+}
+
+/// Kills detached sessions idle for at least `idle_hours`, never the current
+/// one. With `interactive`, the candidates are shown as a checklist first.
+fn handle_gc_command(
+    idle_hours: u64,
+    interactive: bool,
+    dry_run: bool,
+    json: bool,
+    config: &Config,
+) -> Result<()> {
+    let action = if dry_run { "gc-dry-run" } else { "gc" };
+    let current = if TmuxClient::is_in_tmux() {
+        TmuxClient::current_session().ok()
+    } else {
+        None
+    };
+    let now = current_timestamp();
+    let threshold = idle_hours.saturating_mul(3600) as i64;
+    let mut state = State::load();
+
+    // A session group shares its windows, so one attached member keeps the
+    // whole group alive.
+    let activity = TmuxClient::session_activity()?;
+    let in_use: HashSet<&str> = activity
+        .iter()
+        .filter(|s| s.attached)
+        .map(|s| s.name.as_str())
+        .chain(current.as_deref())
+        .collect();
+    let live: HashSet<&str> = activity.iter().map(|s| s.name.as_str()).collect();
+    let candidates: Vec<&SessionActivity> = activity
+        .iter()
+        .filter(|s| now - s.activity >= threshold)
+        .filter(|s| {
+            !state
+                .session_group(&s.name)
+                .iter()
+                .any(|name| in_use.contains(name.as_str()))
+        })
+        .collect();
+    if candidates.is_empty() {
+        eprintln!("{}", messages::NO_IDLE_SESSIONS.format(&[&idle_hours]));
+        return if json {
+            CommandResult::new(action).print()
+        } else {
+            Ok(())
+        };
+    }
+
+    let names: Vec<String> = candidates.iter().map(|s| s.name.clone()).collect();
+    let details: Vec<String> = candidates
+        .iter()
+        .map(|s| {
+            let mut commands: Vec<String> = TmuxClient::list_panes(&s.name)
+                .unwrap_or_default()
+                .into_iter()
+                .filter(|p| !p.dead && !SHELL_COMMANDS.contains(&p.command.as_str()))
+                .map(|p| p.command)
+                .collect();
+            commands.sort();
+            commands.dedup();
+
+            let mut detail = format!(
+                "active {}, {} window{}",
+                format_age((now - s.activity).max(0)),
+                s.windows,
+                if s.windows == 1 { "" } else { "s" }
+            );
+            if !commands.is_empty() {
+                detail.push_str(&format!(", running {}", commands.join(", ")));
+            }
+            detail
+        })
+        .collect();
+
+    if dry_run && json {
+        let mut result = CommandResult::new(action);
+        result.targets = names;
+        return result.print();
+    }
+    if dry_run {
+        for (name, detail) in names.iter().zip(&details) {
+            println!("{}  {}", name, detail);
+        }
+        return Ok(());
+    }
+
+    let targets: Vec<String> = if interactive {
+        Picker::pick_many(&names, &details, "gc> ")
+            .unwrap_or_default()
+            .into_iter()
+            .map(|i| names[i].clone())
+            .collect()
+    } else {
+        names
+    };
+    if targets.is_empty() {
+        return if json {
+            CommandResult::new(action).print()
+        } else {
+            Ok(())
+        };
+    }
+
+    let mut expanded: Vec<String> = Vec::new();
+    for target in &targets {
+        for name in state.session_group(target) {
+            if live.contains(name.as_str()) && !expanded.contains(&name) {
+                expanded.push(name);
+            }
+        }
+    }
+    let (killed, failed) = kill_all(&expanded, &state, config);
+    state.forget_sessions(&killed);
+    state.save()?;
+
+    if json {
+        let mut result = CommandResult::new(action);
+        result.targets = killed;
+        result.errors = failures(failed);
+        return result.print();
+    }
+    let failed: Vec<String> = failed.into_iter().map(|(name, _)| name).collect();
+    let mut message = messages::KILLED_IDLE.format(&[&killed.len(), &killed.join(", ")]);
+    if !failed.is_empty() {
+        message.push_str(&messages::AND_FAILED_TO_KILL.format(&[&failed.join(", ")]));
+    }
+    report(&message, config);
+    Ok(())
+}
+
+/// Keeps the live sessions opened from projects within
+/// `session.max_sessions`. Over the limit, the least recently attached ones
+/// are saved as `evicted-<name>` snapshots and killed with their group;
+/// attached sessions, pinned projects' sessions and `keep` are never picked,
+/// so the limit can be exceeded when nothing else is left.
+fn enforce_session_limit(keep: &str, state: &mut State, config: &Config) -> Result<()> {
+    let Some(limit) = config.session.max_sessions else {
+        return Ok(());
+    };
+    let activity = TmuxClient::session_activity()?;
+    let members: HashSet<&String> = state.groups.values().flatten().collect();
+    let managed: Vec<&SessionActivity> = activity
+        .iter()
+        .filter(|s| state.sessions.contains_key(&s.name) && !members.contains(&s.name))
+        .collect();
+    let excess = managed.len().saturating_sub(limit);
+    if excess == 0 {
+        return Ok(());
+    }
+
+    let attached: HashSet<&str> = activity
+        .iter()
+        .filter(|s| s.attached)
+        .map(|s| s.name.as_str())
+        .collect();
+    let pinned: HashSet<&str> = state.pinned.iter().map(|p| p.path.as_str()).collect();
+    let mut candidates: Vec<&SessionActivity> = managed
+        .into_iter()
+        .filter(|s| s.name != keep)
+        .filter(|s| !pinned.contains(state.sessions[&s.name].as_str()))
+        .filter(|s| {
+            !state
+                .session_group(&s.name)
+                .iter()
+                .any(|name| attached.contains(name.as_str()))
+        })
+        .collect();
+    // Sessions no client ever attached to are judged by their activity.
+    candidates.sort_by_key(|s| {
+        if s.last_attached > 0 {
+            s.last_attached
+        } else {
+            s.activity
+        }
+    });
+    candidates.truncate(excess);
+    if candidates.is_empty() {
+        return Ok(());
+    }
+
+    let windows = TmuxClient::list_all_windows()?;
+    let panes = TmuxClient::list_all_panes()?;
+    let now = current_timestamp();
+    let mut evicted = Vec::new();
+    for session in candidates {
+        if let Some(snapshot) = snapshot_session(&session.name, &windows, &panes, state) {
+            state.snapshots.insert(
+                format!("{}{}", EVICTED_SNAPSHOT_PREFIX, session.name),
+                Snapshot {
+                    saved_at: now,
+                    sessions: vec![snapshot],
+                },
+            );
+        }
+        let group = state.session_group(&session.name);
+        for name in &group {
+            run_hook(Hook::Kill, name, state, config);
+        }
+        let killed: Vec<String> = group
+            .into_iter()
+            .filter(|name| TmuxClient::kill_session(name).is_ok())
+            .collect();
+        if killed.contains(&session.name) {
+            evicted.push(session.name.clone());
+        }
+        state.forget_sessions(&killed);
+    }
+    if evicted.is_empty() {
+        return Ok(());
+    }
+
+    let message = match evicted.as_slice() {
+        [session] => {
+            messages::EVICTED.format(&[session, &limit, &EVICTED_SNAPSHOT_PREFIX, session])
+        }
+        _ => messages::EVICTED_SEVERAL.format(&[
+            &evicted.join(", "),
+            &limit,
+            &EVICTED_SNAPSHOT_PREFIX,
+        ]),
+    };
+    if notifications_enabled() && TmuxClient::is_in_tmux() {
+        notify(&message);
+    } else {
+        eprintln!("{}", message);
+    }
+    Ok(())
+}
+
+fn handle_refresh_command(json: bool, config: &Config) -> Result<()> {
+    let mut state = State::load();
+    let refresh = state.refresh_cache(config);
+    if json {
+        let mut result = CommandResult::new("refresh");
+        result.targets = refresh
+            .new_projects
+            .iter()
+            .map(|project| project.path.clone())
+            .collect();
+        result.errors = refresh
+            .errors
+            .iter()
+            .map(|error| format!("{}: {}", error.path, error.message))
+            .collect();
+        for org in &config.orgs {
+            if let Err(e) = state.org_repos(org, true) {
+                result.errors.push(format!("{}: {}", org.name, e));
+            }
+        }
+        state.save()?;
+        return result.print();
+    }
+    println!(
+        "{}",
+        messages::CACHE_REFRESHED.format(&[&state.cache.projects.len()])
+    );
+    for project in &refresh.new_projects {
+        println!("  new: {}", project.label(&config.display.project));
+    }
+    if !refresh.errors.is_empty() {
+        eprintln!(
+            "{}",
+            messages::UNREADABLE_PATHS.format(&[&refresh.errors.len()])
+        );
+        for error in &refresh.errors {
+            eprintln!("  {}: {}", error.path, error.message);
+        }
+    }
+
+    for org in &config.orgs {
+        match state.org_repos(org, true) {
+            Ok(repos) => println!(
+                "{}",
+                messages::ORG_REPOSITORIES.format(&[&org.name, &repos.len()])
+            ),
+            Err(e) => messages::warn(messages::LIST_REPOSITORIES_FAILED.format(&[&org.name, &e])),
+        }
+    }
+    state.save()?;
+    Ok(())
+}
+
+/// Picks a stable color for a category so it looks the same across sessions
+/// and invocations.
+fn category_color(category: &str) -> &'static str {
+    let hash = category.bytes().fold(0xcbf29ce484222325u64, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    });
+    CATEGORY_COLORS[(hash % CATEGORY_COLORS.len() as u64) as usize]
+}
+
+fn update_status_options(session: &str, project: &ProjectInfo) -> Result<()> {
+    TmuxClient::set_session_option(session, CATEGORY_OPTION, &project.category)?;
+    TmuxClient::set_session_option(
+        session,
+        CATEGORY_COLOR_OPTION,
+        category_color(&project.category),
+    )?;
+    Ok(())
+}
+
+/// Creates the project under the first workspace directory (or
+/// `--workspace`); roots that are path lists are skipped.
+fn handle_new_command(query: &str, template: Option<&str>, config: &Config) -> Result<()> {
+    let (category, name) = query
+        .split_once('/')
+        .filter(|(category, name)| valid_path_component(category) && valid_path_component(name))
+        .ok_or_else(|| format!("Expected category/name, got '{}'", query))?;
+
+    let root = config
+        .workspaces
+        .iter()
+        .find(|root| !root.is_list())
+        .ok_or("No workspace directory configured")?;
+    let category_dir = Path::new(&root.path).join(category);
+    let path = category_dir.join(name);
+    if path.exists() {
+        return Err(messages::ALREADY_EXISTS.format(&[&path.display()]).into());
+    }
+
+    let template = match template {
+        Some(template) => Some(config.template(template)?),
+        None => None,
+    };
+
+    fs::create_dir_all(&category_dir)?;
+    match template {
+        Some(ProjectTemplate::Command(command)) => {
+            let command = command
+                .replace("{name}", name)
+                .replace("{category}", category)
+                .replace("{path}", &path.to_string_lossy());
+            let status = Command::new("sh")
+                .args(["-c", &command])
+                .current_dir(&category_dir)
+                .status()?;
+            if !status.success() {
+                return Err(format!("Template command failed: {}", command).into());
+            }
+            if !path.is_dir() {
+                return Err(format!(
+                    "Template command did not create {}: {}",
+                    path.display(),
+                    command
+                )
+                .into());
+            }
+        }
+        Some(ProjectTemplate::Copy { copy }) => {
+            copy_dir(Path::new(&shellexpand::tilde(&copy).to_string()), &path)?;
+        }
+        None => fs::create_dir(&path)?,
+    }
+
+    let project = ProjectInfo {
+        id: project_id(&path),
+        path: path.to_string_lossy().to_string(),
+        category: category.to_string(),
+        name: name.to_string(),
+        root: root.alias.clone(),
+        git: None,
+    };
+
+    let mut state = State::load();
+    state.add_project(project.clone());
+    let (session_name, _) = open_project(&project, &mut state, config)?;
+    state.save()?;
+
+    let message = format!(
+        "created {} at {}, switched to {}",
+        project.display_name(),
+        tilde_path(&project.path),
+        session_name
+    );
+    switch_reporting(&session_name, &message, &state, config)
+}
+
+/// Clones `url` into `category/<repo>` under the first workspace directory
+/// (or `--workspace`) and opens it. An existing clone is opened as is.
+fn handle_clone_command(url: &str, category: Option<&str>, config: &Config) -> Result<()> {
+    let (owner, name) = org::url_owner_and_name(url)
+        .ok_or_else(|| format!("Can't tell the repository name from '{}'", url))?;
+    let category = category.unwrap_or(&owner);
+    if !valid_path_component(category) || !valid_path_component(&name) {
+        return Err(format!("Can't clone '{}' into {}/{}", url, category, name).into());
+    }
+
+    let root = config
+        .workspaces
+        .iter()
+        .find(|root| !root.is_list())
+        .ok_or("No workspace directory configured")?;
+    let path = Path::new(&root.path).join(category).join(&name);
+    let cloned = !path.exists();
+    if cloned {
+        fs::create_dir_all(Path::new(&root.path).join(category))?;
+        org::clone_repo(
+            &RemoteRepo {
+                name: name.clone(),
+                clone_url: url.to_string(),
+            },
+            &path,
+        )?;
+    }
+
+    // The ID comes from the canonical path, so it waits for the clone.
+    let project = ProjectInfo {
+        id: project_id(&path),
+        path: path.to_string_lossy().to_string(),
+        category: category.to_string(),
+        name,
+        root: root.alias.clone(),
+        git: None,
+    };
+
+    let mut state = State::load();
+    state.add_project(project.clone());
+    let (session_name, _) = open_project(&project, &mut state, config)?;
+    state.save()?;
+
+    let message = format!(
+        "{} {} at {}, switched to {}",
+        if cloned { "cloned" } else { "already cloned" },
+        project.display_name(),
+        tilde_path(&project.path),
+        session_name
+    );
+    switch_reporting(&session_name, &message, &state, config)
+}
+
+/// Category directories of each root as `(root, category, path)`, including
+/// empty ones.
+fn list_categories(config: &Config) -> Result<Vec<(&WorkspaceRoot, String, PathBuf)>> {
+    let mut categories = Vec::new();
+    for root in config.workspaces.iter().filter(|root| !root.is_list()) {
+        let scan = config.scan.for_root(Path::new(&root.path));
+        let entries = match fs::read_dir(&root.path) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(messages::READ_FAILED.format(&[&root.path, &e]).into()),
+        };
+        for entry in entries.filter_map(|e| e.ok()) {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if entry.path().is_dir() && !scan.is_excluded(&name) {
+                categories.push((root, name, entry.path()));
+            }
+        }
+    }
+    categories.sort_by(|a, b| (&a.1, &a.0.alias).cmp(&(&b.1, &b.0.alias)));
+    Ok(categories)
+}
+
+fn handle_categories_command(config: &Config) -> Result<()> {
+    let mut state = State::load();
+    state.ensure_cache_valid(config)?;
+    state.save()?;
+
+    let categories = list_categories(config)?;
+    if categories.is_empty() {
+        println!("{}", messages::NO_CATEGORIES.text());
+        return Ok(());
+    }
+
+    let several_roots = config.workspaces.len() > 1;
+    let labels: Vec<String> = categories
+        .iter()
+        .map(|(root, name, _)| {
+            if several_roots {
+                format!("{}/{}", root.alias, name)
+            } else {
+                name.clone()
+            }
+        })
+        .collect();
+    let width = labels.iter().map(|l| l.len()).max().unwrap_or(0);
+
+    // Projects found by markers can sit deeper, with the category dir as
+    // the start of their category.
+    for ((root, name, path), label) in categories.iter().zip(&labels) {
+        let count = state
+            .cache
+            .projects
+            .iter()
+            .filter(|p| {
+                p.root == root.alias
+                    && (&p.category == name || Path::new(&p.path).starts_with(path))
+            })
+            .count();
+        println!("{:<width$}  {}", label, count, width = width);
+    }
+    Ok(())
+}
+
+fn handle_category_create_command(name: &str, config: &Config) -> Result<()> {
+    if !valid_path_component(name) || config.scan.is_excluded(name) {
+        return Err(format!("'{}' is not a usable category name", name).into());
+    }
+    let root = config
+        .workspaces
+        .iter()
+        .find(|root| !root.is_list())
+        .ok_or("No workspace directory configured")?;
+    let path = Path::new(&root.path).join(name);
+    if path.exists() {
+        return Err(messages::ALREADY_EXISTS.format(&[&path.display()]).into());
+    }
+
+    fs::create_dir_all(&path)?;
+    report(
+        &format!(
+            "created category {} at {}",
+            name,
+            tilde_path(&path.to_string_lossy())
+        ),
+        config,
+    );
+    Ok(())
+}
+
+/// Cached projects grouped by category, in cache order. A category is named
+/// `root/category` when another root has one of the same name.
+fn category_profiles(projects: &[ProjectInfo]) -> Vec<(String, Vec<&ProjectInfo>)> {
+    let mut profiles: Vec<((&str, &str), Vec<&ProjectInfo>)> = Vec::new();
+    for project in projects {
+        let key = (project.root.as_str(), project.category.as_str());
+        match profiles.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, members)) => members.push(project),
+            None => profiles.push((key, vec![project])),
+        }
+    }
+    profiles
+        .iter()
+        .map(|((root, category), members)| {
+            let shared = profiles
+                .iter()
+                .any(|((r, c), _)| c == category && r != root);
+            let name = if shared {
+                format!("{}/{}", root, category)
+            } else {
+                category.to_string()
+            };
+            (name, members.clone())
+        })
+        .collect()
+}
+
+/// The latest of the project's last commit, its last open through ws and
+/// the last activity in a session opened from it.
+fn project_activity(project: &ProjectInfo, state: &State, sessions: &[SessionInfo]) -> i64 {
+    let commit = git_status::last_commit_time(&project.path).unwrap_or(0);
+    let opened = state.opens.get(&project.id).map_or(0, |o| o.last_opened);
+    let session = sessions
+        .iter()
+        .filter(|s| state.sessions.get(&s.name) == Some(&project.path))
+        .map(|s| s.last_active)
+        .max()
+        .unwrap_or(0);
+    commit.max(opened).max(session)
+}
+
+/// `project_activity` of each of `projects` by path, `ACTIVITY_CONCURRENCY`
+/// at a time.
+fn projects_activity<'a>(
+    projects: &[&'a ProjectInfo],
+    state: &State,
+    sessions: &[SessionInfo],
+) -> HashMap<&'a str, i64> {
+    let activity = parallel_map(projects, ACTIVITY_CONCURRENCY, |project| {
+        project_activity(project, state, sessions)
+    });
+    projects
+        .iter()
+        .zip(activity)
+        .map(|(project, at)| (project.path.as_str(), at.unwrap_or(0)))
+        .collect()
+}
+
+/// The projects among `members` active since `cutoff`, most recent first,
+/// going by their `projects_activity`.
+fn active_projects<'a>(
+    members: &[&'a ProjectInfo],
+    cutoff: i64,
+    activity: &HashMap<&str, i64>,
+) -> Vec<&'a ProjectInfo> {
+    let mut active: Vec<(i64, &ProjectInfo)> = members
+        .iter()
+        .map(|project| {
+            let at = activity.get(project.path.as_str()).copied();
+            (at.unwrap_or(0), *project)
+        })
+        .filter(|(at, _)| *at >= cutoff)
+        .collect();
+    active.sort_by_key(|(at, _)| std::cmp::Reverse(*at));
+    active.into_iter().map(|(_, project)| project).collect()
+}
+
+fn handle_profile_list_command(days: u64, config: &Config) -> Result<()> {
+    let mut state = State::load();
+    state.ensure_cache_valid(config)?;
+    state.save()?;
+    let sessions = mux().list_sessions().unwrap_or_default();
+    let cutoff = current_timestamp() - days as i64 * 24 * 3600;
+
+    let profiles = category_profiles(&state.cache.projects);
+    if profiles.is_empty() {
+        println!("{}", messages::NO_CATEGORIES.text());
+        return Ok(());
+    }
+    let width = profiles
+        .iter()
+        .map(|(name, _)| name.len())
+        .max()
+        .unwrap_or(0);
+    let projects: Vec<&ProjectInfo> = profiles
+        .iter()
+        .flat_map(|(_, members)| members.iter().copied())
+        .collect();
+    let activity = projects_activity(&projects, &state, &sessions);
+    for (name, members) in &profiles {
+        let active = active_projects(members, cutoff, &activity);
+        println!(
+            "{:<width$}  {} of {} active",
+            name,
+            active.len(),
+            members.len(),
+            width = width
+        );
+    }
+    Ok(())
+}
+
+/// Opens a session for every project of the category active in the last
+/// `days`, and switches to the most recently active one.
+fn handle_profile_open_command(query: &str, days: u64, config: &Config) -> Result<()> {
+    let mut state = State::load();
+    state.ensure_cache_valid(config)?;
+    let sessions = mux().list_sessions().unwrap_or_default();
+    let cutoff = current_timestamp() - days as i64 * 24 * 3600;
+
+    let profiles = category_profiles(&state.cache.projects);
+    let matches: Vec<&(String, Vec<&ProjectInfo>)> = profiles
+        .iter()
+        .filter(|(name, members)| {
+            name == query
+                || members[0].category == query
+                || format!("{}/{}", members[0].root, members[0].category) == query
+        })
+        .collect();
+    let members = match matches.as_slice() {
+        [] => return Err(messages::NO_CATEGORY_NAMED.format(&[&query]).into()),
+        [(_, members)] => members,
+        _ => {
+            let names: Vec<&str> = matches.iter().map(|(name, _)| name.as_str()).collect();
+            return Err(messages::AMBIGUOUS_PROJECT
+                .format(&[&query, &names.join(", ")])
+                .into());
+        }
+    };
+    let activity = projects_activity(members, &state, &sessions);
+    let items: Vec<SelectableItem> = active_projects(members, cutoff, &activity)
+        .into_iter()
+        .map(|project| SelectableItem::Project(project.clone()))
+        .collect();
+    if items.is_empty() {
+        state.save()?;
+        return Err(format!(
+            "No project in {} was active in the last {} days",
+            query, days
+        )
+        .into());
+    }
+    BatchAction::OpenAll.run(items, &mut state, config)
+}
+
+fn handle_category_archive_command(query: &str, config: &Config) -> Result<()> {
+    let categories = list_categories(config)?;
+    let matches: Vec<_> = categories
+        .iter()
+        .filter(|(root, name, _)| name == query || format!("{}/{}", root.alias, name) == query)
+        .collect();
+
+    let (root, name, path) = match matches.as_slice() {
+        [] => return Err(messages::NO_CATEGORY_NAMED.format(&[&query]).into()),
+        [single] => single,
+        _ => {
+            let names: Vec<String> = matches
+                .iter()
+                .map(|(root, name, _)| format!("{}/{}", root.alias, name))
+                .collect();
+            return Err(messages::AMBIGUOUS_PROJECT
+                .format(&[&query, &names.join(", ")])
+                .into());
+        }
+    };
+
+    let archive = Path::new(&root.path).join(ARCHIVE_DIR);
+    let destination = archive.join(name);
+    if destination.exists() {
+        return Err(messages::ALREADY_EXISTS
+            .format(&[&destination.display()])
+            .into());
+    }
+    let mut state = State::load();
+    let archived: Vec<ProjectInfo> = state
+        .cache
+        .projects
+        .iter()
+        .filter(|p| Path::new(&p.path).starts_with(path))
+        .cloned()
+        .collect();
+    let question = format!(
+        "Archive category {} with {} project{}?",
+        name,
+        archived.len(),
+        if archived.len() == 1 { "" } else { "s" }
+    );
+    if !confirm(&question)? {
+        return Ok(());
+    }
+    fs::create_dir_all(&archive)?;
+    fs::rename(path, &destination)?;
+
+    // Sessions opened from the category keep pointing at their projects.
+    for project in &archived {
+        if let Some(moved) = ProjectInfo::from_path(&destination.join(&project.name)) {
+            state.remap_project(project, &moved);
+        }
+    }
+    state.refresh_cache(config);
+    state.save()?;
+
+    report(
+        &format!(
+            "archived {} to {}",
+            name,
+            tilde_path(&destination.to_string_lossy())
+        ),
+        config,
+    );
+    Ok(())
+}
+
+fn handle_archive_command(query: &str, config: &Config) -> Result<()> {
+    let mut state = State::load();
+    state.ensure_cache_valid(config)?;
+    let project = state.find_project(query)?.clone();
+    let destination = archive_destination(&project, config)?;
+    let targets = project_sessions(&project, &state);
+    let question = if targets.is_empty() {
+        format!("Archive {}?", project.display_name())
+    } else {
+        format!(
+            "Archive {} and kill {}?",
+            project.display_name(),
+            targets.join(", ")
+        )
+    };
+    if !confirm(&question)? {
+        return state.save();
+    }
+    let message = archive_project(&project, &destination, &targets, &mut state, config)?;
+    state.save()?;
+    report(&message, config);
+    Ok(())
+}
+
+/// The workspace root whose directory `project` sits in, as
+/// `root/category/name`; only such projects can be moved around.
+fn workspace_root_of<'a>(project: &ProjectInfo, config: &'a Config) -> Result<&'a WorkspaceRoot> {
+    config
+        .workspaces
+        .iter()
+        .find(|root| root.alias == project.root && !root.is_list())
+        .filter(|root| {
+            Path::new(&project.path)
+                == Path::new(&root.path)
+                    .join(&project.category)
+                    .join(&project.name)
+        })
+        .ok_or_else(|| {
+            format!(
+                "{} is not in a workspace directory",
+                tilde_path(&project.path)
+            )
+            .into()
+        })
+}
+
+/// Where archiving `project` moves it: its category in the root's archive.
+fn archive_destination(project: &ProjectInfo, config: &Config) -> Result<PathBuf> {
+    let root = workspace_root_of(project, config)?;
+    let destination = Path::new(&root.path)
+        .join(ARCHIVE_DIR)
+        .join(&project.category)
+        .join(&project.name);
+    if destination.exists() {
+        return Err(messages::ALREADY_EXISTS
+            .format(&[&destination.display()])
+            .into());
+    }
+    Ok(destination)
+}
+
+/// Live sessions opened from `project`, with their groups.
+fn project_sessions(project: &ProjectInfo, state: &State) -> Vec<String> {
+    let mut targets = Vec::new();
+    let live = mux().list_sessions().unwrap_or_default();
+    for session in live
+        .iter()
+        .filter(|s| state.sessions.get(&s.name) == Some(&project.path))
+    {
+        for name in state.session_group(&session.name) {
+            if !targets.contains(&name) {
+                targets.push(name);
+            }
+        }
+    }
+    targets
+}
+
+/// Kills `targets` and moves `project` to `destination` in its root's
+/// archive, returning the summary line.
+fn archive_project(
+    project: &ProjectInfo,
+    destination: &Path,
+    targets: &[String],
+    state: &mut State,
+    config: &Config,
+) -> Result<String> {
+    let killed = if targets.is_empty() {
+        None
+    } else {
+        Some(kill_sessions(targets, state, config)?.message())
+    };
+    move_project(project, destination, state, config)?;
+
+    let mut message = format!(
+        "archived {} to {}",
+        project.display_name(),
+        tilde_path(&destination.to_string_lossy())
+    );
+    if let Some(killed) = killed {
+        message = format!("{}; {}", message, killed);
+    }
+    Ok(message)
+}
+
+/// Moves `project`'s directory to `destination` and points state at the
+/// new location, so sessions, opens and pins follow it.
+fn move_project(
+    project: &ProjectInfo,
+    destination: &Path,
+    state: &mut State,
+    config: &Config,
+) -> Result<()> {
+    fs::create_dir_all(destination.parent().unwrap_or(destination))?;
+    fs::rename(&project.path, destination)?;
+    if let Some(mut moved) = ProjectInfo::from_path(destination) {
+        moved.root = project.root.clone();
+        state.remap_project(project, &moved);
+    }
+    state.refresh_cache(config);
+    Ok(())
+}
+
+fn handle_unarchive_command(query: &str, config: &Config) -> Result<()> {
+    let archived = archived_projects(config);
+    let project = find_project_in(&archived, query)?;
+    let root = config
+        .workspaces
+        .iter()
+        .find(|root| root.alias == project.root)
+        .ok_or_else(|| format!("No workspace root '{}'", project.root))?;
+    let destination = Path::new(&root.path)
+        .join(&project.category)
+        .join(&project.name);
+    if destination.exists() {
+        return Err(messages::ALREADY_EXISTS
+            .format(&[&destination.display()])
+            .into());
+    }
+
+    fs::create_dir_all(destination.parent().unwrap_or(&destination))?;
+    fs::rename(&project.path, &destination)?;
+
+    let mut state = State::load();
+    if let Some(mut restored) = ProjectInfo::from_path(&destination) {
+        restored.root = project.root.clone();
+        state.remap_project(project, &restored);
+    }
+    state.refresh_cache(config);
+    state.save()?;
+
+    report(
+        &format!(
+            "restored {} to {}",
+            project.display_name(),
+            tilde_path(&destination.to_string_lossy())
+        ),
+        config,
+    );
+    Ok(())
+}
+
+fn valid_path_component(component: &str) -> bool {
+    !component.is_empty() && component != "." && component != ".." && !component.contains('/')
+}
+
+fn copy_dir(source: &Path, destination: &Path) -> Result<()> {
+    if !source.is_dir() {
+        return Err(format!("Template directory {} does not exist", source.display()).into());
+    }
+
+    for entry in WalkDir::new(source) {
+        let entry = entry?;
+        let target = destination.join(entry.path().strip_prefix(source)?);
+        if entry.file_type().is_dir() {
+            fs::create_dir_all(&target)?;
+        } else {
+            fs::copy(entry.path(), &target)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn handle_snapshot_save_command(name: &str, config: &Config) -> Result<()> {
+    let mut state = State::load();
+    let sessions = capture_sessions(&state)?;
+    if sessions.is_empty() {
+        return Err(messages::NO_SESSIONS_TO_SAVE.text().into());
+    }
+
+    let count = sessions.len();
+    let replaced = state
+        .snapshots
+        .insert(
+            name.to_string(),
+            Snapshot {
+                saved_at: current_timestamp(),
+                sessions,
+            },
+        )
+        .is_some();
+    state.save()?;
+
+    report(
+        &format!(
+            "saved {} session(s) as {}{}",
+            count,
+            name,
+            if replaced { " (replaced)" } else { "" }
+        ),
+        config,
+    );
+    Ok(())
+}
+
+/// Every live session as `ws snapshot save` records it, most recently
+/// active first.
+fn capture_sessions(state: &State) -> Result<Vec<SnapshotSession>> {
+    let mut activity = TmuxClient::session_activity()?;
+    activity.sort_by_key(|s| std::cmp::Reverse(s.activity));
+    let windows = TmuxClient::list_all_windows()?;
+    let panes = TmuxClient::list_all_panes()?;
+
+    // Group members share their leader's windows; the leader is enough.
+    let members: Vec<&String> = state.groups.values().flatten().collect();
+    Ok(activity
+        .iter()
+        .filter(|s| !members.contains(&&s.name))
+        .filter_map(|s| snapshot_session(&s.name, &windows, &panes, state))
+        .collect())
+}
+
+/// The session's windows as `ws snapshot save` records them; `None` if it
+/// has none.
+fn snapshot_session(
+    name: &str,
+    windows: &[WindowInfo],
+    panes: &[PaneInfo],
+    state: &State,
+) -> Option<SnapshotSession> {
+    let windows: Vec<&WindowInfo> = windows.iter().filter(|w| w.session == name).collect();
+    let first = windows.first()?;
+    let project = state
+        .sessions
+        .get(name)
+        .map(|path| project_id(Path::new(path)));
+    Some(SnapshotSession {
+        name: name.to_string(),
+        path: first.session_path.clone(),
+        project,
+        active: windows
+            .iter()
+            .find(|w| w.active)
+            .map_or(first.index, |w| w.index),
+        windows: windows
+            .iter()
+            .map(|w| {
+                let mut window_panes = panes
+                    .iter()
+                    .filter(|p| p.session == name && p.window == w.index)
+                    .map(|p| SnapshotPane {
+                        path: p.path.clone(),
+                        command: p.command.clone(),
+                    });
+                // The window's own values are its first pane's, the one restoring
+                // splits the others from; panes are listed in index order.
+                let first = window_panes.next().unwrap_or_else(|| SnapshotPane {
+                    path: w.path.clone(),
+                    command: w.command.clone(),
+                });
+                let panes: Vec<SnapshotPane> = window_panes.collect();
+                SnapshotWindow {
+                    index: w.index,
+                    name: w.name.clone(),
+                    path: first.path,
+                    command: first.command,
+                    layout: (!panes.is_empty()).then(|| w.layout.clone()),
+                    panes,
+                }
+            })
+            .collect(),
+    })
+}
+
+/// Keeps the automatic snapshot current for `ws restore`. It is left alone
+/// while no sessions are running, so that it survives the tmux server
+/// going away at a reboot, and after a restart of the server until one of
+/// its sessions is back, so that the first new session doesn't replace it
+/// before it could be restored.
+fn update_auto_snapshot() -> Result<()> {
+    let mut state = State::load();
+    let sessions = capture_sessions(&state)?;
+    if sessions.is_empty() {
+        return Ok(());
+    }
+    if let Some(saved) = state.snapshots.get(AUTO_SNAPSHOT) {
+        let restarted = TmuxClient::server_start_time().is_none_or(|start| start > saved.saved_at);
+        let restored = saved
+            .sessions
+            .iter()
+            .any(|old| sessions.iter().any(|live| live.name == old.name));
+        if restarted && !restored {
+            return Ok(());
+        }
+    }
+    state.snapshots.insert(
+        AUTO_SNAPSHOT.to_string(),
+        Snapshot {
+            saved_at: current_timestamp(),
+            sessions,
+        },
+    );
+    state.save()
+}
+
+/// Recreates the snapshot's sessions that aren't running, oldest first so
+/// history keeps their order, and switches to the most recent one.
+fn handle_snapshot_restore_command(name: &str, config: &Config) -> Result<()> {
+    let mut state = State::load();
+    let snapshot = state
+        .snapshots
+        .get(name)
+        .cloned()
+        .ok_or_else(|| messages::NO_SNAPSHOT_NAMED.format(&[&name]))?;
+
+    let mut restored = Vec::new();
+    let mut running = Vec::new();
+    for session in snapshot.sessions.iter().rev() {
+        if mux().has_session(&session.name)? {
+            running.push(session.name.clone());
+            continue;
+        }
+
+        let project = session
+            .project
+            .as_ref()
+            .and_then(|id| state.cache.projects.iter().find(|p| &p.id == id).cloned());
+        let env = match &project {
+            Some(project) => session_env(project, &config.session)?,
+            None => Vec::new(),
+        };
+        let layout = Layout {
+            name: format!("snapshot {}", name),
+            windows: session
+                .windows
+                .iter()
+                .map(|w| LayoutWindow {
+                    index: w.index,
+                    name: w.name.clone(),
+                    role: None,
+                    command: w.command.clone(),
+                    path: Some(w.path.clone()),
+                    panes: w
+                        .panes
+                        .iter()
+                        .map(|p| PaneSpec {
+                            command: p.command.clone(),
+                            path: Some(p.path.clone()),
+                            horizontal: false,
+                        })
+                        .collect(),
+                    arrange: w.layout.clone(),
+                })
+                .collect(),
+            focus: session.active,
+        };
+        mux().create_session(&session.name, &session.path, &layout, &env)?;
+
+        if let Some(project) = &project {
+            state
+                .sessions
+                .insert(session.name.clone(), project.path.clone());
+            update_status_options(&session.name, project).ok();
+        }
+        run_hook(Hook::Create, &session.name, &state, config);
+        state.push_history(session.name.clone(), &config.history);
+        restored.push(session.name.clone());
+    }
+    state.save()?;
+
+    let mut message = format!("restored {} session(s) from {}", restored.len(), name);
+    if !running.is_empty() {
+        message.push_str(&format!(", already running: {}", running.join(", ")));
+    }
+    match snapshot.sessions.first() {
+        Some(latest) => switch_reporting(&latest.name, &message, &state, config),
+        None => {
+            report(&message, config);
+            Ok(())
+        }
+    }
+}
+
+fn handle_snapshot_list_command() -> Result<()> {
+    let state = State::load();
+    if state.snapshots.is_empty() {
+        eprintln!("{}", messages::NO_SNAPSHOTS.text());
+        return Ok(());
+    }
+    let now = current_timestamp();
+    for (name, snapshot) in &state.snapshots {
+        let sessions: Vec<&str> = snapshot.sessions.iter().map(|s| s.name.as_str()).collect();
+        println!(
+            "{}  {}  {}",
+            name,
+            format_age(now - snapshot.saved_at),
+            sessions.join(", ")
+        );
+    }
+    Ok(())
+}
+
+fn handle_pin_command(query: &str, config: &Config) -> Result<()> {
+    let mut state = State::load();
+    state.ensure_cache_valid(config)?;
+    let project = state.find_project(query)?.clone();
+    if state.is_pinned(&project) {
+        state.save()?;
+        return Err(messages::ALREADY_PINNED
+            .format(&[&project.display_name()])
+            .into());
+    }
+    let name = project.display_name();
+    state.pinned.push(project);
+    state.save()?;
+    report(&format!("pinned {}", name), config);
+    Ok(())
+}
+
+/// Unpins by the pinned entries themselves, so a project the scan no longer
+/// finds can still be unpinned.
+fn handle_unpin_command(query: &str, config: &Config) -> Result<()> {
+    let mut state = State::load();
+    let project = find_project_in(&state.pinned, query)?.clone();
+    state.pinned.retain(|p| p.path != project.path);
+    state.save()?;
+    report(&format!("unpinned {}", project.display_name()), config);
+    Ok(())
+}
+
+fn handle_snapshot_delete_command(name: &str, config: &Config) -> Result<()> {
+    let mut state = State::load();
+    if !state.snapshots.contains_key(name) {
+        return Err(messages::NO_SNAPSHOT_NAMED.format(&[&name]).into());
+    }
+    if !confirm(&format!("Delete snapshot {}?", name))? {
+        return Ok(());
+    }
+    state.snapshots.remove(name);
+    state.save()?;
+    report(&format!("deleted snapshot {}", name), config);
+    Ok(())
+}
+
+fn handle_layout_test_command(
+    name: &str,
+    project: Option<&str>,
+    wait: u64,
+    config: &Config,
+) -> Result<()> {
+    let layout = Layout::named(name, config)?;
+    let path = match project {
+        Some(query) => {
+            let mut state = State::load();
+            state.ensure_cache_valid(config)?;
+            state.find_project(query)?.path.clone()
+        }
+        None => std::env::current_dir()?.to_string_lossy().to_string(),
+    };
+
+    // A private server keeps the throwaway session out of the real session
+    // list; remain-on-exit keeps failed panes around so they can be inspected.
+    TmuxClient::use_socket(TmuxSocket::Name(format!(
+        "ws-layout-test-{}",
+        std::process::id()
+    )))?;
+    let session = "ws-layout-test";
+    TmuxClient::command()
+        .args(["new-session", "-d", "-s", "ws-layout-test-holder"])
+        .output_retrying()?;
+    TmuxClient::set_global_option("remain-on-exit", "on")?;
+
+    let result = TmuxClient::create_session(session, &path, &layout, &[]).and_then(|_| {
+        std::thread::sleep(std::time::Duration::from_secs(wait));
+        TmuxClient::list_panes(session)
+    });
+    TmuxClient::kill_server().ok();
+
+    let panes = result?;
+    let mut failures = 0;
+    for pane in &panes {
+        match (pane.dead, pane.exit_status) {
+            (false, _) => println!("ok      {}", pane.window),
+            (true, Some(0)) => println!("exited  {} (status 0)", pane.window),
+            (true, status) => {
+                failures += 1;
+                let status = status.map_or("unknown".to_string(), |s| s.to_string());
+                println!("failed  {} (status {})", pane.window, status);
+            }
+        }
+    }
+
+    if failures > 0 {
+        Err(format!(
+            "{} of {} panes failed in layout '{}'",
+            failures,
+            panes.len(),
+            name
+        )
+        .into())
+    } else {
+        println!("Layout '{}' ok: {} panes checked", name, panes.len());
+        Ok(())
+    }
+}
+
+fn handle_stats_command(commands: bool, enable: bool, disable: bool, reset: bool) -> Result<()> {
+    let mut state = State::load();
+
+    if enable || disable || reset {
+        if enable {
+            state.stats.enabled = true;
+        }
+        if disable {
+            state.stats.enabled = false;
+        }
+        if reset {
+            state.stats.commands.clear();
+        }
+        state.save()?;
+    }
 
-        let output = Skim::run_with(&options, Some(skim_items))?;
+    if !state.stats.enabled && state.stats.commands.is_empty() {
+        println!("Usage stats are disabled (enable with `ws stats --enable`)");
+        return Ok(());
+    }
 
-        if output.is_abort {
-            None
-        } else {
-            output.selected_items.first().and_then(|item| {
-                let selected_text = item.output().to_string();
-                items
-                    .iter()
-                    .position(|s| s == &selected_text)
-            })
+    if !commands {
+        let total: u64 = state.stats.commands.values().map(|c| c.count).sum();
+        println!(
+            "Usage stats {}: {} invocations recorded",
+            if state.stats.enabled {
+                "enabled"
+            } else {
+                "disabled"
+            },
+            total
+        );
+        return Ok(());
+    }
+
+    let now = current_timestamp();
+    let mut rows: Vec<(&String, &CommandStats)> = state.stats.commands.iter().collect();
+    rows.sort_by(|a, b| b.1.count.cmp(&a.1.count).then_with(|| a.0.cmp(b.0)));
+
+    println!(
+        "{:<12} {:>7} {:>9} {:>9}  last used",
+        "command", "count", "avg", "max"
+    );
+    for (name, stats) in rows {
+        println!(
+            "{:<12} {:>7} {:>7}ms {:>7}ms  {}",
+            name,
+            stats.count,
+            stats.total_ms / stats.count.max(1),
+            stats.max_ms,
+            format_age(now - stats.last_used)
+        );
+        // Averaged over the runs a phase happened in; a cancelled pick
+        // doesn't switch, for one.
+        for (phase, timing) in &stats.phases {
+            println!(
+                "  {:<18} {:>7}ms {:>7}ms",
+                phase,
+                timing.total_ms / timing.count.max(1),
+                timing.max_ms
+            );
         }
     }
+
+    Ok(())
 }
 
-fn handle_pick_command(workspace: &str) -> Result<()> {
+fn record_usage(command: &str, elapsed_ms: u64, phases: &[(&str, Duration)]) {
     let mut state = State::load();
-    state.ensure_cache_valid(workspace)?;
+    if state.stats.enabled {
+        state.stats.record(command, elapsed_ms, phases);
+        state.save().ok();
+    }
+}
 
-    let in_tmux = TmuxClient::is_in_tmux();
-    let sessions = if in_tmux {
-        TmuxClient::list_sessions().unwrap_or_default()
-    } else {
-        Vec::new()
-    };
+/// Prints the phases timed so far to stderr, if `--timings` asked for them.
+fn print_timings() {
+    if let Some(started) = TIMINGS.get() {
+        eprintln!("{}", timings::report(&timings::phases(), started.elapsed()));
+    }
+}
 
-    let mut selectable_items = Vec::new();
+fn handle_which_command(query: &str, config: &Config) -> Result<()> {
+    let mut state = State::load();
+    state.ensure_cache_valid(config)?;
+    let sessions = mux().list_sessions().unwrap_or_default();
 
-    for session in &sessions {
-        selectable_items.push(SelectableItem::Session(session.name.clone()));
-    }
+    println!("query:     {}", query);
+    match state.find_project(query) {
+        Ok(project) => {
+            let rule = if project.display_name() == query {
+                "exact category/name"
+            } else if project.qualified_name() == query {
+                "exact root/category/name"
+            } else {
+                "unique name"
+            };
+            println!("project:   {} ({})", project.display_name(), rule);
+            println!("path:      {}", project.path);
+            println!("id:        {}", project.id);
+
+            let names = naming::SessionNames::new(&state.cache.projects, &config.session);
+            let session_name = names.name(project)?;
+            let status = if !sessions.iter().any(|s| s.name == session_name) {
+                "not running, would be created"
+            } else if state.sessions.get(&session_name) == Some(&project.path) {
+                "running, opened from this project"
+            } else if state.sessions.contains_key(&session_name) {
+                "running, opened from another project"
+            } else {
+                "running, not opened by ws"
+            };
+            println!("session:   {} ({})", session_name, status);
+            let unsanitized = names.unsanitized(project);
+            let templated = naming::template_name(project, &config.session);
+            if unsanitized != templated {
+                println!(
+                    "           instead of '{}', which another project's name matches",
+                    templated
+                );
+            }
+            if session_name != unsanitized {
+                println!("           sanitized from '{}'", unsanitized);
+            }
+
+            for other in &state.cache.projects {
+                if other.path != project.path
+                    && names.name(other).is_ok_and(|name| name == session_name)
+                {
+                    println!(
+                        "collides:  {} uses the same session name",
+                        other.display_name()
+                    );
+                }
+            }
 
-    for project in &state.cache.projects {
-        selectable_items.push(SelectableItem::Project(project.clone()));
+            match Layout::for_project(project, config) {
+                Ok(layout) => println!("layout:    {}", layout.name),
+                Err(e) => println!("layout:    none ({})", e),
+            }
+        }
+        Err(e) => println!("project:   none ({})", e),
     }
 
-    let mut display_strings: Vec<String> = selectable_items
+    let matcher = SkimMatcherV2::default();
+    let mut candidates: Vec<(i64, String)> = sessions
         .iter()
-        .map(|item| item.to_display_string())
+        .map(|s| SelectableItem::Session(s.name.clone()))
+        .chain(
+            state
+                .cache
+                .projects
+                .iter()
+                .cloned()
+                .map(SelectableItem::Project),
+        )
+        .filter_map(|item| {
+            let display = item.to_display_string(&config.display.project);
+            matcher
+                .fuzzy_match(&display, query)
+                .map(|score| (score, display))
+        })
         .collect();
+    candidates.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
 
-    let separator_offset = if in_tmux && !sessions.is_empty() && !state.cache.projects.is_empty() {
-        display_strings.insert(sessions.len(), "---".to_string());
-        1
-    } else {
-        0
-    };
+    if !candidates.is_empty() {
+        println!("picker matches:");
+        for (score, display) in candidates.iter().take(WHICH_MAX_CANDIDATES) {
+            println!("  {:>5}  {}", score, display);
+        }
+    }
 
-    let selected_index = match Picker::pick(&display_strings, "> ") {
-        Some(idx) => idx,
-        None => return Ok(()),
-    };
+    Ok(())
+}
 
-    let adjusted_index = if separator_offset > 0 && selected_index >= sessions.len() {
-        selected_index - separator_offset
-    } else {
-        selected_index
-    };
+/// A row of `ws list --format json|tsv`: a project, or a live session.
+#[derive(Serialize)]
+struct ListedEntry<'a> {
+    /// `project` or `session`.
+    kind: &'static str,
+    name: &'a str,
+    /// The project's, also for sessions opened from one.
+    id: Option<&'a str>,
+    root: Option<&'a str>,
+    category: Option<&'a str>,
+    path: Option<&'a str>,
+    /// The session a project is open in, or would be opened as.
+    session: Option<String>,
+    has_session: bool,
+    /// When the session was last active, if the backend reports it.
+    last_active: Option<i64>,
+    /// Times the project was opened through ws.
+    opens: u64,
+    last_opened: Option<i64>,
+    /// What `--format text` prints.
+    #[serde(skip)]
+    label: String,
+}
 
-    if separator_offset > 0 && selected_index == sessions.len() {
-        return Ok(());
+impl<'a> ListedEntry<'a> {
+    fn project(
+        project: &'a ProjectInfo,
+        session: Option<&SessionInfo>,
+        state: &State,
+        names: &naming::SessionNames,
+        config: &Config,
+    ) -> Self {
+        let opens = state.opens.get(&project.id);
+        ListedEntry {
+            kind: "project",
+            name: &project.name,
+            id: Some(&project.id),
+            root: Some(&project.root),
+            category: Some(&project.category),
+            path: Some(&project.path),
+            session: session
+                .map(|s| s.name.clone())
+                .or_else(|| names.name(project).ok()),
+            has_session: session.is_some(),
+            last_active: session.and_then(SessionInfo::active_at),
+            opens: opens.map_or(0, |o| o.count),
+            last_opened: opens.map(|o| o.last_opened),
+            label: project.label(&config.display.project),
+        }
     }
 
-    let item = selectable_items
-        .get(adjusted_index)
-        .ok_or("Invalid selection")?;
+    fn tsv(&self) -> String {
+        [
+            self.kind.to_string(),
+            self.name.to_string(),
+            self.category.unwrap_or_default().to_string(),
+            self.path.unwrap_or_default().to_string(),
+            self.session.clone().unwrap_or_default(),
+            self.has_session.to_string(),
+            self.last_active.map(|t| t.to_string()).unwrap_or_default(),
+        ]
+        .join("\t")
+    }
+}
 
-    handle_selection(item.clone(), &mut state)?;
+/// Lists live sessions and projects. With both, a session shows up as its
+/// project's row when it has one, so every entry appears once.
+fn handle_list_command(
+    format: ListFormat,
+    with_sessions: bool,
+    with_projects: bool,
+    config: &Config,
+) -> Result<()> {
+    let mut state = State::load();
+    state.ensure_cache_valid(config)?;
     state.save()?;
 
+    let live = mux().list_sessions().unwrap_or_default();
+    let names = naming::SessionNames::new(&state.cache.projects, &config.session);
+    let mut entries = Vec::new();
+    let mut claimed: HashMap<&str, &SessionInfo> = HashMap::new();
+    for info in &live {
+        let project = state.project_for_session(&info.name, &config.session);
+        if let (true, Some(project)) = (with_projects, project) {
+            claimed.insert(&project.path, info);
+            continue;
+        }
+        if with_sessions {
+            let mut entry = match project {
+                Some(project) => ListedEntry::project(project, Some(info), &state, &names, config),
+                None => ListedEntry {
+                    kind: "session",
+                    name: &info.name,
+                    id: None,
+                    root: None,
+                    category: None,
+                    path: state.sessions.get(&info.name).map(|p| p.as_str()),
+                    session: Some(info.name.clone()),
+                    has_session: true,
+                    last_active: info.active_at(),
+                    opens: 0,
+                    last_opened: None,
+                    label: String::new(),
+                },
+            };
+            entry.kind = "session";
+            entry.name = &info.name;
+            entry.label = info.name.clone();
+            entries.push(entry);
+        }
+    }
+    if with_projects {
+        for project in &state.cache.projects {
+            // A session another project claimed isn't this one's, even if
+            // the names match.
+            let session = claimed.get(project.path.as_str()).copied().or_else(|| {
+                let name = names.name(project).ok()?;
+                live.iter()
+                    .find(|s| s.name == name && !claimed.values().any(|c| c.name == name))
+            });
+            entries.push(ListedEntry::project(
+                project, session, &state, &names, config,
+            ));
+        }
+    }
+
+    match format {
+        ListFormat::Text => {
+            for entry in &entries {
+                println!("{}", entry.label);
+            }
+        }
+        ListFormat::Json => println!("{}", serde_json::to_string_pretty(&entries)?),
+        ListFormat::Tsv => {
+            for entry in &entries {
+                println!("{}", entry.tsv());
+            }
+        }
+    }
+    Ok(())
+}
+
+fn handle_export_command(output: Option<&Path>, config: &Config) -> Result<()> {
+    let mut state = State::load();
+    state.ensure_cache_valid(config)?;
+
+    let names = naming::SessionNames::new(&state.cache.projects, &config.session);
+    let inventory = Inventory {
+        version: INVENTORY_VERSION,
+        hostname: inventory::hostname(),
+        home: dirs::home_dir()
+            .map(|home| home.to_string_lossy().to_string())
+            .unwrap_or_default(),
+        exported_at: current_timestamp(),
+        projects: state
+            .cache
+            .projects
+            .iter()
+            .map(|project| InventoryProject::from_project(project, names.name(project).ok()))
+            .collect(),
+        sessions: mux()
+            .list_sessions()
+            .unwrap_or_default()
+            .into_iter()
+            .map(|s| s.name)
+            .collect(),
+        pins: state.pinned.iter().map(|p| p.qualified_name()).collect(),
+    };
+
+    let json = serde_json::to_string_pretty(&inventory)?;
+    match output {
+        Some(path) => fs::write(path, json)?,
+        None => println!("{}", json),
+    }
+    state.save()?;
     Ok(())
 }
 
-fn handle_selection(item: SelectableItem, state: &mut State) -> Result<()> {
-    match item {
-        SelectableItem::Session(name) => {
-            state.push_history(name.clone());
-            TmuxClient::switch_or_attach(&name)?;
+fn handle_compare_command(left: &Path, right: &Path, commands: bool) -> Result<()> {
+    let load = |path: &Path| -> Result<Inventory> {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| messages::READ_FAILED.format(&[&path.display(), &e]))?;
+        let inventory: Inventory = serde_json::from_str(&contents)
+            .map_err(|e| format!("Invalid inventory {}: {}", path.display(), e))?;
+        if inventory.version != INVENTORY_VERSION {
+            return Err(format!(
+                "{} has inventory version {}, expected {}",
+                path.display(),
+                inventory.version,
+                INVENTORY_VERSION
+            )
+            .into());
+        }
+        Ok(inventory)
+    };
+    let left = load(left)?;
+    let right = load(right)?;
+
+    let diff = left.diff(&right);
+    if diff.is_empty() {
+        println!(
+            "{} and {} have the same projects, sessions and pins",
+            left.hostname, right.hostname
+        );
+        return Ok(());
+    }
+
+    let sides = [
+        (
+            &left,
+            &right,
+            &diff.projects_only_left,
+            &diff.sessions_only_left,
+            &diff.pins_only_left,
+        ),
+        (
+            &right,
+            &left,
+            &diff.projects_only_right,
+            &diff.sessions_only_right,
+            &diff.pins_only_right,
+        ),
+    ];
+
+    for (source, target, projects, sessions, pins) in sides {
+        if projects.is_empty() && sessions.is_empty() && pins.is_empty() {
+            continue;
+        }
+
+        println!("only on {}:", source.hostname);
+        for project in projects.iter() {
+            println!("  project: {}", project.key);
+        }
+        for session in sessions.iter() {
+            println!("  session: {}", session);
+        }
+        for pin in pins.iter() {
+            println!("  pin: {}", pin);
         }
-        SelectableItem::Project(project) => {
-            let session_name = &project.name;
 
-            if !TmuxClient::has_session(session_name)? {
-                TmuxClient::create_session(session_name, &project.path)?;
+        if commands {
+            println!("  # to run on {}:", target.hostname);
+            for project in projects.iter() {
+                let destination =
+                    shell_quote(&source.portable_path(&project.path)).replacen("'~/", "~/'", 1);
+                match &project.remote {
+                    Some(remote) => {
+                        println!("  git clone {} {}", shell_quote(remote), destination)
+                    }
+                    None => println!("  # {} has no git remote", project.key),
+                }
+            }
+            for session in sessions.iter() {
+                let project = source
+                    .projects
+                    .iter()
+                    .find(|p| p.session.as_deref() == Some(*session));
+                match project {
+                    Some(project) => println!("  ws open {}", shell_quote(&project.key)),
+                    None => println!("  # session {} has no matching project", session),
+                }
             }
+            for pin in pins.iter() {
+                println!("  ws pin {}", shell_quote(pin));
+            }
+        }
+        println!();
+    }
+
+    Ok(())
+}
+
+fn handle_pause_command(sessions: Vec<String>, config: &Config) -> Result<()> {
+    let sessions = if sessions.is_empty() {
+        vec![TmuxClient::current_session()?]
+    } else {
+        sessions
+    };
 
-            state.push_history(session_name.clone());
-            TmuxClient::switch_or_attach(session_name)?;
+    for session in &sessions {
+        if !TmuxClient::has_session(session)? {
+            return Err(messages::NO_SESSION_NAMED.format(&[&session]).into());
+        }
+        // The pane processes themselves are skipped: tmux continues a pane
+        // process as soon as it stops, so only what runs under it can stay
+        // stopped.
+        if config.pause.signal {
+            let pids = process::stoppable(&TmuxClient::pane_pids(session)?)?;
+            process::signal(&pids, "STOP")?;
+        }
+        if let Some(hook) = &config.pause.command {
+            run_pause_hook(hook, session);
         }
+        TmuxClient::set_session_option(session, PAUSED_OPTION, "1")?;
     }
 
+    report(&messages::PAUSED.format(&[&sessions.join(", ")]), config);
     Ok(())
 }
 
-fn handle_kill_command() -> Result<()> {
-    let sessions = TmuxClient::list_sessions()?;
+fn handle_resume_command(sessions: Vec<String>, config: &Config) -> Result<()> {
     if sessions.is_empty() {
-        eprintln!("No sessions to kill");
+        let mut state = State::load();
+        let live: Vec<String> = TmuxClient::list_sessions()?
+            .into_iter()
+            .map(|s| s.name)
+            .collect();
+        let lost = state.lost_sessions(&live, TmuxClient::server_start_time());
+        if !lost.is_empty() {
+            return recreate_lost_sessions(lost, &mut state, config);
+        }
+    }
+
+    let sessions = if sessions.is_empty() {
+        TmuxClient::list_sessions()?
+            .into_iter()
+            .filter(|s| s.paused)
+            .map(|s| s.name)
+            .collect()
+    } else {
+        sessions
+    };
+    if sessions.is_empty() {
+        eprintln!("{}", messages::NO_PAUSED_SESSIONS.text());
         return Ok(());
     }
 
-    let mut state = State::load();
-    let current = TmuxClient::current_session().ok();
+    for session in &sessions {
+        if !TmuxClient::has_session(session)? {
+            return Err(messages::NO_SESSION_NAMED.format(&[&session]).into());
+        }
+        if config.pause.signal {
+            let pids = process::descendants(&TmuxClient::pane_pids(session)?)?;
+            process::signal(&pids, "CONT")?;
+        }
+        if let Some(hook) = &config.pause.resume_command {
+            run_pause_hook(hook, session);
+        }
+        TmuxClient::unset_session_option(session, PAUSED_OPTION)?;
+    }
 
-    let session_names: Vec<String> = sessions.iter().map(|s| s.name.clone()).collect();
+    report(&messages::RESUMED.format(&[&sessions.join(", ")]), config);
+    Ok(())
+}
 
-    let selected_index = match Picker::pick(&session_names, "kill> ") {
-        Some(idx) => idx,
-        None => return Ok(()),
-    };
+/// Recreates sessions lost to a tmux server restart from the projects they
+/// were opened from, oldest first so history keeps its order. Sessions
+/// whose project is unknown or gone are dropped from state.
+fn recreate_lost_sessions(lost: Vec<String>, state: &mut State, config: &Config) -> Result<()> {
+    let mut recreated = Vec::new();
+    for session in lost.iter().rev() {
+        let project = state
+            .sessions
+            .get(session)
+            .and_then(|path| state.project_for_path(path))
+            .filter(|p| Path::new(&p.path).is_dir());
+        match project {
+            Some(project) => recreated.push(open_project(&project, state, config)?.0),
+            None => eprintln!("{}", messages::SESSION_PROJECT_GONE.format(&[&session])),
+        }
+    }
 
-    let selected = &session_names[selected_index];
-    let previous = state.previous_session().map(|s| s.to_string());
+    let stale: Vec<String> = lost
+        .into_iter()
+        .filter(|s| !recreated.contains(s))
+        .collect();
+    state.forget_sessions(&stale);
+    state.save()?;
+
+    if recreated.is_empty() {
+        eprintln!("{}", messages::NO_SESSIONS_TO_RECREATE.text());
+    } else {
+        report(
+            &messages::RECREATED_AFTER_RESTART.format(&[&recreated.join(", ")]),
+            config,
+        );
+    }
+    Ok(())
+}
 
-    TmuxClient::kill_session(selected)?;
+fn run_pause_hook(hook: &str, session: &str) {
+    let status = Command::new("sh")
+        .args(["-c", hook])
+        .env("WS_SESSION", session)
+        .status();
+    match status {
+        Ok(status) if status.success() => {}
+        Ok(status) => messages::warn(messages::PAUSE_HOOK_EXITED.format(&[&session, &status])),
+        Err(e) => messages::warn(messages::PAUSE_HOOK_FAILED.format(&[&session, &e])),
+    }
+}
 
-    if current.as_deref() == Some(selected.as_str()) {
-        if let Some(prev) = previous {
-            if prev != *selected {
-                TmuxClient::switch_client(&prev).ok();
+/// Prints names for shell completion. The cache is used as it is, however
+/// old, since a rescan would stall the shell.
+fn handle_complete_command(names: completions::Names, config: &Config) -> Result<()> {
+    if names != completions::Names::Projects {
+        for session in mux().list_sessions().unwrap_or_default() {
+            println!("{}", session.name);
+        }
+    }
+    if names != completions::Names::Sessions {
+        let state = State::load();
+        for project in &state.cache.projects {
+            if config
+                .workspaces
+                .iter()
+                .any(|root| root.alias == project.root)
+            {
+                println!("{}", project.display_name());
             }
         }
     }
+    Ok(())
+}
 
-    state.history.retain(|s| s != selected);
-    state.save()?;
+/// Lists the programs ws can run, whether each is found, and what doesn't
+/// work without it.
+fn handle_doctor_command(config: &Config) -> Result<()> {
+    let backend = match BACKEND.get().copied().unwrap_or_default() {
+        Backend::Tmux => "tmux",
+        Backend::Zellij => "zellij",
+        Backend::Wezterm => "wezterm",
+    };
+    let first_word = |command: &str| command.split_whitespace().next().map(str::to_string);
+    let mut tools: Vec<(String, &str)> = vec![(backend.to_string(), "sessions")];
+    tools.extend(
+        first_word(&config.layout.shell).map(|shell| (shell, "shell in the editor window")),
+    );
+    tools.extend(
+        first_word(&config.layout.editor).map(|editor| (editor, "editor in the editor window")),
+    );
+    tools.extend(first_word(&config.layout.file_manager).map(|fm| (fm, "files window")));
+    tools.push((
+        "git".to_string(),
+        "git status in the picker and previews, worktrees, ws branch or ws health",
+    ));
+    tools.push(("direnv".to_string(), ".envrc variables in ws env"));
+    tools.push(("curl".to_string(), "[[orgs]] listings or ws health"));
+    if config.picker.finder == Finder::Fzf {
+        tools.push(("fzf".to_string(), "picker (finder = \"fzf\")"));
+    }
+    if config.recent_dirs.enabled {
+        tools.push(("zoxide".to_string(), "zoxide directories in the picker"));
+    }
+    tools.push((
+        watch::PROGRAM.to_string(),
+        "change events for ws daemon (it polls instead)",
+    ));
 
+    let mut missing = 0;
+    for (tool, feature) in &tools {
+        match find_program(tool) {
+            Some(path) => println!(
+                "ok       {:<10} {}",
+                tool,
+                tilde_path(&path.to_string_lossy())
+            ),
+            None => {
+                missing += 1;
+                println!("missing  {:<10} no {}", tool, feature);
+            }
+        }
+    }
+    if missing == 0 {
+        println!("Everything ws uses is installed");
+    }
     Ok(())
 }
 
-fn handle_back_command() -> Result<()> {
-    let mut state = State::load();
-
-    if let Some(previous) = state.previous_session() {
-        let previous = previous.to_string();
-        TmuxClient::switch_client(&previous)?;
-        
-        state.push_history(previous);
+/// Rescans whenever a watched path changes, and at least every half TTL so
+/// the cache never expires: with the daemon running, `ws pick` reads the
+/// cache without scanning. State is reloaded before each rescan so changes
+/// made by other ws processes are kept.
+fn handle_daemon_command(config: &Config) -> Result<()> {
+    if !StateMode::current().writes() {
+        return Err(
+            "ws daemon keeps the cache in state, which is not written with \
+                    --read-only or --in-memory"
+                .into(),
+        );
+    }
+    let interval = Duration::from_secs(CACHE_TTL_SECONDS as u64 / 2);
+    let use_program = tool_available(watch::PROGRAM, "ws daemon polls for changes instead");
+    // Snapshots are of tmux sessions, and only when a server is running.
+    let snapshots = config.backend == Backend::Tmux;
+    let mut watcher: Option<watch::Watcher> = None;
+    loop {
+        let mut state = State::load();
+        let refresh = state.refresh_cache(config);
         state.save()?;
+        println!("{} projects cached", state.cache.projects.len());
+        for project in &refresh.new_projects {
+            println!("  new: {}", project.label(&config.display.project));
+        }
+        // The daemon runs outside tmux, where notify() has no client to use.
+        if !refresh.new_projects.is_empty()
+            && notifications_enabled()
+            && config.backend == Backend::Tmux
+        {
+            let message = new_projects_message(&refresh.new_projects, &config.display.project);
+            if let Err(e) = TmuxClient::display_message_everywhere(&message) {
+                messages::warn(e);
+            }
+        }
+        for error in &refresh.errors {
+            eprintln!("  {}: {}", error.path, error.message);
+        }
+
+        // Projects appear and disappear, and so do the directories to watch.
+        let paths = watched_paths(config);
+        if watcher.as_ref().is_none_or(|w| w.paths() != paths) {
+            let new = watch::Watcher::new(paths, use_program);
+            println!(
+                "watching {} path(s){}",
+                new.paths().len(),
+                if new.is_evented() { "" } else { " (polling)" }
+            );
+            watcher = Some(new);
+        }
+        let watcher = watcher.as_mut().expect("watcher was just set");
+        let rescan_at = Instant::now() + interval;
+        loop {
+            if snapshots {
+                if let Err(e) = update_auto_snapshot() {
+                    messages::warn(e);
+                }
+            }
+            let wait = rescan_at
+                .saturating_duration_since(Instant::now())
+                .min(AUTO_SNAPSHOT_INTERVAL);
+            if wait.is_zero() {
+                break;
+            }
+            if watcher.wait(wait) {
+                while watcher.wait(DAEMON_SETTLE) {}
+                break;
+            }
+        }
+    }
+}
+
+fn handle_statusline_command(session: Option<String>, update: bool, config: &Config) -> Result<()> {
+    let session = match session {
+        Some(session) => session,
+        None => TmuxClient::current_session()?,
+    };
+
+    let state = State::load();
+    let Some(project) = state.project_for_session(&session, &config.session) else {
+        return Ok(());
+    };
+
+    if update {
+        update_status_options(&session, project)?;
     } else {
-        eprintln!("No previous session in history");
+        println!(
+            "#[fg={}]{}#[default]",
+            category_color(&project.category),
+            project.category
+        );
     }
 
     Ok(())
 }
 
-fn handle_refresh_command(workspace: &str) -> Result<()> {
-    let mut state = State::load();
-    state.refresh_cache(workspace)?;
-    state.save()?;
-    println!("Cache refreshed: {} projects found", state.cache.projects.len());
-    Ok(())
+fn format_age(seconds: i64) -> String {
+    match seconds.max(0) {
+        s if s < 60 => format!("{}s ago", s),
+        s if s < 3600 => format!("{}m ago", s / 60),
+        s if s < 86400 => format!("{}h ago", s / 3600),
+        s => format!("{}d ago", s / 86400),
+    }
 }
 
+/// Seconds since the epoch, or 0 if the clock is set before it.
 fn current_timestamp() -> i64 {
     SystemTime::now()
         .duration_since(UNIX_EPOCH)
-        .unwrap()
-        .as_secs() as i64
+        .map_or(0, |elapsed| elapsed.as_secs() as i64)
+}
+
+/// Whether something stamped at `stamp` is less than `ttl` seconds old at
+/// `now`. A stamp in the future means the clock moved back since (VM
+/// snapshots, NTP corrections), so it counts as stale rather than as fresh
+/// until the clock catches up.
+fn is_fresh(stamp: i64, ttl: i64, now: i64) -> bool {
+    (0..ttl).contains(&(now - stamp))
+}
+
+/// `f` of each of `items`, on up to `workers` threads, in the order of
+/// `items`. A call that panics gives `None`.
+fn parallel_map<T: Sync, R: Send>(
+    items: &[T],
+    workers: usize,
+    f: impl Fn(&T) -> R + Sync,
+) -> Vec<Option<R>> {
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<R>>> = Mutex::new(items.iter().map(|_| None).collect());
+    std::thread::scope(|scope| {
+        for _ in 0..workers.min(items.len()) {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(item) = items.get(index) else {
+                    break;
+                };
+                let result = std::panic::catch_unwind(AssertUnwindSafe(|| f(item))).ok();
+                results.lock().unwrap()[index] = result;
+            });
+        }
+    });
+    results.into_inner().unwrap()
+}
+
+/// Points state, config and the default workspace at `dir`, and tmux at a
+/// server of its own unless `allow_tmux` is set.
+fn enter_sandbox(dir: &Path, allow_tmux: bool) -> Result<Config> {
+    let workspace = dir.join("workspace");
+    fs::create_dir_all(&workspace)?;
+    let dir = dir.canonicalize()?;
+
+    DATA_DIR.set(dir.join("data")).ok();
+    if !allow_tmux {
+        TmuxClient::use_socket(TmuxSocket::Path(dir.join("tmux.sock")))?;
+    }
+    Config::load_from(
+        &dir.join("config.toml"),
+        &dir.join("workspace").to_string_lossy(),
+    )
+}
+
+/// Asks `question` before something that is hard to undo. At a terminal the
+/// answer is read from stdin; from a tmux key binding, where there is none,
+/// tmux asks in the invoking client's status line instead. Without either
+/// it refuses, unless `--yes` was given.
+fn confirm(question: &str) -> Result<bool> {
+    if ASSUME_YES.get().copied().unwrap_or(false) {
+        return Ok(true);
+    }
+    if std::io::stdin().is_terminal() && std::io::stderr().is_terminal() {
+        eprint!("{} [y/N] ", question);
+        let mut answer = String::new();
+        std::io::stdin().read_line(&mut answer)?;
+        return Ok(matches!(answer.trim(), "y" | "Y" | "yes"));
+    }
+    if let Some(client) = TmuxClient::is_in_tmux()
+        .then(TmuxClient::invoking_client)
+        .flatten()
+    {
+        return TmuxClient::confirm(&client, question);
+    }
+    Err(messages::PASS_YES.format(&[&question]).into())
+}
+
+/// Asks whether a workspace found from the current directory should go into
+/// the config, so later runs don't depend on where ws is started from. A
+/// workspace the user said no to isn't offered again.
+fn offer_to_save_workspace(workspace: &Path) {
+    if !StateMode::current().writes() {
+        return;
+    }
+    let path = workspace.to_string_lossy().to_string();
+    let mut state = State::load();
+    if state.declined_workspaces.contains(&path) {
+        return;
+    }
+    let config_path = Config::path();
+    let question = format!(
+        "No workspace configured. Use {} and save it to {}?",
+        tilde_path(&path),
+        tilde_path(&config_path.to_string_lossy())
+    );
+    match confirm(&question) {
+        Ok(true) => {
+            if let Err(e) = Config::save_workspace(&config_path, workspace) {
+                messages::warn(e);
+            }
+        }
+        Ok(false) => {
+            state.declined_workspaces.push(path);
+            if let Err(e) = state.save() {
+                messages::warn(e);
+            }
+        }
+        // Nowhere to ask.
+        Err(_) => {}
+    }
 }
 
 fn main() {
     let cli = Cli::parse();
+    let command_name = cli.command.name();
+
+    let state_mode = if cli.read_only {
+        Ok(StateMode::ReadOnly)
+    } else if cli.in_memory {
+        Ok(StateMode::Memory)
+    } else {
+        StateMode::from_env()
+    };
+    match state_mode {
+        Ok(mode) => {
+            STATE_MODE.set(mode).ok();
+        }
+        Err(e) => {
+            messages::error(e);
+            std::process::exit(1);
+        }
+    }
+
+    let config = match &cli.sandbox {
+        Some(dir) => enter_sandbox(dir, cli.allow_tmux),
+        None => Config::load(),
+    };
+    let mut config = match config {
+        Ok(config) => config,
+        Err(e) => {
+            messages::error(e);
+            std::process::exit(1);
+        }
+    };
+    if let Some(mode) = cli.attach {
+        config.attach.mode = mode;
+    }
+    if cli.quiet {
+        config.output.summary = SummaryMode::Off;
+    }
+    if let Some(editor) = cli.editor {
+        config.layout.editor = editor;
+    }
+    if let Some(file_manager) = cli.file_manager {
+        config.layout.file_manager = file_manager;
+    }
+    if let Some(shell) = cli.window_shell {
+        config.layout.shell = shell;
+    }
+    if let Some(backend) = cli.backend {
+        config.backend = backend;
+    }
+    if cli.min_depth.is_some() || cli.max_depth.is_some() {
+        config.override_depth(cli.min_depth, cli.max_depth);
+    }
+    BACKEND.set(config.backend).ok();
+    if config.backend != Backend::Tmux && cli.command.needs_tmux() {
+        messages::error(messages::NEEDS_TMUX.format(&[&cli.command.name()]));
+        std::process::exit(1);
+    }
+    PLAIN_PICKER.set(cli.no_tui || config.picker.plain).ok();
+    ASSUME_YES.set(cli.yes).ok();
+    FINDER.set(cli.picker.unwrap_or(config.picker.finder)).ok();
+    if let Some(workspace) = &config.detected_workspace {
+        offer_to_save_workspace(workspace);
+    }
+
+    let started = Instant::now();
+    if cli.timings {
+        TIMINGS.set(started).ok();
+    }
 
     let result = match cli.command {
-        Commands::Pick { workspace } => {
-            let workspace = shellexpand::tilde(&workspace).to_string();
-            handle_pick_command(&workspace)
+        Commands::Pick {
+            workspace,
+            stdin,
+            print,
+            json,
+            exclude_current_project,
+            archived,
+            within,
+            recent_first,
+            max_items,
+        } => {
+            config.override_workspace(workspace);
+            if exclude_current_project {
+                config.picker.exclude_current = true;
+            }
+            let source = match within {
+                Some(dir) => ProjectSource::Within(dir),
+                None if stdin => ProjectSource::Stdin,
+                None if archived => ProjectSource::Archived,
+                None if recent_first => {
+                    ProjectSource::Recent(max_items.unwrap_or(RECENT_PICK_ITEMS))
+                }
+                None => ProjectSource::Workspace,
+            };
+            let output = match (print, json) {
+                (true, true) => PickOutput::Json,
+                (true, false) => PickOutput::Print,
+                _ => PickOutput::Open,
+            };
+            handle_pick_command(source, output, &config)
+        }
+        Commands::Archive { project, workspace } => {
+            config.override_workspace(workspace);
+            handle_archive_command(&project, &config)
+        }
+        Commands::Pin { project, workspace } => {
+            config.override_workspace(workspace);
+            handle_pin_command(&project, &config)
+        }
+        Commands::Unpin { project } => handle_unpin_command(&project, &config),
+        Commands::Unarchive { project, workspace } => {
+            config.override_workspace(workspace);
+            handle_unarchive_command(&project, &config)
+        }
+        Commands::Kill { json } => handle_kill_command(json, &config),
+        Commands::Gc {
+            idle,
+            interactive,
+            dry_run,
+            json,
+        } => handle_gc_command(idle, interactive, dry_run, json, &config),
+        Commands::Back => handle_back_command(&config),
+        Commands::Attach { last: _ } => handle_attach_last_command(&config),
+        Commands::Refresh { json, workspace } => {
+            config.override_workspace(workspace);
+            handle_refresh_command(json, &config)
+        }
+        Commands::Files { project, workspace } => {
+            config.override_workspace(workspace);
+            handle_files_command(project.as_deref(), &config)
+        }
+        Commands::Todos {
+            project,
+            refresh,
+            workspace,
+        } => {
+            config.override_workspace(workspace);
+            handle_todos_command(project.as_deref(), refresh, &config)
+        }
+        Commands::Switch { name, workspace } => {
+            config.override_workspace(workspace);
+            handle_switch_command(&name, &config)
+        }
+        Commands::Open {
+            project,
+            window,
+            app,
+            session,
+            workspace,
+        } => {
+            config.override_workspace(workspace);
+            handle_open_command(
+                &project,
+                window.as_deref(),
+                app.as_deref(),
+                session,
+                &config,
+            )
+        }
+        Commands::Group { session } => handle_group_command(session, &config),
+        Commands::New {
+            project,
+            template,
+            workspace,
+        } => {
+            config.override_workspace(workspace);
+            handle_new_command(&project, template.as_deref(), &config)
+        }
+        Commands::Clone {
+            url,
+            category,
+            workspace,
+        } => {
+            config.override_workspace(workspace);
+            handle_clone_command(&url, category.as_deref(), &config)
+        }
+        Commands::Categories { command, workspace } => {
+            config.override_workspace(workspace);
+            match command {
+                None => handle_categories_command(&config),
+                Some(CategoryCommands::Create { name }) => {
+                    handle_category_create_command(&name, &config)
+                }
+                Some(CategoryCommands::Archive { name }) => {
+                    handle_category_archive_command(&name, &config)
+                }
+            }
+        }
+        Commands::Snapshot { command } => match command {
+            SnapshotCommands::Save { name } => handle_snapshot_save_command(&name, &config),
+            SnapshotCommands::Restore { name } => handle_snapshot_restore_command(&name, &config),
+            SnapshotCommands::List => handle_snapshot_list_command(),
+            SnapshotCommands::Delete { name } => handle_snapshot_delete_command(&name, &config),
+        },
+        Commands::Restore { name } => {
+            handle_snapshot_restore_command(name.as_deref().unwrap_or(AUTO_SNAPSHOT), &config)
         }
-        Commands::Kill => handle_kill_command(),
-        Commands::Back => handle_back_command(),
-        Commands::Refresh { workspace } => {
-            let workspace = shellexpand::tilde(&workspace).to_string();
-            handle_refresh_command(&workspace)
+        Commands::Profile { command, workspace } => {
+            config.override_workspace(workspace);
+            match command {
+                ProfileCommands::List { days } => handle_profile_list_command(days, &config),
+                ProfileCommands::Open { name, days } => {
+                    handle_profile_open_command(&name, days, &config)
+                }
+            }
+        }
+        Commands::Layout {
+            command:
+                LayoutCommands::Test {
+                    name,
+                    project,
+                    wait,
+                    workspace,
+                },
+        } => {
+            config.override_workspace(workspace);
+            handle_layout_test_command(&name, project.as_deref(), wait, &config)
+        }
+        Commands::Stats {
+            commands,
+            enable,
+            disable,
+            reset,
+        } => handle_stats_command(commands, enable, disable, reset),
+        Commands::Which { query, workspace } => {
+            config.override_workspace(workspace);
+            handle_which_command(&query, &config)
+        }
+        Commands::Export { output, workspace } => {
+            config.override_workspace(workspace);
+            handle_export_command(output.as_deref(), &config)
+        }
+        Commands::List {
+            format,
+            json,
+            tsv,
+            sessions,
+            projects,
+            workspace,
+        } => {
+            config.override_workspace(workspace);
+            let format = if json {
+                ListFormat::Json
+            } else if tsv {
+                ListFormat::Tsv
+            } else {
+                format
+            };
+            handle_list_command(format, !projects, !sessions, &config)
+        }
+        Commands::Compare {
+            left,
+            right,
+            commands,
+        } => handle_compare_command(&left, &right, commands),
+        Commands::Run {
+            project,
+            task,
+            pick,
+            workspace,
+        } => {
+            config.override_workspace(workspace);
+            handle_run_command(project.as_deref(), task.as_deref(), pick, &config)
+        }
+        Commands::Branch {
+            branch,
+            project,
+            workspace,
+        } => {
+            config.override_workspace(workspace);
+            handle_branch_command(branch.as_deref(), project.as_deref(), &config)
         }
+        Commands::Env {
+            project,
+            shell,
+            workspace,
+        } => {
+            config.override_workspace(workspace);
+            handle_env_command(project.as_deref(), shell, &config)
+        }
+        Commands::Health {
+            project,
+            path,
+            workspace,
+        } => {
+            config.override_workspace(workspace);
+            handle_health_command(project.as_deref(), path.as_deref(), &config)
+        }
+        Commands::Import { from, file, write } => {
+            handle_import_command(from, file.as_deref(), write, &config)
+        }
+        Commands::Pause { sessions } => handle_pause_command(sessions, &config),
+        Commands::Resume { sessions } => handle_resume_command(sessions, &config),
+        Commands::Statusline { session, update } => {
+            handle_statusline_command(session, update, &config)
+        }
+        Commands::Doctor => handle_doctor_command(&config),
+        Commands::Daemon => handle_daemon_command(&config),
+        Commands::Completions { shell } => {
+            let mut cli = <Cli as clap::CommandFactory>::command();
+            cli.build();
+            print!("{}", completions::script(shell, &cli));
+            Ok(())
+        }
+        Commands::Complete { names } => handle_complete_command(names, &config),
+        #[cfg(feature = "self-update")]
+        Commands::SelfUpdate { channel, check } => self_update::run(channel, check),
     };
 
+    // The status line is polled by tmux and completion runs on every tab
+    // press, so counting them would only add noise and a state write each.
+    print_timings();
+    if command_name != "statusline"
+        && command_name != "complete"
+        && std::env::var_os(BACKGROUND_REFRESH_ENV).is_none()
+    {
+        let phases = timings::phases();
+        record_usage(command_name, started.elapsed().as_millis() as u64, &phases);
+    }
+
     if let Err(e) = result {
-        eprintln!("Error: {}", e);
+        messages::error(e);
         std::process::exit(1);
     }
 }
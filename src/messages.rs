@@ -0,0 +1,247 @@
+use std::fmt::Display;
+use std::sync::OnceLock;
+
+/// Languages ws has messages in. Messages missing from a language's
+/// catalog, and everything else, are in English.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Locale {
+    English,
+    Spanish,
+}
+
+impl Locale {
+    /// The locale of the first of `LC_ALL`, `LC_MESSAGES` and `LANG` that is
+    /// set, the order gettext uses; `es_ES.UTF-8`, `es_MX` and `es` all
+    /// select Spanish.
+    pub fn current() -> Self {
+        static LOCALE: OnceLock<Locale> = OnceLock::new();
+        *LOCALE.get_or_init(|| {
+            let value = ["LC_ALL", "LC_MESSAGES", "LANG"]
+                .iter()
+                .filter_map(|var| std::env::var(var).ok())
+                .find(|value| !value.is_empty())
+                .unwrap_or_default();
+            Self::parse(&value)
+        })
+    }
+
+    fn parse(value: &str) -> Self {
+        let language = value.split(['_', '.', '@', '-']).next().unwrap_or("");
+        match language {
+            "es" => Self::Spanish,
+            _ => Self::English,
+        }
+    }
+}
+
+/// A user-facing message in each language, with `{}` where its arguments
+/// go, in order.
+pub struct Message {
+    english: &'static str,
+    spanish: &'static str,
+}
+
+impl Message {
+    /// The message in the current locale.
+    pub fn text(&self) -> &'static str {
+        match Locale::current() {
+            Locale::English => self.english,
+            Locale::Spanish => self.spanish,
+        }
+    }
+
+    /// `text` with each `{}` replaced by the next of `args`.
+    pub fn format(&self, args: &[&dyn Display]) -> String {
+        let mut parts = self.text().split("{}");
+        let mut message = parts.next().unwrap_or_default().to_string();
+        let mut args = args.iter();
+        for part in parts {
+            if let Some(arg) = args.next() {
+                message.push_str(&arg.to_string());
+            }
+            message.push_str(part);
+        }
+        message
+    }
+}
+
+/// Prints a warning to stderr.
+pub fn warn(message: impl Display) {
+    eprintln!("{}{}", WARNING.text(), message);
+}
+
+/// Prints an error to stderr.
+pub fn error(message: impl Display) {
+    eprintln!("{}{}", ERROR.text(), message);
+}
+
+/// How many `{}` `text` has.
+const fn placeholders(text: &str) -> usize {
+    let bytes = text.as_bytes();
+    let mut count = 0;
+    let mut i = 0;
+    while i + 1 < bytes.len() {
+        if bytes[i] == b'{' && bytes[i + 1] == b'}' {
+            count += 1;
+            i += 2;
+        } else {
+            i += 1;
+        }
+    }
+    count
+}
+
+/// Defines each message, failing the build when its translations take
+/// different numbers of arguments.
+macro_rules! messages {
+    ($($name:ident: $english:literal, $spanish:literal;)*) => {
+        $(pub const $name: Message = Message {
+            english: $english,
+            spanish: $spanish,
+        };
+        const _: () = assert!(
+            placeholders($english) == placeholders($spanish),
+            concat!(stringify!($name), " takes a different number of arguments in Spanish"),
+        );)*
+    };
+}
+
+messages! {
+    WARNING: "Warning: ", "Aviso: ";
+    ERROR: "Error: ", "Error: ";
+
+    // Sessions
+    SWITCHED: "switched to {}", "cambiado a {}";
+    SWITCHED_CREATED: "switched to {} (created)", "cambiado a {} (creada)";
+    NEEDS_TMUX: "ws {} needs the tmux backend", "ws {} necesita el backend de tmux";
+    NO_SESSIONS_TO_KILL: "No sessions to kill", "No hay sesiones que cerrar";
+    KILLED_NOTHING: "killed nothing", "no se cerró nada";
+    KILLED: "killed {}", "cerrada {}";
+    KILLED_SESSIONS: "killed {} sessions ({})", "cerradas {} sesiones ({})";
+    AND_FAILED_TO_KILL: ", failed to kill {}", ", no se pudo cerrar {}";
+    AND_NOW_IN: ", now in {}", ", ahora en {}";
+    KILLED_PROGRESS: "[{}/{}] killed {}", "[{}/{}] cerrada {}";
+    KILL_FAILED_PROGRESS: "[{}/{}] failed to kill {}: {}", "[{}/{}] no se pudo cerrar {}: {}";
+    NO_SESSION_NAMED: "No session named '{}'", "No hay ninguna sesión llamada '{}'";
+    NO_SESSION_IN_HISTORY: "No session in history", "No hay ninguna sesión en el historial";
+    NOTHING_MATCHING: "No session or project matching '{}'",
+        "Ninguna sesión ni proyecto coincide con '{}'";
+    NO_WINDOW: "No window '{}' in session '{}'", "No hay ninguna ventana '{}' en la sesión '{}'";
+    NO_PANE: "No pane '{}'", "No hay ningún panel '{}'";
+    NO_SESSIONS_TO_SAVE: "No sessions to save", "No hay sesiones que guardar";
+    NO_SNAPSHOT_NAMED: "No snapshot named '{}'", "No hay ninguna instantánea llamada '{}'";
+    NO_PREVIOUS_SESSION: "No previous session in history",
+        "No hay una sesión anterior en el historial";
+    NO_IDLE_SESSIONS: "No sessions idle for {}h or more",
+        "No hay sesiones inactivas desde hace {}h o más";
+    NO_PAUSED_SESSIONS: "No paused sessions", "No hay sesiones en pausa";
+    NO_SESSIONS_TO_RECREATE: "No sessions to recreate", "No hay sesiones que recrear";
+    SESSION_PROJECT_GONE: "Skipping {}: its project is unknown or gone",
+        "Se omite {}: su proyecto es desconocido o ya no existe";
+    NO_SNAPSHOTS: "No snapshots", "No hay instantáneas";
+    EVICTED: "evicted {} (over {} sessions; ws snapshot restore {}{} brings it back)",
+        "cerrada {} (más de {} sesiones; ws snapshot restore {}{} la recupera)";
+    EVICTED_SEVERAL: "evicted {} (over {} sessions; ws snapshot restore {}NAME brings one back)",
+        "cerradas {} (más de {} sesiones; ws snapshot restore {}NOMBRE recupera una)";
+    MAX_SESSIONS_FAILED: "failed to enforce session.max_sessions: {}",
+        "no se pudo aplicar session.max_sessions: {}";
+    KILLED_IDLE: "killed {} idle session(s): {}", "cerradas {} sesiones inactivas: {}";
+    PAUSED: "paused {}", "en pausa: {}";
+    RESUMED: "resumed {}", "reanudadas: {}";
+    RECREATED_AFTER_RESTART: "recreated {} after tmux restart",
+        "recreadas {} tras reiniciar tmux";
+
+    // tmux
+    CURRENT_SESSION_FAILED: "Failed to get current session",
+        "No se pudo obtener la sesión actual";
+    LIST_WINDOWS_FAILED: "Failed to list the windows of '{}'",
+        "No se pudieron listar las ventanas de '{}'";
+    LIST_PANES_FAILED: "Failed to list panes of '{}'", "No se pudieron listar los paneles de '{}'";
+    CREATE_GROUPED_FAILED: "Failed to create session '{}' grouped with '{}'",
+        "No se pudo crear la sesión '{}' agrupada con '{}'";
+    SEND_KEYS_FAILED: "Failed to send keys to '{}'", "No se pudieron enviar teclas a '{}'";
+    OPEN_FILE_FAILED: "Failed to open {} in '{}'", "No se pudo abrir {} en '{}'";
+    START_WINDOW_FAILED: "Failed to start '{}' in session '{}'",
+        "No se pudo iniciar '{}' en la sesión '{}'";
+    SET_OPTION_FAILED: "Failed to set {} on '{}'", "No se pudo poner {} en '{}'";
+    UNSET_OPTION_FAILED: "Failed to unset {} on '{}'", "No se pudo quitar {} de '{}'";
+    ATTACH_FAILED: "Failed to attach to '{}': {}", "No se pudo conectar a '{}': {}";
+
+    // Hooks and commands run for the user
+    ON_SWITCH_EXITED: "on_switch command exited with {}", "el comando on_switch terminó con {}";
+    ON_SWITCH_FAILED: "failed to run on_switch command: {}",
+        "no se pudo ejecutar el comando on_switch: {}";
+    HOOK_EXITED: "{} hook exited with {}", "el hook {} terminó con {}";
+    HOOK_FAILED: "failed to run {} hook: {}", "no se pudo ejecutar el hook {}: {}";
+    PAUSE_HOOK_EXITED: "pause hook for {} exited with {}", "el hook de pausa de {} terminó con {}";
+    PAUSE_HOOK_FAILED: "failed to run pause hook for {}: {}",
+        "no se pudo ejecutar el hook de pausa de {}: {}";
+    TERMINAL_DIRECTORY_FAILED: "failed to report directory to the terminal: {}",
+        "no se pudo comunicar el directorio a la terminal: {}";
+    TERMINAL_TITLE_FAILED: "failed to set the terminal title: {}",
+        "no se pudo poner el título de la terminal: {}";
+    RUN_FAILED: "Failed to run {}: {}", "No se pudo ejecutar {}: {}";
+    PROGRAM_MISSING: "{} not found, {} (`ws doctor` lists what is missing)",
+        "no se encuentra {}, {} (`ws doctor` muestra lo que falta)";
+
+    // Projects and state
+    NO_PROJECT_NAMED: "No project named '{}'", "No hay ningún proyecto llamado '{}'";
+    NO_CATEGORY_NAMED: "No category named '{}'", "No hay ninguna categoría llamada '{}'";
+    NO_RECENT_FILES: "No recent files in {}", "No hay archivos recientes en {}";
+    NO_BRANCHES: "No branches in {}", "No hay ramas en {}";
+    ALREADY_EXISTS: "{} already exists", "{} ya existe";
+    ALREADY_PINNED: "{} is already pinned", "{} ya está fijado";
+    PASS_YES: "{} Pass --yes to confirm without a terminal",
+        "{} Pasa --yes para confirmar sin una terminal";
+    AMBIGUOUS_PROJECT: "'{}' is ambiguous: {}", "'{}' es ambiguo: {}";
+    CANNOT_READ: "cannot read {}: {}", "no se puede leer {}: {}";
+    READ_FAILED: "Failed to read {}: {}", "No se pudo leer {}: {}";
+    WRITE_FAILED: "Failed to write {}: {}", "No se pudo escribir {}: {}";
+    CACHE_REFRESHED: "Cache refreshed: {} projects found",
+        "Caché actualizada: {} proyectos encontrados";
+    ORG_REPOSITORIES: "Org {}: {} repositories", "Organización {}: {} repositorios";
+    NO_CATEGORIES: "No categories", "No hay categorías";
+    NO_TODOS_IN: "No TODO or FIXME comments in {}", "No hay comentarios TODO ni FIXME en {}";
+    NO_TODOS: "No TODO or FIXME comments in the workspace",
+        "No hay comentarios TODO ni FIXME en el espacio de trabajo";
+    UNREADABLE_PATHS: "{} path(s) could not be read, keeping their cached projects:",
+        "No se pudieron leer {} ruta(s); se conservan sus proyectos en caché:";
+    INVALID_PATH_SKIPPED: "Skipping invalid path: {}", "Se omite la ruta no válida: {}";
+    STATE_UNREADABLE: "{} is unreadable: {}", "{} no se puede leer: {}";
+    STATE_UNREADABLE_MOVED: "{} is unreadable ({}), starting over; the old file is at {}",
+        "{} no se puede leer ({}), se empieza de cero; el archivo anterior está en {}";
+    USING_CACHED_REPOSITORIES: "using cached repositories for {}: {}",
+        "se usan los repositorios en caché de {}: {}";
+    LIST_REPOSITORIES_FAILED: "failed to list repositories of {}: {}",
+        "no se pudieron listar los repositorios de {}: {}";
+    WATCHER_FAILED: "{} failed, polling instead: {}", "{} falló, se consulta periódicamente: {}";
+    WATCHER_EXITED: "{} exited, polling instead", "{} terminó, se consulta periódicamente";
+
+    // The picker
+    NO_PROJECT_PATHS_ON_STDIN: "No project paths on stdin",
+        "No hay rutas de proyectos en la entrada estándar";
+    NO_ARCHIVED_PROJECTS: "No archived projects", "No hay proyectos archivados";
+    NO_PROJECTS_IN: "No projects in {}", "No hay proyectos en {}";
+    NO_RECENT_PROJECTS: "No sessions or recently opened projects; `ws pick` lists them all",
+        "No hay sesiones ni proyectos abiertos hace poco; `ws pick` los muestra todos";
+    NOTHING_BESIDES_CURRENT: "Nothing to pick besides the current project (picker.exclude_current)",
+        "No hay nada que elegir aparte del proyecto actual (picker.exclude_current)";
+    ENTER_NUMBERS: "Enter one or more numbers separated by spaces; empty input cancels",
+        "Escribe uno o más números separados por espacios; una entrada vacía cancela";
+    ENTER_NUMBER: "Enter a number; empty input cancels",
+        "Escribe un número; una entrada vacía cancela";
+    ENTER_SINGLE_NUMBER: "Enter a single number", "Escribe un solo número";
+    NO_ROW: "No row {} (1-{})", "No hay fila {} (1-{})";
+    ADD_ACTION: "Add an action after the number: {}", "Añade una acción después del número: {}";
+    ALL_SELECTED: "All are selected; enter the numbers to leave out, or just enter to confirm",
+        "Están todos seleccionados; escribe los números a quitar, o Intro para confirmar";
+    NOTHING_MATCHES: "Nothing matches '{}'", "Nada coincide con '{}'";
+    FZF_FAILED: "Failed to run fzf: {}", "No se pudo ejecutar fzf: {}";
+
+    // ws import
+    IMPORT_LIST_HINT: "With --write, {} project paths also go to {}",
+        "Con --write, {} rutas de proyectos van también a {}";
+    IMPORT_LAYOUTS_HINT: "With --write, layouts also go to {}: {}",
+        "Con --write, los layouts van también a {}: {}";
+    LAYOUT_EXISTS: "{} exists, keeping it", "{} ya existe, se conserva";
+}
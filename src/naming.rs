@@ -0,0 +1,112 @@
+use std::collections::HashMap;
+
+use crate::config::SessionConfig;
+use crate::{expand_project_template, ProjectInfo, Result};
+
+/// Templates tried in turn when another project would get the same session
+/// name, e.g. `work/api` and `home/api` become `work-api` and `home-api`.
+/// The `{alias}` one only applies to projects found in a workspace root.
+const DISAMBIGUATING_TEMPLATES: &[&str] = &["{category}-{name}", "{alias}-{category}-{name}"];
+
+/// Turns a name into one tmux will store verbatim. tmux itself replaces `:`
+/// and `.` with `_`; doing it here keeps state and history in sync with the
+/// real session name.
+pub fn sanitize(name: &str) -> Result<String> {
+    if name.trim().is_empty() {
+        return Err("Cannot create a session with an empty name".into());
+    }
+    if name.chars().any(|c| c.is_control()) {
+        return Err(format!(
+            "Cannot create a session for '{}': name contains control characters",
+            name.escape_debug()
+        )
+        .into());
+    }
+
+    Ok(replace_reserved(name))
+}
+
+fn replace_reserved(name: &str) -> String {
+    name.replace([':', '.'], "_")
+}
+
+/// Session name for a project, see `SessionNames::unsanitized`. Naming
+/// many projects goes through one `SessionNames` instead.
+pub fn project_session_name(
+    project: &ProjectInfo,
+    projects: &[ProjectInfo],
+    session: &SessionConfig,
+) -> Result<String> {
+    SessionNames::new(projects, session).name(project)
+}
+
+/// The name `session.name` gives the project, or the `prefix_template` one
+/// when `session.prefix` is on and the project has a root.
+pub fn template_name(project: &ProjectInfo, session: &SessionConfig) -> String {
+    if session.prefix && !project.root.is_empty() {
+        expand_project_template(&session.prefix_template, project)
+    } else {
+        expand_project_template(&session.name, project)
+    }
+}
+
+/// The names every template gives each of a set of projects, indexed once
+/// so that naming all of them doesn't compare each with all the others.
+pub struct SessionNames<'a> {
+    session: &'a SessionConfig,
+    /// Paths of the projects behind each name, per template: `template_name`
+    /// first, then `DISAMBIGUATING_TEMPLATES` in order.
+    taken: Vec<HashMap<String, Vec<&'a str>>>,
+}
+
+impl<'a> SessionNames<'a> {
+    pub fn new(projects: &'a [ProjectInfo], session: &'a SessionConfig) -> Self {
+        let mut taken = vec![HashMap::new(); DISAMBIGUATING_TEMPLATES.len() + 1];
+        for project in projects {
+            let names = std::iter::once(template_name(project, session)).chain(
+                DISAMBIGUATING_TEMPLATES
+                    .iter()
+                    .map(|template| expand_project_template(template, project)),
+            );
+            for (index, name) in names.enumerate() {
+                taken[index]
+                    .entry(replace_reserved(&name))
+                    .or_insert_with(Vec::new)
+                    .push(project.path.as_str());
+            }
+        }
+        Self { session, taken }
+    }
+
+    /// Session name for a project, see `unsanitized`.
+    pub fn name(&self, project: &ProjectInfo) -> Result<String> {
+        sanitize(&self.unsanitized(project))
+    }
+
+    /// The project's `template_name`, unless another project would end up
+    /// with the same session; then the first of `DISAMBIGUATING_TEMPLATES`
+    /// that no other project's name matches.
+    pub fn unsanitized(&self, project: &ProjectInfo) -> String {
+        let by_other = |index: usize, name: &str| {
+            self.taken[index]
+                .get(name)
+                .is_some_and(|paths| paths.iter().any(|path| *path != project.path))
+        };
+        let taken = |name: &str, template: Option<usize>| {
+            let name = replace_reserved(name);
+            by_other(0, &name) || template.is_some_and(|index| by_other(index + 1, &name))
+        };
+
+        let name = template_name(project, self.session);
+        if !taken(&name, None) {
+            return name;
+        }
+        DISAMBIGUATING_TEMPLATES
+            .iter()
+            .enumerate()
+            .filter(|(_, template)| !template.contains("{alias}") || !project.root.is_empty())
+            .map(|(index, template)| (expand_project_template(template, project), index))
+            .find(|(candidate, index)| !taken(candidate, Some(*index)))
+            .map_or(name, |(candidate, _)| candidate)
+    }
+}
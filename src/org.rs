@@ -0,0 +1,179 @@
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use crate::config::{OrgConfig, OrgProvider};
+use crate::Result;
+
+const PAGE_SIZE: usize = 100;
+pub const GITHUB_TOKEN_ENV: &str = "GITHUB_TOKEN";
+const GITLAB_TOKEN_ENV: &str = "GITLAB_TOKEN";
+
+/// A repository listed by a provider API.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemoteRepo {
+    pub name: String,
+    pub clone_url: String,
+}
+
+#[derive(Deserialize)]
+struct GithubRepo {
+    name: String,
+    clone_url: String,
+    ssh_url: String,
+    #[serde(default)]
+    archived: bool,
+}
+
+#[derive(Deserialize)]
+struct GitlabProject {
+    path: String,
+    http_url_to_repo: String,
+    ssh_url_to_repo: String,
+    #[serde(default)]
+    archived: bool,
+}
+
+/// Lists the org's repositories, skipping archived ones. Requests go through
+/// `curl`; `GITHUB_TOKEN` / `GITLAB_TOKEN` are sent when set so private
+/// repositories are included.
+pub fn fetch_repos(org: &OrgConfig) -> Result<Vec<RemoteRepo>> {
+    match org.provider {
+        OrgProvider::Github => {
+            let host = org.host.as_deref().unwrap_or("api.github.com");
+            // Orgs and users have separate endpoints; try the org one first.
+            let pages = fetch_pages::<GithubRepo>(
+                &format!("https://{}/orgs/{}/repos", host, org.name),
+                GITHUB_TOKEN_ENV,
+                "Authorization: Bearer",
+            )
+            .or_else(|_| {
+                fetch_pages::<GithubRepo>(
+                    &format!("https://{}/users/{}/repos", host, org.name),
+                    GITHUB_TOKEN_ENV,
+                    "Authorization: Bearer",
+                )
+            })?;
+            Ok(pages
+                .into_iter()
+                .filter(|repo| !repo.archived)
+                .map(|repo| RemoteRepo {
+                    name: repo.name,
+                    clone_url: if org.ssh {
+                        repo.ssh_url
+                    } else {
+                        repo.clone_url
+                    },
+                })
+                .collect())
+        }
+        OrgProvider::Gitlab => {
+            let host = org.host.as_deref().unwrap_or("gitlab.com");
+            let pages = fetch_pages::<GitlabProject>(
+                &format!(
+                    "https://{}/api/v4/groups/{}/projects?include_subgroups=true",
+                    host,
+                    org.name.replace('/', "%2F")
+                ),
+                GITLAB_TOKEN_ENV,
+                "PRIVATE-TOKEN:",
+            )?;
+            Ok(pages
+                .into_iter()
+                .filter(|project| !project.archived)
+                .map(|project| RemoteRepo {
+                    name: project.path,
+                    clone_url: if org.ssh {
+                        project.ssh_url_to_repo
+                    } else {
+                        project.http_url_to_repo
+                    },
+                })
+                .collect())
+        }
+    }
+}
+
+fn fetch_pages<T: for<'de> Deserialize<'de>>(
+    url: &str,
+    token_env: &str,
+    auth_header: &str,
+) -> Result<Vec<T>> {
+    let separator = if url.contains('?') { '&' } else { '?' };
+
+    let mut items = Vec::new();
+    for page in 1.. {
+        let page_url = format!("{}{}per_page={}&page={}", url, separator, PAGE_SIZE, page);
+        let batch: Vec<T> = fetch_json(&page_url, token_env, auth_header)?;
+        let done = batch.len() < PAGE_SIZE;
+        items.extend(batch);
+        if done {
+            break;
+        }
+    }
+    Ok(items)
+}
+
+/// GETs `url` with `curl`, sending the token from `token_env` if it is set.
+/// The token header goes through curl's stdin, since its arguments are
+/// visible to every user of the machine.
+pub fn fetch_json<T: for<'de> Deserialize<'de>>(
+    url: &str,
+    token_env: &str,
+    auth_header: &str,
+) -> Result<T> {
+    let token = std::env::var(token_env).ok().filter(|t| !t.is_empty());
+    let mut command = Command::new("curl");
+    command.args(["-fsSL", "-H", "Accept: application/json"]);
+    if token.is_some() {
+        command.args(["-H", "@-"]);
+    }
+    let mut child = command
+        .arg(url)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    if let Some(token) = token {
+        writeln!(stdin, "{} {}", auth_header, token)?;
+    }
+    drop(stdin);
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        return Err(format!(
+            "Failed to fetch {}: {}",
+            url,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )
+        .into());
+    }
+    serde_json::from_slice(&output.stdout)
+        .map_err(|e| format!("Unexpected response from {}: {}", url, e).into())
+}
+
+/// Owner and repository name of a clone URL: the last two components of an
+/// https, ssh or scp-like (`git@host:owner/repo.git`) URL.
+pub fn url_owner_and_name(url: &str) -> Option<(String, String)> {
+    let path = match url.split_once("://") {
+        Some((_, rest)) => rest.split_once('/')?.1,
+        None => url.split_once(':').map_or(url, |(_, path)| path),
+    };
+    let path = path.trim_end_matches('/').trim_end_matches(".git");
+    let (owner, name) = path.rsplit_once('/')?;
+    let owner = owner.rsplit('/').next()?;
+    (!owner.is_empty() && !name.is_empty()).then(|| (owner.to_string(), name.to_string()))
+}
+
+pub fn clone_repo(repo: &RemoteRepo, destination: &Path) -> Result<()> {
+    let status = Command::new("git")
+        .arg("clone")
+        .arg(&repo.clone_url)
+        .arg(destination)
+        .status()?;
+    if !status.success() {
+        return Err(format!("Failed to clone {}", repo.clone_url).into());
+    }
+    Ok(())
+}
@@ -0,0 +1,133 @@
+use std::collections::{HashMap, HashSet};
+use std::process::Command;
+
+use crate::Result;
+
+/// All descendants of `pids`, parents before children. ws itself and its
+/// ancestors are left out, so pausing the session ws runs in does not stop
+/// ws or the shell waiting for it.
+pub fn descendants(pids: &[u32]) -> Result<Vec<u32>> {
+    let parents = parent_map()?;
+
+    let mut children: HashMap<u32, Vec<u32>> = HashMap::new();
+    for (&pid, &ppid) in &parents {
+        children.entry(ppid).or_default().push(pid);
+    }
+
+    let mut protected = HashSet::new();
+    let mut pid = std::process::id();
+    while protected.insert(pid) {
+        match parents.get(&pid) {
+            Some(&ppid) if ppid != 0 => pid = ppid,
+            _ => break,
+        }
+    }
+
+    let mut found = Vec::new();
+    let mut queue: Vec<u32> = pids.to_vec();
+    let mut seen: HashSet<u32> = pids.iter().copied().collect();
+    while let Some(pid) = queue.pop() {
+        for &child in children.get(&pid).into_iter().flatten() {
+            if !seen.insert(child) {
+                continue;
+            }
+            if !protected.contains(&child) {
+                found.push(child);
+            }
+            queue.push(child);
+        }
+    }
+    Ok(found)
+}
+
+/// The descendants of the pane processes `pids` that can be stopped without
+/// upsetting a job-control shell. A shell whose foreground job stops takes
+/// the terminal back and leaves the job in the background once it is
+/// continued, so the processes a pane shell runs as its foreground job are
+/// left running; what they run in turn is stopped.
+pub fn stoppable(pids: &[u32]) -> Result<Vec<u32>> {
+    let output = Command::new("ps")
+        .args([
+            "-e", "-o", "pid=", "-o", "ppid=", "-o", "pgid=", "-o", "tpgid=",
+        ])
+        .output()?;
+    if !output.status.success() {
+        return Err("Failed to list processes".into());
+    }
+
+    let mut groups: HashMap<u32, (u32, i64, i64)> = HashMap::new();
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if let [pid, ppid, pgid, tpgid] = fields[..] {
+            if let (Ok(pid), Ok(ppid), Ok(pgid), Ok(tpgid)) =
+                (pid.parse(), ppid.parse(), pgid.parse(), tpgid.parse())
+            {
+                groups.insert(pid, (ppid, pgid, tpgid));
+            }
+        }
+    }
+    // A job of the shell's own is in a process group of its own; a program
+    // started without job control shares its parent's.
+    let foreground_job = |pid: &u32| {
+        groups.get(pid).is_some_and(|&(ppid, pgid, tpgid)| {
+            pids.contains(&ppid)
+                && pgid == tpgid
+                && groups
+                    .get(&ppid)
+                    .is_some_and(|&(_, parent, _)| parent != pgid)
+        })
+    };
+
+    Ok(descendants(pids)?
+        .into_iter()
+        .filter(|pid| !foreground_job(pid))
+        .collect())
+}
+
+fn parent_map() -> Result<HashMap<u32, u32>> {
+    let output = Command::new("ps")
+        .args(["-e", "-o", "pid=", "-o", "ppid="])
+        .output()?;
+    if !output.status.success() {
+        return Err("Failed to list processes".into());
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let pid = fields.next()?.parse().ok()?;
+            let ppid = fields.next()?.parse().ok()?;
+            Some((pid, ppid))
+        })
+        .collect())
+}
+
+/// Sends `signal` (e.g. `STOP`, `CONT`) to every pid. Processes that exited
+/// in the meantime are not an error.
+pub fn signal(pids: &[u32], signal: &str) -> Result<()> {
+    if pids.is_empty() {
+        return Ok(());
+    }
+    Command::new("kill")
+        .arg(format!("-{}", signal))
+        .args(pids.iter().map(|pid| pid.to_string()))
+        .stderr(std::process::Stdio::null())
+        .status()?;
+    Ok(())
+}
+
+/// The command names (`ps -o comm`) of the processes that still exist.
+pub fn command_names(pids: &[u32]) -> Result<Vec<String>> {
+    if pids.is_empty() {
+        return Ok(Vec::new());
+    }
+    let list: Vec<String> = pids.iter().map(|pid| pid.to_string()).collect();
+    let output = Command::new("ps")
+        .args(["-o", "comm=", "-p", &list.join(",")])
+        .output()?;
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(|name| name.trim().to_string())
+        .collect())
+}
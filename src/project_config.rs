@@ -0,0 +1,62 @@
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use crate::config::LayoutSpec;
+use crate::{messages, Result};
+
+/// Per-project settings, read from this file in the project's directory.
+pub const PROJECT_CONFIG_FILE: &str = ".ws.toml";
+
+/// Settings a project carries in its own `.ws.toml`. A missing file behaves
+/// like an empty one.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProjectConfig {
+    /// Named shell commands for `ws run`, e.g. `test = "cargo nextest run"`.
+    pub tasks: BTreeMap<String, String>,
+    /// Environment set in the project's new sessions and printed by
+    /// `ws env`.
+    pub env: BTreeMap<String, String>,
+    /// Layout for new sessions: a name from the layouts directory, or the
+    /// windows themselves.
+    pub layout: Option<ProjectLayout>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum ProjectLayout {
+    Named(String),
+    Inline(LayoutSpec),
+}
+
+impl ProjectConfig {
+    pub fn load(project_path: &str) -> Result<Self> {
+        let path = Self::path(project_path);
+        match fs::read_to_string(&path) {
+            Ok(contents) => toml::from_str(&contents)
+                .map_err(|e| format!("Invalid {}: {}", path.display(), e).into()),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(messages::READ_FAILED.format(&[&path.display(), &e]).into()),
+        }
+    }
+
+    pub fn path(project_path: &str) -> PathBuf {
+        Path::new(project_path).join(PROJECT_CONFIG_FILE)
+    }
+
+    pub fn task(&self, project_path: &str, name: &str) -> Result<&str> {
+        match self.tasks.get(name) {
+            Some(command) => Ok(command),
+            None if self.tasks.is_empty() => {
+                Err(format!("No tasks defined in {}", Self::path(project_path).display()).into())
+            }
+            None => {
+                let names: Vec<&str> = self.tasks.keys().map(|k| k.as_str()).collect();
+                Err(format!("No task '{}' (available: {})", name, names.join(", ")).into())
+            }
+        }
+    }
+}
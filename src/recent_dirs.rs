@@ -0,0 +1,88 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// Frequently visited directories from shell jump tools, best first.
+///
+/// Reads the `z` data file (`$_Z_DATA`, `~/.z`, or the fish port's
+/// `~/.local/share/z/data`) ranked by visit score, then zoxide's database
+/// through `zoxide query`, followed by zsh's `cdr` list in recency order.
+/// Missing or unreadable sources are ignored.
+pub fn load() -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+
+    for path in z_data_files() {
+        if let Ok(contents) = fs::read_to_string(&path) {
+            dirs.extend(parse_z(&contents));
+        }
+    }
+
+    if let Ok(output) = Command::new("zoxide").args(["query", "--list"]).output() {
+        if output.status.success() {
+            dirs.extend(
+                String::from_utf8_lossy(&output.stdout)
+                    .lines()
+                    .map(PathBuf::from),
+            );
+        }
+    }
+
+    if let Some(home) = dirs::home_dir() {
+        if let Ok(contents) = fs::read_to_string(home.join(".chpwd-recent-dirs")) {
+            dirs.extend(parse_cdr(&contents));
+        }
+    }
+
+    let mut seen = HashSet::new();
+    dirs.retain(|dir| dir.is_dir() && seen.insert(dir.clone()));
+    dirs
+}
+
+fn z_data_files() -> Vec<PathBuf> {
+    if let Ok(path) = std::env::var("_Z_DATA") {
+        return vec![PathBuf::from(path)];
+    }
+
+    let mut files = Vec::new();
+    if let Some(home) = dirs::home_dir() {
+        files.push(home.join(".z"));
+    }
+    if let Some(data) = dirs::data_dir() {
+        files.push(data.join("z").join("data"));
+    }
+    files
+}
+
+/// `z` stores `path|rank|timestamp` per line.
+fn parse_z(contents: &str) -> Vec<PathBuf> {
+    let mut entries: Vec<(PathBuf, f64)> = contents
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.rsplitn(3, '|');
+            let _timestamp = parts.next()?;
+            let rank = parts.next()?.parse().ok()?;
+            let path = parts.next()?;
+            Some((PathBuf::from(path), rank))
+        })
+        .collect();
+
+    entries.sort_by(|a, b| b.1.total_cmp(&a.1));
+    entries.into_iter().map(|(path, _)| path).collect()
+}
+
+/// `cdr` stores one shell-quoted path per line, most recent first.
+fn parse_cdr(contents: &str) -> Vec<PathBuf> {
+    contents
+        .lines()
+        .filter_map(|line| {
+            let line = line.trim();
+            let unquoted = line
+                .strip_prefix("$'")
+                .or_else(|| line.strip_prefix('\''))
+                .and_then(|rest| rest.strip_suffix('\''))
+                .unwrap_or(line);
+            (!unquoted.is_empty()).then(|| PathBuf::from(unquoted.replace("\\'", "'")))
+        })
+        .collect()
+}
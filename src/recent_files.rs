@@ -0,0 +1,76 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::SystemTime;
+
+/// Files of a project worth reopening, most recently modified first.
+///
+/// Draws on the files `ws files` opened before (`opened`), vim's `~/.viminfo`
+/// and the files git reports as changed or untracked, so the list is useful
+/// whichever editor is in use. Only files that still exist inside `project`
+/// are kept.
+pub fn load(project: &Path, opened: &[String], limit: usize) -> Vec<(PathBuf, SystemTime)> {
+    let mut candidates: Vec<PathBuf> = opened.iter().map(PathBuf::from).collect();
+    candidates.extend(viminfo_files());
+    candidates.extend(changed_files(project));
+
+    let mut seen = HashSet::new();
+    let mut files: Vec<(PathBuf, SystemTime)> = candidates
+        .into_iter()
+        .filter(|path| path.starts_with(project) && !is_editor_backup(path))
+        .filter(|path| seen.insert(path.clone()))
+        .filter_map(|path| {
+            let metadata = fs::metadata(&path).ok()?;
+            metadata
+                .is_file()
+                .then(|| (path, metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH)))
+        })
+        .collect();
+    files.sort_by_key(|(_, modified)| std::cmp::Reverse(*modified));
+    files.truncate(limit);
+    files
+}
+
+/// Swap and backup files editors leave next to the files they edit.
+fn is_editor_backup(path: &Path) -> bool {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy())
+        .unwrap_or_default();
+    name.ends_with('~')
+        || name.starts_with(".#")
+        || name.ends_with(".swp")
+        || name.ends_with(".swo")
+}
+
+/// Files in vim's history of edited files (the `> path` lines).
+fn viminfo_files() -> Vec<PathBuf> {
+    let Some(home) = dirs::home_dir() else {
+        return Vec::new();
+    };
+    let Ok(contents) = fs::read_to_string(home.join(".viminfo")) else {
+        return Vec::new();
+    };
+    contents
+        .lines()
+        .filter_map(|line| line.strip_prefix("> "))
+        .map(|path| PathBuf::from(shellexpand::tilde(path).as_ref()))
+        .collect()
+}
+
+/// Modified and untracked files of the repository at `project`.
+fn changed_files(project: &Path) -> Vec<PathBuf> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(project)
+        .args(["ls-files", "--modified", "--others", "--exclude-standard"])
+        .output();
+    match output {
+        Ok(output) if output.status.success() => String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(|file| project.join(file))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
@@ -0,0 +1,205 @@
+use serde::Deserialize;
+use std::cmp::Ordering;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::org::{fetch_json, GITHUB_TOKEN_ENV};
+use crate::Result;
+
+const RELEASES_URL: &str = "https://api.github.com/repos/seg6/ws/releases";
+/// Release asset listing the SHA-256 of every other asset, as written by
+/// `sha256sum`.
+const CHECKSUMS_ASSET: &str = "SHA256SUMS";
+
+/// Which releases `ws self-update` installs.
+#[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Channel {
+    /// The latest full release
+    Stable,
+    /// The newest release, pre-releases included
+    Pre,
+}
+
+#[derive(Deserialize)]
+struct Release {
+    tag_name: String,
+    #[serde(default)]
+    draft: bool,
+    #[serde(default)]
+    assets: Vec<Asset>,
+}
+
+#[derive(Deserialize)]
+struct Asset {
+    name: String,
+    browser_download_url: String,
+}
+
+impl Release {
+    fn version(&self) -> &str {
+        self.tag_name.trim_start_matches('v')
+    }
+
+    fn asset(&self, name: &str) -> Result<&Asset> {
+        self.assets
+            .iter()
+            .find(|asset| asset.name == name)
+            .ok_or_else(|| format!("Release {} has no {} asset", self.tag_name, name).into())
+    }
+}
+
+/// The release binary for this machine, e.g. `ws-x86_64-linux` or
+/// `ws-aarch64-macos`.
+fn asset_name() -> String {
+    format!("ws-{}-{}", std::env::consts::ARCH, std::env::consts::OS)
+}
+
+fn latest_release(channel: Channel) -> Result<Release> {
+    match channel {
+        Channel::Stable => fetch_json(
+            &format!("{}/latest", RELEASES_URL),
+            GITHUB_TOKEN_ENV,
+            "Authorization: Bearer",
+        ),
+        Channel::Pre => {
+            let releases: Vec<Release> = fetch_json(
+                &format!("{}?per_page=20", RELEASES_URL),
+                GITHUB_TOKEN_ENV,
+                "Authorization: Bearer",
+            )?;
+            releases
+                .into_iter()
+                .find(|release| !release.draft)
+                .ok_or_else(|| "No releases published yet".into())
+        }
+    }
+}
+
+/// Orders `1.2.10` after `1.2.9`, and a release after its pre-releases
+/// (`1.3.0` after `1.3.0-rc.1`).
+fn compare_versions(a: &str, b: &str) -> Ordering {
+    fn parse(version: &str) -> (Vec<u64>, Option<&str>) {
+        let (numbers, pre) = match version.split_once('-') {
+            Some((numbers, pre)) => (numbers, Some(pre)),
+            None => (version, None),
+        };
+        let numbers = numbers
+            .split('.')
+            .map(|part| part.parse().unwrap_or(0))
+            .collect();
+        (numbers, pre)
+    }
+    let (a_numbers, a_pre) = parse(a);
+    let (b_numbers, b_pre) = parse(b);
+    a_numbers
+        .cmp(&b_numbers)
+        .then_with(|| match (a_pre, b_pre) {
+            (None, None) => Ordering::Equal,
+            (None, Some(_)) => Ordering::Greater,
+            (Some(_), None) => Ordering::Less,
+            (Some(a), Some(b)) => a.cmp(b),
+        })
+}
+
+fn download(url: &str, destination: &Path) -> Result<()> {
+    let output = Command::new("curl")
+        .args(["-fsSL", "-o"])
+        .arg(destination)
+        .arg(url)
+        .output()?;
+    if !output.status.success() {
+        return Err(format!(
+            "Failed to download {}: {}",
+            url,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// SHA-256 of `path` in hex, from `sha256sum` or macOS's `shasum`.
+fn sha256(path: &Path) -> Result<String> {
+    let attempts: [(&str, &[&str]); 2] = [("sha256sum", &[]), ("shasum", &["-a", "256"])];
+    for (program, args) in attempts {
+        let Ok(output) = Command::new(program).args(args).arg(path).output() else {
+            continue;
+        };
+        if output.status.success() {
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            if let Some(hash) = stdout.split_whitespace().next() {
+                return Ok(hash.to_lowercase());
+            }
+        }
+    }
+    Err("ws self-update needs sha256sum or shasum to verify the download".into())
+}
+
+/// The checksum listed for `name` in a `SHA256SUMS` file.
+fn expected_checksum(sums: &str, name: &str) -> Option<String> {
+    sums.lines().find_map(|line| {
+        let (hash, file) = line.split_once(char::is_whitespace)?;
+        // sha256sum marks files hashed in binary mode with a leading '*'.
+        (file.trim_start().trim_start_matches('*') == name).then(|| hash.to_lowercase())
+    })
+}
+
+/// Checks `channel` for a release newer than the running ws and, unless
+/// `check_only`, installs it over the current executable.
+///
+/// The binary is downloaded next to the executable, checked against the
+/// release's `SHA256SUMS` and then renamed over it, so an interrupted update
+/// leaves the old binary in place.
+pub fn run(channel: Channel, check_only: bool) -> Result<()> {
+    let current = env!("CARGO_PKG_VERSION");
+    let release = latest_release(channel)?;
+    let latest = release.version();
+    if compare_versions(latest, current) != Ordering::Greater {
+        println!("ws {} is up to date", current);
+        return Ok(());
+    }
+    if check_only {
+        println!("ws {} is available (installed: {})", latest, current);
+        return Ok(());
+    }
+
+    let binary = release.asset(&asset_name())?;
+    let sums_url = &release.asset(CHECKSUMS_ASSET)?.browser_download_url;
+
+    let executable = std::env::current_exe()?.canonicalize()?;
+    let directory = executable
+        .parent()
+        .ok_or_else(|| format!("No directory for {}", executable.display()))?;
+    let staged: PathBuf = directory.join(format!(".ws-update-{}", std::process::id()));
+    let sums = staged.with_extension("sums");
+
+    let result = (|| -> Result<()> {
+        download(&binary.browser_download_url, &staged)
+            .map_err(|e| format!("{} (is {} writable?)", e, directory.display()))?;
+        download(sums_url, &sums)?;
+        let expected = expected_checksum(&fs::read_to_string(&sums)?, &binary.name)
+            .ok_or_else(|| format!("{} lists no checksum for {}", CHECKSUMS_ASSET, binary.name))?;
+        let actual = sha256(&staged)?;
+        if actual != expected {
+            return Err(format!(
+                "Checksum mismatch for {}: expected {}, got {}",
+                binary.name, expected, actual
+            )
+            .into());
+        }
+        fs::set_permissions(&staged, fs::Permissions::from_mode(0o755))?;
+        fs::rename(&staged, &executable)
+            .map_err(|e| format!("Failed to replace {}: {}", executable.display(), e))?;
+        Ok(())
+    })();
+    fs::remove_file(&sums).ok();
+    if result.is_err() {
+        fs::remove_file(&staged).ok();
+    }
+    result?;
+
+    println!("updated ws {} -> {}", current, latest);
+    Ok(())
+}
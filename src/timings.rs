@@ -0,0 +1,53 @@
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+
+/// Time spent so far in each phase, in the order the phases first ran.
+static PHASES: Mutex<Vec<(&'static str, Duration)>> = Mutex::new(Vec::new());
+/// Phases currently running; a phase started inside another one is counted
+/// as part of the outer phase, so the phases never add up to more than the
+/// whole run.
+static DEPTH: Mutex<usize> = Mutex::new(0);
+
+/// Runs `f`, adding the time it takes to `phase`.
+pub fn time<T>(phase: &'static str, f: impl FnOnce() -> T) -> T {
+    let outermost = {
+        let mut depth = DEPTH.lock().unwrap();
+        *depth += 1;
+        *depth == 1
+    };
+    let started = Instant::now();
+    let result = f();
+    let elapsed = started.elapsed();
+    *DEPTH.lock().unwrap() -= 1;
+
+    if outermost {
+        let mut phases = PHASES.lock().unwrap();
+        match phases.iter_mut().find(|(name, _)| *name == phase) {
+            Some((_, total)) => *total += elapsed,
+            None => phases.push((phase, elapsed)),
+        }
+    }
+    result
+}
+
+/// The phases timed so far and how long each took.
+pub fn phases() -> Vec<(&'static str, Duration)> {
+    PHASES.lock().unwrap().clone()
+}
+
+/// One line per phase, then the whole run, e.g. `picker        812ms`.
+pub fn report(phases: &[(&str, Duration)], total: Duration) -> String {
+    let mut lines: Vec<String> = phases
+        .iter()
+        .map(|(name, elapsed)| format!("{:<14}{:>6}ms", name, elapsed.as_millis()))
+        .collect();
+    let accounted: Duration = phases.iter().map(|(_, elapsed)| *elapsed).sum();
+    lines.push(format!(
+        "{:<14}{:>6}ms",
+        "other",
+        total.saturating_sub(accounted).as_millis()
+    ));
+    lines.push(format!("{:<14}{:>6}ms", "total", total.as_millis()));
+    lines.join("\n")
+}
@@ -0,0 +1,83 @@
+/// Separates the fields of a line; tmux escapes it inside values.
+const SEPARATOR: char = '|';
+
+/// The fields a tmux `-F` query asks for, e.g. `session_name` or a user
+/// option like `@ws-paused`, and the parser for the lines tmux prints.
+///
+/// Every value is requested with the `q:` modifier, which backslash-escapes
+/// `|` along with other shell characters, so names and paths containing the
+/// separator come back intact.
+pub struct Format<'a> {
+    fields: &'a [&'a str],
+}
+
+impl<'a> Format<'a> {
+    pub fn new(fields: &'a [&'a str]) -> Self {
+        Self { fields }
+    }
+
+    /// The format string to pass to `-F`.
+    pub fn template(&self) -> String {
+        self.fields
+            .iter()
+            .map(|field| format!("#{{q:{}}}", field))
+            .collect::<Vec<_>>()
+            .join(&SEPARATOR.to_string())
+    }
+
+    /// The rows of a query's output, one per line.
+    pub fn rows(&self, stdout: &[u8]) -> Vec<Row<'a>> {
+        String::from_utf8_lossy(stdout)
+            .lines()
+            .map(|line| self.parse(line))
+            .collect()
+    }
+
+    /// Splits `line` at unescaped separators. Fields missing at the end of
+    /// the line read as empty, and values past the last field are dropped.
+    pub fn parse(&self, line: &str) -> Row<'a> {
+        let mut values = vec![String::new()];
+        let mut chars = line.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => values.last_mut().unwrap().extend(chars.next()),
+                SEPARATOR => values.push(String::new()),
+                c => values.last_mut().unwrap().push(c),
+            }
+        }
+        values.resize(self.fields.len(), String::new());
+        Row {
+            fields: self.fields,
+            values,
+        }
+    }
+}
+
+/// One line of a query's output.
+pub struct Row<'a> {
+    fields: &'a [&'a str],
+    values: Vec<String>,
+}
+
+impl Row<'_> {
+    /// The value of `field`, empty if tmux has none for it: an unset option,
+    /// or a format this tmux version doesn't know.
+    pub fn get(&self, field: &str) -> &str {
+        let index = self
+            .fields
+            .iter()
+            .position(|f| *f == field)
+            .unwrap_or_else(|| panic!("{} is not part of the format", field));
+        &self.values[index]
+    }
+
+    pub fn parse<T: std::str::FromStr>(&self, field: &str) -> Option<T> {
+        self.get(field).parse().ok()
+    }
+
+    /// Whether a flag or count such as `window_active` or `session_attached`
+    /// is set.
+    pub fn flag(&self, field: &str) -> bool {
+        !matches!(self.get(field), "" | "0")
+    }
+}
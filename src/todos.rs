@@ -0,0 +1,143 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use crate::{is_fresh, parallel_map, Result};
+
+/// Seconds a project's markers are reused before it is searched again.
+const TTL_SECONDS: i64 = 600;
+/// Markers kept per project; the rest are dropped.
+const PER_PROJECT: usize = 200;
+/// Projects searched at the same time.
+const SEARCH_CONCURRENCY: usize = 8;
+/// Characters of a marker's line kept for the picker.
+const TEXT_LIMIT: usize = 160;
+/// Directories searched without git that hold no code of the project's own.
+const SKIPPED_DIRS: &[&str] = &[".git", "node_modules", "target", "vendor", "dist", "build"];
+
+/// A `TODO` or `FIXME` comment in a project.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Todo {
+    /// Path relative to the project.
+    pub file: String,
+    pub line: u32,
+    /// The line, trimmed and shortened.
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Entry {
+    scanned_at: i64,
+    todos: Vec<Todo>,
+}
+
+/// The markers of each project in `projects`, from `cache_file` where it has
+/// a recent enough list (unless `refresh`), otherwise searched for and,
+/// with `store`, stored there. Projects are searched a few at a time.
+pub fn cached(
+    projects: &[&str],
+    cache_file: &Path,
+    refresh: bool,
+    store: bool,
+    now: i64,
+) -> Result<Vec<Vec<Todo>>> {
+    let mut cache: HashMap<String, Entry> = fs::read_to_string(cache_file)
+        .ok()
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default();
+
+    let stale: Vec<&str> = projects
+        .iter()
+        .copied()
+        .filter(|path| {
+            refresh
+                || !cache
+                    .get(*path)
+                    .is_some_and(|entry| is_fresh(entry.scanned_at, TTL_SECONDS, now))
+        })
+        .collect();
+    let found = parallel_map(&stale, SEARCH_CONCURRENCY, |path| search(Path::new(path)));
+    for (path, todos) in stale.iter().zip(found) {
+        cache.insert(
+            path.to_string(),
+            Entry {
+                scanned_at: now,
+                todos: todos.unwrap_or_default(),
+            },
+        );
+    }
+
+    if store && !stale.is_empty() {
+        // Projects no longer asked about are dropped along the way.
+        cache.retain(|path, entry| {
+            projects.contains(&path.as_str()) || is_fresh(entry.scanned_at, TTL_SECONDS, now)
+        });
+        if let Some(parent) = cache_file.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(cache_file, serde_json::to_string(&cache)?)?;
+    }
+    Ok(projects
+        .iter()
+        .map(|path| {
+            cache
+                .get(*path)
+                .map(|entry| entry.todos.clone())
+                .unwrap_or_default()
+        })
+        .collect())
+}
+
+/// Searches the project with `git grep`, which skips ignored and binary
+/// files, or with `grep -r` outside git repositories.
+fn search(project: &Path) -> Vec<Todo> {
+    let pattern = "TODO|FIXME";
+    let output = if project.join(".git").exists() {
+        Command::new("git")
+            .arg("-C")
+            .arg(project)
+            .args([
+                "grep",
+                "--untracked",
+                "-n",
+                "-I",
+                "-w",
+                "-E",
+                "--no-color",
+                pattern,
+            ])
+            .output()
+    } else {
+        let mut command = Command::new("grep");
+        command.args(["-r", "-n", "-I", "-w", "-E"]);
+        for dir in SKIPPED_DIRS {
+            command.arg(format!("--exclude-dir={}", dir));
+        }
+        command
+            .args(["-e", pattern, "."])
+            .current_dir(project)
+            .output()
+    };
+    let Ok(output) = output else {
+        return Vec::new();
+    };
+    // Exit status 1 means nothing matched.
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(parse_line)
+        .take(PER_PROJECT)
+        .collect()
+}
+
+/// A `file:line:text` line of grep output.
+fn parse_line(line: &str) -> Option<Todo> {
+    let (file, rest) = line.split_once(':')?;
+    let (number, text) = rest.split_once(':')?;
+    Some(Todo {
+        file: file.trim_start_matches("./").to_string(),
+        line: number.parse().ok()?,
+        text: text.trim().chars().take(TEXT_LIMIT).collect(),
+    })
+}
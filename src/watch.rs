@@ -0,0 +1,186 @@
+use std::collections::HashSet;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::messages;
+
+/// Program that reports file system events: `inotifywait` (inotify-tools)
+/// on Linux, `fswatch` (FSEvents) on macOS.
+#[cfg(target_os = "macos")]
+pub const PROGRAM: &str = "fswatch";
+#[cfg(not(target_os = "macos"))]
+pub const PROGRAM: &str = "inotifywait";
+
+/// How often modification times are compared when `PROGRAM` is missing.
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// fswatch flags for an entry appearing in, or leaving, a directory.
+const FSWATCH_ENTRY_FLAGS: &[&str] = &["Created", "Removed", "Renamed", "MovedFrom", "MovedTo"];
+
+/// Watches directories for entries being added, removed or renamed, and
+/// files for being rewritten. Events come from `PROGRAM`; without it the
+/// paths' modification times are compared every `POLL_INTERVAL`.
+pub struct Watcher {
+    paths: Vec<PathBuf>,
+    child: Option<Child>,
+    events: Option<Receiver<()>>,
+    mtimes: Vec<Option<SystemTime>>,
+}
+
+impl Watcher {
+    pub fn new(paths: Vec<PathBuf>, use_program: bool) -> Self {
+        let mut watcher = Self {
+            mtimes: modification_times(&paths),
+            paths,
+            child: None,
+            events: None,
+        };
+        if use_program && !watcher.paths.is_empty() {
+            if let Err(e) = watcher.spawn() {
+                messages::warn(messages::WATCHER_FAILED.format(&[&PROGRAM, &e]));
+            }
+        }
+        watcher
+    }
+
+    pub fn paths(&self) -> &[PathBuf] {
+        &self.paths
+    }
+
+    /// Whether changes are reported by `PROGRAM` rather than polled for.
+    pub fn is_evented(&self) -> bool {
+        self.events.is_some()
+    }
+
+    fn spawn(&mut self) -> std::io::Result<()> {
+        let mut command = Command::new(PROGRAM);
+        if cfg!(target_os = "macos") {
+            command.args(["--format", "%f|%p"]);
+        } else {
+            command.args([
+                "-m",
+                "-q",
+                "--format",
+                "%e|%w|%f",
+                "-e",
+                "create",
+                "-e",
+                "delete",
+                "-e",
+                "moved_from",
+                "-e",
+                "moved_to",
+                "-e",
+                "delete_self",
+                "-e",
+                "move_self",
+                "-e",
+                "close_write",
+            ]);
+        }
+        let mut child = command
+            .args(&self.paths)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let watched: HashSet<PathBuf> = self.paths.iter().cloned().collect();
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+                if relevant(&line, &watched) && sender.send(()).is_err() {
+                    break;
+                }
+            }
+        });
+        self.child = Some(child);
+        self.events = Some(receiver);
+        Ok(())
+    }
+
+    /// Blocks until a watched path changes or `timeout` passes, and returns
+    /// whether something changed.
+    pub fn wait(&mut self, timeout: Duration) -> bool {
+        if let Some(events) = &self.events {
+            match events.recv_timeout(timeout) {
+                Ok(()) => {
+                    // One change usually comes with more; they are all
+                    // covered by the same rescan.
+                    while events.try_recv().is_ok() {}
+                    return true;
+                }
+                Err(RecvTimeoutError::Timeout) => return false,
+                Err(RecvTimeoutError::Disconnected) => {
+                    messages::warn(messages::WATCHER_EXITED.format(&[&PROGRAM]));
+                    self.stop();
+                }
+            }
+        }
+
+        let deadline = Instant::now() + timeout;
+        loop {
+            let now = Instant::now();
+            if now >= deadline {
+                return false;
+            }
+            std::thread::sleep(POLL_INTERVAL.min(deadline - now));
+            let mtimes = modification_times(&self.paths);
+            if mtimes != self.mtimes {
+                self.mtimes = mtimes;
+                return true;
+            }
+        }
+    }
+
+    fn stop(&mut self) {
+        if let Some(mut child) = self.child.take() {
+            child.kill().ok();
+            child.wait().ok();
+        }
+        self.events = None;
+    }
+}
+
+impl Drop for Watcher {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+fn modification_times(paths: &[PathBuf]) -> Vec<Option<SystemTime>> {
+    paths
+        .iter()
+        .map(|path| path.metadata().and_then(|m| m.modified()).ok())
+        .collect()
+}
+
+/// Whether an event line changes what a scan finds: a watched path itself
+/// changed, or an entry came or went in a watched directory. Files written
+/// in a watched directory don't count, and neither do fswatch's events
+/// deeper down, which it reports recursively.
+fn relevant(line: &str, watched: &HashSet<PathBuf>) -> bool {
+    let Some((flags, path)) = line.split_once('|') else {
+        return false;
+    };
+    if cfg!(target_os = "macos") {
+        let path = Path::new(path);
+        watched.contains(path)
+            || path.parent().is_some_and(|parent| watched.contains(parent))
+                && flags
+                    .split_whitespace()
+                    .any(|flag| FSWATCH_ENTRY_FLAGS.contains(&flag))
+    } else {
+        // inotifywait only reports on watched paths: the watched path, then
+        // the entry in it if the event is about one.
+        let entry = path.split_once('|').map_or("", |(_, entry)| entry);
+        entry.is_empty() || !flags.contains("CLOSE_WRITE")
+    }
+}
@@ -0,0 +1,279 @@
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::os::unix::process::CommandExt;
+use std::process::Command;
+
+use serde::Deserialize;
+
+use crate::{
+    layout_directory, messages, shell_quote, Layout, LayoutWindow, Multiplexer, Result, SessionInfo,
+};
+
+/// Set in every pane WezTerm starts, to the pane's id.
+const WEZTERM_PANE_ENV: &str = "WEZTERM_PANE";
+/// User variable set to ask WezTerm to switch workspaces; see the README for
+/// the `user-var-changed` handler that acts on it.
+const SWITCH_USER_VAR: &str = "WS_WORKSPACE";
+
+/// Sessions as WezTerm workspaces (`backend = "wezterm"`), driven through
+/// `wezterm cli`. Each layout window becomes a tab.
+pub struct WeztermClient;
+
+/// One entry of `wezterm cli list --format json`.
+#[derive(Debug, Deserialize)]
+struct PaneEntry {
+    window_id: u64,
+    pane_id: u64,
+    workspace: String,
+}
+
+impl WeztermClient {
+    fn command() -> Command {
+        let mut command = Command::new("wezterm");
+        command.arg("cli");
+        command
+    }
+
+    fn panes() -> Result<Vec<PaneEntry>> {
+        let output = Self::command()
+            .args(["list", "--format", "json"])
+            .output()
+            .map_err(|e| messages::RUN_FAILED.format(&[&"wezterm", &e]))?;
+        if !output.status.success() {
+            return Err(format!(
+                "wezterm cli list failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )
+            .into());
+        }
+        Ok(serde_json::from_slice(&output.stdout)?)
+    }
+
+    /// Runs a `wezterm cli` command that prints a pane id, and returns it.
+    fn pane_id(command: &mut Command) -> Result<u64> {
+        let output = command.output()?;
+        if !output.status.success() {
+            return Err(String::from_utf8_lossy(&output.stderr).trim().into());
+        }
+        let id = String::from_utf8_lossy(&output.stdout);
+        id.trim()
+            .parse()
+            .map_err(|_| format!("Unexpected pane id from wezterm: {}", id.trim()).into())
+    }
+
+    fn run(args: &[&str]) -> Result<()> {
+        let output = Self::command().args(args).output()?;
+        if !output.status.success() {
+            return Err(format!(
+                "wezterm cli {} failed: {}",
+                args[0],
+                String::from_utf8_lossy(&output.stderr).trim()
+            )
+            .into());
+        }
+        Ok(())
+    }
+
+    /// Adds the layout window's extra panes next to `pane` and goes back to
+    /// it.
+    fn split_panes(
+        pane: u64,
+        path: &str,
+        window: &LayoutWindow,
+        env: &[(String, String)],
+    ) -> Result<()> {
+        let pane_id = pane.to_string();
+        for spec in &window.panes {
+            let mut command = Self::command();
+            command.args(["split-pane", "--pane-id", &pane_id]);
+            if spec.horizontal {
+                command.arg("--right");
+            } else {
+                command.arg("--bottom");
+            }
+            command.args(["--cwd", &layout_directory(path, &spec.path)]);
+            command.args(program(&spec.command, env));
+            Self::pane_id(&mut command)?;
+        }
+        if !window.panes.is_empty() {
+            Self::run(&["activate-pane", "--pane-id", &pane_id])?;
+        }
+        Ok(())
+    }
+}
+
+impl Multiplexer for WeztermClient {
+    fn is_inside(&self) -> bool {
+        std::env::var_os(WEZTERM_PANE_ENV).is_some()
+    }
+
+    fn current_session(&self) -> Result<String> {
+        let pane: u64 = std::env::var(WEZTERM_PANE_ENV)
+            .ok()
+            .and_then(|id| id.parse().ok())
+            .ok_or("Not in a WezTerm pane")?;
+        Self::panes()?
+            .into_iter()
+            .find(|entry| entry.pane_id == pane)
+            .map(|entry| entry.workspace)
+            .ok_or_else(|| format!("WezTerm doesn't know pane {}", pane).into())
+    }
+
+    /// Workspaces with at least one pane. WezTerm doesn't report when a
+    /// workspace was last active.
+    fn list_sessions(&self) -> Result<Vec<SessionInfo>> {
+        let mut names: Vec<String> = Vec::new();
+        for entry in Self::panes()? {
+            if !names.contains(&entry.workspace) {
+                names.push(entry.workspace);
+            }
+        }
+        Ok(names
+            .into_iter()
+            .map(|name| SessionInfo {
+                name,
+                last_active: 0,
+                paused: false,
+            })
+            .collect())
+    }
+
+    fn has_session(&self, name: &str) -> Result<bool> {
+        Ok(Self::panes()?.iter().any(|entry| entry.workspace == name))
+    }
+
+    /// Spawns a window in workspace `name` with one tab per layout window.
+    /// `wezterm cli` can't pass an environment, so every pane's program is
+    /// started through `env`.
+    fn create_session(
+        &self,
+        name: &str,
+        path: &str,
+        layout: &Layout,
+        env: &[(String, String)],
+    ) -> Result<()> {
+        let Some((first, rest)) = layout.windows.split_first() else {
+            return Err(format!("Layout '{}' has no windows", layout.name).into());
+        };
+
+        let mut command = Self::command();
+        command.args(["spawn", "--new-window", "--workspace", name]);
+        command.args(["--cwd", &layout_directory(path, &first.path)]);
+        command.args(program(&first.command, env));
+        let first_pane = Self::pane_id(&mut command)
+            .map_err(|e| format!("Failed to create WezTerm workspace '{}': {}", name, e))?;
+        let window_id = Self::panes()?
+            .into_iter()
+            .find(|entry| entry.pane_id == first_pane)
+            .map(|entry| entry.window_id.to_string())
+            .ok_or_else(|| format!("WezTerm lost the window of workspace '{}'", name))?;
+
+        let mut tabs = vec![(first, first_pane)];
+        for window in rest {
+            let mut command = Self::command();
+            command.args(["spawn", "--window-id", &window_id]);
+            command.args(["--cwd", &layout_directory(path, &window.path)]);
+            command.args(program(&window.command, env));
+            tabs.push((window, Self::pane_id(&mut command)?));
+        }
+
+        let mut focus = first_pane;
+        for (window, pane) in tabs {
+            // Tab titles need WezTerm 20230408 or later; older ones keep
+            // the program's title.
+            Self::run(&[
+                "set-tab-title",
+                "--pane-id",
+                &pane.to_string(),
+                &window.name,
+            ])
+            .ok();
+            Self::split_panes(pane, path, window, env)?;
+            if window.index == layout.focus {
+                focus = pane;
+            }
+        }
+        Self::run(&["activate-pane", "--pane-id", &focus.to_string()])
+    }
+
+    fn kill_session(&self, name: &str) -> Result<()> {
+        let panes: Vec<PaneEntry> = Self::panes()?
+            .into_iter()
+            .filter(|entry| entry.workspace == name)
+            .collect();
+        if panes.is_empty() {
+            return Err(format!("No WezTerm workspace '{}'", name).into());
+        }
+        for entry in panes {
+            Self::run(&["kill-pane", "--pane-id", &entry.pane_id.to_string()])?;
+        }
+        Ok(())
+    }
+
+    /// `wezterm cli` has no way to switch workspaces, so this sets a user
+    /// variable on the current pane for the config's `user-var-changed`
+    /// handler to act on.
+    fn switch_client(&self, name: &str) -> Result<()> {
+        let mut tty = OpenOptions::new()
+            .write(true)
+            .open("/dev/tty")
+            .map_err(|e| format!("Can't ask WezTerm to switch to '{}': {}", name, e))?;
+        write!(
+            tty,
+            "\x1b]1337;SetUserVar={}={}\x07",
+            SWITCH_USER_VAR,
+            base64(name.as_bytes())
+        )?;
+        Ok(())
+    }
+
+    /// Connects to the `unix` mux domain, where the workspaces live when no
+    /// WezTerm window is open.
+    fn exec_attach(&self, name: &str) -> Result<()> {
+        let error = Command::new("wezterm")
+            .args(["connect", "--workspace", name, "unix"])
+            .exec();
+        Err(messages::ATTACH_FAILED.format(&[&name, &error]).into())
+    }
+
+    fn attach_command(&self, name: &str) -> String {
+        format!("wezterm connect --workspace {} unix", shell_quote(name))
+    }
+}
+
+/// Arguments after `--` for `wezterm cli spawn` and `split-pane`: `command`
+/// through `sh` with `env` set, or nothing for the default program.
+fn program(command: &str, env: &[(String, String)]) -> Vec<String> {
+    if command.is_empty() && env.is_empty() {
+        return Vec::new();
+    }
+    let mut args = vec!["--".to_string(), "env".to_string()];
+    args.extend(env.iter().map(|(key, value)| format!("{}={}", key, value)));
+    args.push("sh".to_string());
+    args.push("-c".to_string());
+    if command.is_empty() {
+        args.push("exec \"${SHELL:-sh}\"".to_string());
+    } else {
+        args.push(command.to_string());
+    }
+    args
+}
+
+/// Standard base64, which is how terminals expect user variable values.
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::new();
+    for chunk in bytes.chunks(3) {
+        let value = chunk.iter().enumerate().fold(0u32, |value, (i, byte)| {
+            value | (*byte as u32) << (16 - 8 * i)
+        });
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(value >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
@@ -0,0 +1,183 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::config::WORKTREE_DIR;
+use crate::{messages, Result};
+
+/// A branch of a repository as offered by `ws branch`.
+#[derive(Debug, Clone)]
+pub struct Branch {
+    pub name: String,
+    /// Where the branch is checked out, if anywhere.
+    pub worktree: Option<PathBuf>,
+    /// Only known as a remote-tracking branch; checking it out creates the
+    /// local branch.
+    pub remote_only: bool,
+}
+
+/// The main working tree of the repository `path` belongs to, which is
+/// `path` itself unless it is a linked worktree.
+pub fn main_worktree(path: &str) -> Result<PathBuf> {
+    let common_dir = git(
+        Path::new(path),
+        &["rev-parse", "--path-format=absolute", "--git-common-dir"],
+    )?;
+    let common_dir = PathBuf::from(common_dir.trim());
+    common_dir
+        .parent()
+        .map(Path::to_path_buf)
+        .ok_or_else(|| format!("Unexpected git directory {}", common_dir.display()).into())
+}
+
+/// Local branches, then remote branches without a local one, each with the
+/// worktree it is checked out in.
+pub fn branches(repo: &Path) -> Result<Vec<Branch>> {
+    let mut worktrees: Vec<(String, PathBuf)> = Vec::new();
+    let mut path = None;
+    for line in git(repo, &["worktree", "list", "--porcelain"])?.lines() {
+        if let Some(worktree) = line.strip_prefix("worktree ") {
+            path = Some(PathBuf::from(worktree));
+        } else if let Some(branch) = line.strip_prefix("branch refs/heads/") {
+            if let Some(path) = path.take() {
+                worktrees.push((branch.to_string(), path));
+            }
+        }
+    }
+    let worktree = |name: &str| {
+        worktrees
+            .iter()
+            .find(|(branch, _)| branch == name)
+            .map(|(_, path)| path.clone())
+    };
+
+    let mut branches: Vec<Branch> = git(
+        repo,
+        &["for-each-ref", "--format=%(refname:short)", "refs/heads"],
+    )?
+    .lines()
+    .map(|name| Branch {
+        name: name.to_string(),
+        worktree: worktree(name),
+        remote_only: false,
+    })
+    .collect();
+
+    let remotes = git(
+        repo,
+        &[
+            "for-each-ref",
+            "--format=%(refname:lstrip=3)",
+            "refs/remotes",
+        ],
+    )?;
+    for name in remotes.lines() {
+        if name == "HEAD" || name.is_empty() || branches.iter().any(|b| b.name == name) {
+            continue;
+        }
+        branches.push(Branch {
+            name: name.to_string(),
+            worktree: None,
+            remote_only: true,
+        });
+    }
+    Ok(branches)
+}
+
+/// Whether `path` is a bare repository rather than a working tree: a bare
+/// repository itself, or a directory holding one in `.bare`.
+pub fn is_bare(path: &Path) -> bool {
+    path.join(".bare").is_dir() || (path.join("HEAD").is_file() && path.join("objects").is_dir())
+}
+
+/// Linked worktrees of the repository at `path` with the branch checked out
+/// in each (the directory name if detached). Repositories without any are
+/// recognized from their git directory, without running git.
+pub fn linked_worktrees(path: &Path) -> Vec<(String, PathBuf)> {
+    let has_worktrees = [".git/worktrees", ".bare/worktrees", "worktrees"]
+        .iter()
+        .any(|dir| path.join(dir).is_dir());
+    if !has_worktrees {
+        return Vec::new();
+    }
+    let Ok(list) = git(path, &["worktree", "list", "--porcelain"]) else {
+        return Vec::new();
+    };
+
+    let mut worktrees = Vec::new();
+    // Entries are blocks of lines separated by a blank line.
+    for entry in list.split("\n\n") {
+        let mut worktree = None;
+        let mut branch = None;
+        let mut bare = false;
+        for line in entry.lines() {
+            if let Some(path) = line.strip_prefix("worktree ") {
+                worktree = Some(PathBuf::from(path));
+            } else if let Some(name) = line.strip_prefix("branch refs/heads/") {
+                branch = Some(name.to_string());
+            } else if line == "bare" {
+                bare = true;
+            }
+        }
+        let Some(worktree) = worktree else {
+            continue;
+        };
+        if bare || worktree == path {
+            continue;
+        }
+        let branch = branch.unwrap_or_else(|| {
+            worktree
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string()
+        });
+        worktrees.push((branch, worktree));
+    }
+    worktrees
+}
+
+/// Where `ws branch` puts the worktree for `branch`: next to the project in
+/// its category's `.worktrees` directory, which scans skip.
+pub fn worktree_path(repo: &Path, branch: &str) -> PathBuf {
+    let name = repo.file_name().unwrap_or_default();
+    repo.parent()
+        .unwrap_or(repo)
+        .join(WORKTREE_DIR)
+        .join(name)
+        .join(branch.replace('/', "-"))
+}
+
+/// Checks `branch` out into a new worktree at `path`. A remote-only branch
+/// gets a local branch tracking it.
+pub fn add_worktree(repo: &Path, path: &Path, branch: &str) -> Result<()> {
+    let status = Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .args(["worktree", "add"])
+        .arg(path)
+        .arg(branch)
+        .status()?;
+    if !status.success() {
+        return Err(format!("Failed to create a worktree for {}", branch).into());
+    }
+    Ok(())
+}
+
+fn git(repo: &Path, args: &[&str]) -> Result<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .args(args)
+        .output()
+        .map_err(|e| messages::RUN_FAILED.format(&[&"git", &e]))?;
+    if !output.status.success() {
+        return Err(format!(
+            "git {} failed in {}: {}",
+            args[0],
+            repo.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )
+        .into());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
@@ -0,0 +1,371 @@
+use crate::Result;
+
+/// A YAML document, as far as tmuxinator and tmuxp project files use YAML:
+/// block mappings and sequences, plain and quoted scalars, flow sequences
+/// and mappings of scalars, and `|` / `>` block scalars. Anchors, aliases
+/// and tags are rejected.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    String(String),
+    List(Vec<Value>),
+    /// Entries in file order.
+    Map(Vec<(String, Value)>),
+}
+
+impl Value {
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Self::Map(entries) => entries.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Converts JSON, which tmuxp accepts as well, keeping the order of keys
+    /// only as far as serde_json does.
+    pub fn from_json(json: serde_json::Value) -> Self {
+        match json {
+            serde_json::Value::Null => Self::Null,
+            serde_json::Value::Bool(b) => Self::String(b.to_string()),
+            serde_json::Value::Number(n) => Self::String(n.to_string()),
+            serde_json::Value::String(s) => Self::String(s),
+            serde_json::Value::Array(items) => {
+                Self::List(items.into_iter().map(Self::from_json).collect())
+            }
+            serde_json::Value::Object(map) => Self::Map(
+                map.into_iter()
+                    .map(|(k, v)| (k, Self::from_json(v)))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+struct Line {
+    number: usize,
+    indent: usize,
+    /// Without indentation and comment.
+    text: String,
+    /// The whole line, for block scalars.
+    raw: String,
+}
+
+pub fn parse(source: &str) -> Result<Value> {
+    let mut lines = Vec::new();
+    for (index, raw) in source.lines().enumerate() {
+        let text = strip_comment(raw);
+        let trimmed = text.trim_end();
+        let content = trimmed.trim_start();
+        if content.is_empty() || content == "---" || content == "..." {
+            // Blank lines still belong to block scalars.
+            lines.push(Line {
+                number: index + 1,
+                indent: usize::MAX,
+                text: String::new(),
+                raw: raw.to_string(),
+            });
+            continue;
+        }
+        if raw.starts_with('\t') {
+            return Err(format!("line {}: tabs can't indent YAML", index + 1).into());
+        }
+        lines.push(Line {
+            number: index + 1,
+            indent: trimmed.len() - content.len(),
+            text: content.to_string(),
+            raw: raw.to_string(),
+        });
+    }
+
+    let mut parser = Parser { lines, next: 0 };
+    parser.skip_blank();
+    if parser.done() {
+        return Ok(Value::Null);
+    }
+    let indent = parser.lines[parser.next].indent;
+    let value = parser.block(indent)?;
+    parser.skip_blank();
+    if let Some(line) = parser.lines.get(parser.next) {
+        return Err(format!("line {}: unexpected indentation", line.number).into());
+    }
+    Ok(value)
+}
+
+struct Parser {
+    lines: Vec<Line>,
+    next: usize,
+}
+
+impl Parser {
+    fn done(&self) -> bool {
+        self.next >= self.lines.len()
+    }
+
+    fn skip_blank(&mut self) {
+        while self
+            .lines
+            .get(self.next)
+            .is_some_and(|line| line.indent == usize::MAX)
+        {
+            self.next += 1;
+        }
+    }
+
+    /// The next non-blank line's indentation, if there is one.
+    fn peek_indent(&mut self) -> Option<usize> {
+        self.skip_blank();
+        self.lines.get(self.next).map(|line| line.indent)
+    }
+
+    fn block(&mut self, indent: usize) -> Result<Value> {
+        let line = &self.lines[self.next];
+        if is_sequence_item(&line.text) {
+            self.sequence(indent)
+        } else if split_key(&line.text).is_some() {
+            self.mapping(indent)
+        } else {
+            let (number, text) = (line.number, line.text.clone());
+            self.next += 1;
+            scalar(&text, number)
+        }
+    }
+
+    fn sequence(&mut self, indent: usize) -> Result<Value> {
+        let mut items = Vec::new();
+        while self.peek_indent() == Some(indent) && is_sequence_item(&self.lines[self.next].text) {
+            let line = &mut self.lines[self.next];
+            let rest = line.text[1..].trim_start().to_string();
+            if rest.is_empty() {
+                self.next += 1;
+                items.push(match self.peek_indent() {
+                    Some(child) if child > indent => self.block(child)?,
+                    _ => Value::Null,
+                });
+            } else {
+                // The item's content continues as if it started a line of its
+                // own, so `- key: value` and the keys below it form a mapping.
+                line.indent = indent + line.text.len() - rest.len();
+                line.text = rest;
+                let child = line.indent;
+                items.push(self.block(child)?);
+            }
+        }
+        Ok(Value::List(items))
+    }
+
+    fn mapping(&mut self, indent: usize) -> Result<Value> {
+        let mut entries = Vec::new();
+        while let Some(current) = self.peek_indent() {
+            let line = &self.lines[self.next];
+            if current < indent || (current == indent && is_sequence_item(&line.text)) {
+                break;
+            }
+            if current > indent {
+                return Err(format!("line {}: unexpected indentation", line.number).into());
+            }
+            let number = line.number;
+            let Some((key, rest)) = split_key(&line.text) else {
+                return Err(format!("line {}: expected `key: value`", number).into());
+            };
+            let key = match scalar(&key, number)? {
+                Value::String(key) => key,
+                _ => return Err(format!("line {}: unsupported key", number).into()),
+            };
+            self.next += 1;
+
+            let value = if rest.is_empty() {
+                match self.peek_indent() {
+                    Some(child) if child > indent => self.block(child)?,
+                    // A sequence may sit at the same indentation as its key.
+                    Some(child)
+                        if child == indent && is_sequence_item(&self.lines[self.next].text) =>
+                    {
+                        self.sequence(indent)?
+                    }
+                    _ => Value::Null,
+                }
+            } else if rest.starts_with('|') || rest.starts_with('>') {
+                Value::String(self.block_scalar(indent, rest.starts_with('>')))
+            } else {
+                scalar(&rest, number)?
+            };
+            entries.push((key, value));
+        }
+        Ok(Value::Map(entries))
+    }
+
+    /// The lines indented deeper than `parent`, joined with newlines, or
+    /// with spaces when `folded`.
+    fn block_scalar(&mut self, parent: usize, folded: bool) -> String {
+        let mut raw = Vec::new();
+        while let Some(line) = self.lines.get(self.next) {
+            if line.indent != usize::MAX && line.indent <= parent {
+                break;
+            }
+            raw.push(line.raw.as_str());
+            self.next += 1;
+        }
+        while raw.last().is_some_and(|line| line.trim().is_empty()) {
+            raw.pop();
+        }
+        let strip = raw
+            .iter()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| line.len() - line.trim_start().len())
+            .min()
+            .unwrap_or(0);
+        let lines: Vec<&str> = raw
+            .iter()
+            .map(|line| line.get(strip..).unwrap_or("").trim_end())
+            .collect();
+        lines.join(if folded { " " } else { "\n" })
+    }
+}
+
+fn is_sequence_item(text: &str) -> bool {
+    text == "-" || text.starts_with("- ")
+}
+
+/// Drops a `#` comment: one at the start of the line or after whitespace,
+/// outside quotes.
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut previous = ' ';
+    for (index, c) in line.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') if previous.is_whitespace() || "[{,:-".contains(previous) => {
+                quote = Some(c)
+            }
+            (Some(q), c) if c == q => quote = None,
+            (None, '#') if previous.is_whitespace() => return &line[..index],
+            _ => {}
+        }
+        previous = c;
+    }
+    line
+}
+
+/// Splits `key: value` at the first `: ` (or a trailing `:`) outside quotes
+/// and brackets.
+fn split_key(text: &str) -> Option<(String, String)> {
+    let mut quote = None;
+    let mut depth = 0;
+    let chars: Vec<(usize, char)> = text.char_indices().collect();
+    for (position, &(index, c)) in chars.iter().enumerate() {
+        match (quote, c) {
+            (None, '"' | '\'') if index == 0 => quote = Some(c),
+            (Some(q), c) if c == q => quote = None,
+            (None, '[' | '{') if index == 0 || depth > 0 => depth += 1,
+            (None, ']' | '}') if depth > 0 => depth -= 1,
+            (None, ':') if depth == 0 => {
+                let next = chars.get(position + 1).map(|&(_, c)| c);
+                if next.is_none_or(char::is_whitespace) {
+                    let key = text[..index].trim().to_string();
+                    let rest = text[index + 1..].trim().to_string();
+                    return (!key.is_empty()).then_some((key, rest));
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+fn scalar(text: &str, line: usize) -> Result<Value> {
+    let text = text.trim();
+    match text.chars().next() {
+        None => Ok(Value::Null),
+        Some('"') => double_quoted(text, line).map(Value::String),
+        Some('\'') => single_quoted(text, line).map(Value::String),
+        Some('[') => {
+            let inner = enclosed(text, '[', ']', line)?;
+            split_flow(inner)
+                .iter()
+                .map(|item| scalar(item, line))
+                .collect::<Result<Vec<_>>>()
+                .map(Value::List)
+        }
+        Some('{') => {
+            let inner = enclosed(text, '{', '}', line)?;
+            let mut entries = Vec::new();
+            for item in split_flow(inner) {
+                let (key, value) = split_key(&item).unwrap_or((item, String::new()));
+                let Value::String(key) = scalar(&key, line)? else {
+                    return Err(format!("line {}: unsupported key", line).into());
+                };
+                entries.push((key, scalar(&value, line)?));
+            }
+            Ok(Value::Map(entries))
+        }
+        Some('&' | '*' | '!') => Err(format!(
+            "line {}: YAML anchors, aliases and tags aren't supported",
+            line
+        )
+        .into()),
+        _ if matches!(text, "~" | "null" | "Null" | "NULL") => Ok(Value::Null),
+        _ => Ok(Value::String(text.to_string())),
+    }
+}
+
+fn enclosed(text: &str, open: char, close: char, line: usize) -> Result<&str> {
+    text.strip_prefix(open)
+        .and_then(|rest| rest.strip_suffix(close))
+        .ok_or_else(|| format!("line {}: unclosed {}", line, open).into())
+}
+
+/// Splits the inside of a flow collection at commas outside quotes and
+/// nested brackets.
+fn split_flow(inner: &str) -> Vec<String> {
+    let mut items = Vec::new();
+    let mut current = String::new();
+    let mut quote = None;
+    let mut depth = 0;
+    for c in inner.chars() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), c) if c == q => quote = None,
+            (None, '[' | '{') => depth += 1,
+            (None, ']' | '}') => depth -= 1,
+            (None, ',') if depth == 0 => {
+                items.push(std::mem::take(&mut current).trim().to_string());
+                continue;
+            }
+            _ => {}
+        }
+        current.push(c);
+    }
+    if !current.trim().is_empty() {
+        items.push(current.trim().to_string());
+    }
+    items
+}
+
+fn double_quoted(text: &str, line: usize) -> Result<String> {
+    let inner = enclosed(text, '"', '"', line)?;
+    let mut value = String::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            value.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => value.push('\n'),
+            Some('t') => value.push('\t'),
+            Some(c) => value.push(c),
+            None => return Err(format!("line {}: unfinished escape", line).into()),
+        }
+    }
+    Ok(value)
+}
+
+fn single_quoted(text: &str, line: usize) -> Result<String> {
+    Ok(enclosed(text, '\'', '\'', line)?.replace("''", "'"))
+}
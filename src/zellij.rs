@@ -0,0 +1,203 @@
+use std::fs;
+use std::os::unix::process::CommandExt;
+use std::process::Command;
+
+use crate::{
+    layout_directory, messages, shell_quote, Layout, LayoutWindow, Multiplexer, Result, SessionInfo,
+};
+
+/// Set in every zellij pane; `ZELLIJ_SESSION_NAME` names the session.
+const ZELLIJ_ENV: &str = "ZELLIJ";
+const ZELLIJ_SESSION_ENV: &str = "ZELLIJ_SESSION_NAME";
+
+/// Sessions in zellij (`backend = "zellij"`), driven through its CLI. Layouts
+/// are translated into a KDL layout with one tab per window.
+pub struct ZellijClient;
+
+impl ZellijClient {
+    fn command() -> Command {
+        Command::new("zellij")
+    }
+
+    /// The layout as zellij KDL, with zellij's tab and status bars around
+    /// every tab.
+    fn layout_kdl(path: &str, layout: &Layout) -> String {
+        let mut kdl = String::from("layout {\n");
+        kdl.push_str("    default_tab_template {\n");
+        kdl.push_str("        pane size=1 borderless=true {\n");
+        kdl.push_str("            plugin location=\"zellij:tab-bar\"\n");
+        kdl.push_str("        }\n");
+        kdl.push_str("        children\n");
+        kdl.push_str("        pane size=2 borderless=true {\n");
+        kdl.push_str("            plugin location=\"zellij:status-bar\"\n");
+        kdl.push_str("        }\n");
+        kdl.push_str("    }\n");
+        for window in &layout.windows {
+            kdl.push_str(&Self::tab_kdl(path, window, window.index == layout.focus));
+        }
+        kdl.push_str("}\n");
+        kdl
+    }
+
+    fn tab_kdl(path: &str, window: &LayoutWindow, focus: bool) -> String {
+        let mut tab = format!(
+            "    tab name={} cwd={}",
+            kdl_string(&window.name),
+            kdl_string(&layout_directory(path, &window.path))
+        );
+        if focus {
+            tab.push_str(" focus=true");
+        }
+        // zellij splits a whole tab one way; any side-by-side pane wins.
+        if window.panes.iter().any(|pane| pane.horizontal) {
+            tab.push_str(" split_direction=\"vertical\"");
+        }
+        tab.push_str(" {\n");
+        tab.push_str(&pane_kdl(&window.command, None));
+        for pane in &window.panes {
+            let cwd = layout_directory(path, &pane.path);
+            tab.push_str(&pane_kdl(&pane.command, Some(&cwd)));
+        }
+        tab.push_str("    }\n");
+        tab
+    }
+
+    fn run(args: &[&str]) -> Result<()> {
+        let output = Self::command().args(args).output()?;
+        if !output.status.success() {
+            return Err(format!(
+                "zellij {} failed: {}",
+                args[0],
+                String::from_utf8_lossy(&output.stderr).trim()
+            )
+            .into());
+        }
+        Ok(())
+    }
+}
+
+impl Multiplexer for ZellijClient {
+    fn is_inside(&self) -> bool {
+        std::env::var_os(ZELLIJ_ENV).is_some()
+    }
+
+    fn current_session(&self) -> Result<String> {
+        std::env::var(ZELLIJ_SESSION_ENV).map_err(|_| "Not in a zellij session".into())
+    }
+
+    /// Live sessions; exited ones zellij keeps around for resurrection are
+    /// left out. zellij doesn't report when a session was last attached.
+    fn list_sessions(&self) -> Result<Vec<SessionInfo>> {
+        let output = Self::command()
+            .args(["list-sessions", "--no-formatting"])
+            .output()?;
+        if !output.status.success() {
+            // Also how zellij reports that there are no sessions.
+            return Ok(Vec::new());
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter(|line| !line.contains("(EXITED"))
+            .filter_map(|line| line.split_whitespace().next())
+            .map(|name| SessionInfo {
+                name: name.to_string(),
+                last_active: 0,
+                paused: false,
+            })
+            .collect())
+    }
+
+    fn has_session(&self, name: &str) -> Result<bool> {
+        Ok(self.list_sessions()?.iter().any(|s| s.name == name))
+    }
+
+    /// Starts the session in the background from a generated layout file.
+    /// The environment goes to the zellij server, which passes it on.
+    fn create_session(
+        &self,
+        name: &str,
+        path: &str,
+        layout: &Layout,
+        env: &[(String, String)],
+    ) -> Result<()> {
+        if layout.windows.is_empty() {
+            return Err(format!("Layout '{}' has no windows", layout.name).into());
+        }
+        // An exited session of the same name would be resurrected instead.
+        Self::run(&["delete-session", name]).ok();
+
+        let layout_file =
+            std::env::temp_dir().join(format!("ws-zellij-{}-{}.kdl", std::process::id(), name));
+        fs::write(&layout_file, Self::layout_kdl(path, layout))?;
+
+        let output = Self::command()
+            .args(["attach", "--create-background", name, "options"])
+            .arg("--default-layout")
+            .arg(&layout_file)
+            .args(["--default-cwd", path])
+            .envs(env.iter().map(|(key, value)| (key, value)))
+            .output();
+        fs::remove_file(&layout_file).ok();
+
+        let output = output?;
+        if !output.status.success() {
+            return Err(format!(
+                "Failed to create zellij session '{}': {}",
+                name,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )
+            .into());
+        }
+        Ok(())
+    }
+
+    /// Kills the session and deletes it, so the name isn't resurrected with
+    /// its old layout when it is opened again.
+    fn kill_session(&self, name: &str) -> Result<()> {
+        Self::run(&["kill-session", name])?;
+        Self::run(&["delete-session", name]).ok();
+        Ok(())
+    }
+
+    fn switch_client(&self, name: &str) -> Result<()> {
+        Err(format!(
+            "zellij can't be switched to '{}' from the command line; use its session manager, \
+             or detach and run `ws attach`",
+            name
+        )
+        .into())
+    }
+
+    fn exec_attach(&self, name: &str) -> Result<()> {
+        let error = Self::command().args(["attach", name]).exec();
+        Err(messages::ATTACH_FAILED.format(&[&name, &error]).into())
+    }
+
+    fn attach_command(&self, name: &str) -> String {
+        format!("zellij attach {}", shell_quote(name))
+    }
+}
+
+/// A pane running `command` through `sh`, or the default shell.
+fn pane_kdl(command: &str, cwd: Option<&str>) -> String {
+    let mut pane = String::from("        pane");
+    if let Some(cwd) = cwd {
+        pane.push_str(&format!(" cwd={}", kdl_string(cwd)));
+    }
+    if command.is_empty() {
+        pane.push('\n');
+    } else {
+        pane.push_str(" command=\"sh\" {\n");
+        pane.push_str(&format!(
+            "            args \"-c\" {}\n",
+            kdl_string(command)
+        ));
+        pane.push_str("        }\n");
+    }
+    pane
+}
+
+fn kdl_string(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}